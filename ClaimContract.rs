@@ -1,6 +1,14 @@
+// Anchor's `Error` is large by design; every handler returns it.
+#![allow(clippy::result_large_err)]
+
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::system_program;
+use anchor_lang::{Discriminator, Event};
 use anchor_spl::token::{self, Token, TokenAccount, Transfer};
 
+declare_id!("Dist1bUtion11111111111111111111111111111111");
+
 #[account]
 #[derive(Default)]
 pub struct DistributionState {
@@ -12,14 +20,255 @@ pub struct DistributionState {
     pub max_batch_size: u64,
     pub claim_period_open: bool,
     pub paused: bool,
-    pub contributors: Vec<Contributor>,
+    /// Number of `Allocation` PDAs created so far; also the next allocation's index.
+    pub contributor_count: u32,
+    pub version: u8,
+    /// Index of the next allocation `calculate_allocations` expects.
+    pub allocation_cursor: u32,
+    /// Token balance snapshotted by the first `calculate_allocations` chunk.
+    pub allocation_total_tokens: u64,
+    /// Sum of the allocations computed so far.
+    pub allocated_amount: u64,
+    pub vesting: VestingSchedule,
+    /// Calendar unlocks; when set, these replace `vesting`.
+    pub tranches: Vec<Tranche>,
+    /// Root over `(user, allocation)` leaves for `claim_with_proof`; zero disables it.
+    pub merkle_root: [u8; 32],
+    /// Claim window set by `open_claims`; claims outside it are rejected.
+    pub claim_start: i64,
+    pub claim_end: i64,
+    /// Tokens paid out by `claim` and `claim_with_proof`.
+    pub total_claimed: u64,
+}
+
+pub const DISTRIBUTION_VERSION: u8 = 8;
+pub const MAX_TRANCHES: usize = 24;
+
+impl DistributionState {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 1 + 1 + 8 + 1 + 1 + 4 + 1 + 4 + 8 + 8
+        + VestingSchedule::LEN
+        + 4 + (MAX_TRANCHES * Tranche::LEN)
+        + 32
+        + 8 + 8
+        + 8;
+
+    /// Portion of `total` unlocked at `now`, by tranche if any are set.
+    pub fn vested_amount(&self, total: u64, now: i64) -> u64 {
+        if self.tranches.is_empty() {
+            return self.vesting.vested_amount(total, now);
+        }
+        let matured_bps: u64 = self
+            .tranches
+            .iter()
+            .filter(|t| t.unlock_timestamp <= now)
+            .map(|t| t.bps as u64)
+            .sum();
+        ((total as u128) * (matured_bps as u128) / 10_000) as u64
+    }
+
+    /// Checks claims are enabled and `now` falls in the claim window.
+    pub fn require_claimable(&self, now: i64) -> Result<()> {
+        require!(!self.paused, DistributionError::ContractPaused);
+        require!(self.claim_enabled, DistributionError::ClaimingNotEnabled);
+        require!(
+            self.claim_period_open && now >= self.claim_start && now < self.claim_end,
+            DistributionError::ClaimPeriodClosed
+        );
+        Ok(())
+    }
+
+    /// Records the vested part of `total` not yet in `claimed` as claimed at
+    /// `now` and returns it, so repeated claims release only new unlocks.
+    pub fn take_claim(&self, total: u64, claimed: &mut u64, now: i64) -> Result<u64> {
+        let vested = self.vested_amount(total, now);
+        let claim_amount = vested.saturating_sub(*claimed);
+        require!(claim_amount > 0, DistributionError::NothingToClaim);
+        *claimed = vested;
+        Ok(claim_amount)
+    }
+
+    /// Writes `allocation`'s share of the snapshotted token balance, which
+    /// must be the entry at `allocation_cursor`, and advances the cursor.
+    /// The share is written even when zero, so nothing a skipped reset left
+    /// behind survives.
+    pub fn allocate_next(&mut self, allocation: &mut Allocation) -> Result<()> {
+        require!(
+            allocation.index == self.allocation_cursor,
+            DistributionError::InvalidAllocationAccount
+        );
+        let amount = (allocation.contribution as u128)
+            .checked_mul(self.allocation_total_tokens as u128)
+            .ok_or(DistributionError::Overflow)?
+            / self.total_raised as u128;
+        let amount = u64::try_from(amount).map_err(|_| DistributionError::Overflow)?;
+
+        allocation.allocation = amount;
+        self.allocated_amount = self
+            .allocated_amount
+            .checked_add(amount)
+            .ok_or(DistributionError::Overflow)?;
+        self.allocation_cursor += 1;
+        Ok(())
+    }
+
+    /// Forgets any calculated or partly calculated allocation.
+    pub fn clear_allocations(&mut self) {
+        self.allocation_calculated = false;
+        self.claim_enabled = false;
+        self.allocation_cursor = 0;
+        self.allocation_total_tokens = 0;
+        self.allocated_amount = 0;
+    }
+
+    /// Reads a state written by any version from its raw account data.
+    /// Every field after `version` was appended by a later version, so each
+    /// group is read only if the stored version has it; the rest keep their
+    /// defaults instead of whatever bytes follow.
+    pub fn read_any_version(data: &[u8]) -> Result<Self> {
+        require!(
+            data.len() >= 8 && data[..8] == Self::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        // Versions 0 and 1 kept contributors inline, in an account far
+        // larger than any later layout.
+        let inline_contributors = data.len() >= INLINE_CONTRIBUTORS_LEN;
+        let mut data = &data[8..];
+        let mut state = Self {
+            owner: AnchorDeserialize::deserialize(&mut data)?,
+            token_mint: AnchorDeserialize::deserialize(&mut data)?,
+            total_raised: AnchorDeserialize::deserialize(&mut data)?,
+            allocation_calculated: AnchorDeserialize::deserialize(&mut data)?,
+            claim_enabled: AnchorDeserialize::deserialize(&mut data)?,
+            max_batch_size: AnchorDeserialize::deserialize(&mut data)?,
+            claim_period_open: AnchorDeserialize::deserialize(&mut data)?,
+            paused: AnchorDeserialize::deserialize(&mut data)?,
+            // The inline contributor vector's length prefix.
+            contributor_count: AnchorDeserialize::deserialize(&mut data)?,
+            ..Self::default()
+        };
+        // Inline entries sit before the version, so only an empty vector
+        // leaves it readable; version 0 reads the zeroed byte after it.
+        require!(
+            !inline_contributors || state.contributor_count == 0,
+            DistributionError::ContributorsNotMigratable
+        );
+        state.version = AnchorDeserialize::deserialize(&mut data)?;
+        if state.version >= 3 {
+            state.allocation_cursor = AnchorDeserialize::deserialize(&mut data)?;
+            state.allocation_total_tokens = AnchorDeserialize::deserialize(&mut data)?;
+            state.allocated_amount = AnchorDeserialize::deserialize(&mut data)?;
+        }
+        if state.version >= 4 {
+            state.vesting = AnchorDeserialize::deserialize(&mut data)?;
+        }
+        if state.version >= 5 {
+            state.tranches = AnchorDeserialize::deserialize(&mut data)?;
+        }
+        if state.version >= 6 {
+            state.merkle_root = AnchorDeserialize::deserialize(&mut data)?;
+        }
+        if state.version >= 7 {
+            state.claim_start = AnchorDeserialize::deserialize(&mut data)?;
+            state.claim_end = AnchorDeserialize::deserialize(&mut data)?;
+        }
+        if state.version >= 8 {
+            state.total_claimed = AnchorDeserialize::deserialize(&mut data)?;
+        }
+        Ok(state)
+    }
+}
+
+/// Size of the version 0 layout, which held up to 2000 contributors inline;
+/// version 1 appended `version` after them.
+const INLINE_CONTRIBUTORS_LEN: usize = 8 + 32 + 32 + 8 + 1 + 1 + 8 + 1 + 1 + 4 + (2000 * (32 + 8 + 8));
+
+/// Releases `bps` of each allocation at `unlock_timestamp`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct Tranche {
+    pub unlock_timestamp: i64,
+    pub bps: u16,
+}
+
+impl Tranche {
+    pub const LEN: usize = 8 + 2;
+}
+
+/// How allocations unlock: `tge_unlock_bps` at `tge_timestamp`, then the rest
+/// linearly over `vesting_duration` once `cliff_seconds` have passed. The
+/// all-zero default unlocks everything immediately.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct VestingSchedule {
+    pub tge_unlock_bps: u16,
+    pub tge_timestamp: i64,
+    pub cliff_seconds: i64,
+    pub vesting_duration: i64,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
-pub struct Contributor {
+impl VestingSchedule {
+    pub const LEN: usize = 2 + 8 + 8 + 8;
+
+    /// Portion of `total` unlocked at `now`.
+    pub fn vested_amount(&self, total: u64, now: i64) -> u64 {
+        if now < self.tge_timestamp {
+            return 0;
+        }
+        let tge_amount = ((total as u128) * (self.tge_unlock_bps as u128) / 10_000) as u64;
+        let vesting_start = self.tge_timestamp.saturating_add(self.cliff_seconds);
+        if now < vesting_start {
+            return tge_amount;
+        }
+        let elapsed = now - vesting_start;
+        if elapsed >= self.vesting_duration {
+            return total;
+        }
+        let linear = ((total - tge_amount) as u128) * (elapsed as u128)
+            / (self.vesting_duration as u128);
+        tge_amount + linear as u64
+    }
+}
+
+/// A contributor's entry, at PDA `[b"allocation", distribution_state, user]`.
+#[account]
+#[derive(Default)]
+pub struct Allocation {
+    pub distribution: Pubkey,
     pub user: Pubkey,
+    /// Creation order; `calculate_allocations` takes allocations in this order.
+    pub index: u32,
     pub contribution: u64,
     pub allocation: u64,
+    /// Portion of `allocation` already paid out by `claim`.
+    pub claimed_amount: u64,
+    /// Set by `freeze_claim` to hold claims, e.g. pending a compliance review.
+    pub frozen: bool,
+    pub bump: u8,
+}
+
+impl Allocation {
+    pub const LEN: usize = 8 + 32 + 32 + 4 + 8 + 8 + 8 + 1 + 1;
+}
+
+/// What a merkle claimant has taken, at PDA `[b"claim_status", distribution_state, user]`.
+#[account]
+#[derive(Default)]
+pub struct ClaimStatus {
+    pub distribution: Pubkey,
+    pub user: Pubkey,
+    pub claimed_amount: u64,
+    pub bump: u8,
+    /// Set by `freeze_merkle_claim` to hold claims, as `Allocation::frozen` does.
+    pub frozen: bool,
+}
+
+impl ClaimStatus {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 1 + 1;
+}
+
+/// One `batch_set_contributions` item: 40 bytes on the wire.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct ContributionEntry {
+    pub user: Pubkey,
+    pub amount: u64,
 }
 
 #[derive(Accounts)]
@@ -30,13 +279,324 @@ pub struct InitializeDistribution<'info> {
     #[account(
         init,
         payer = payer,
-        space = 8 + 32 + 32 + 8 + 1 + 1 + 8 + 1 + 1 + 4 + (2000 * (32 + 8 + 8))
+        space = DistributionState::LEN
+    )]
+    pub distribution_state: Account<'info, DistributionState>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateDistribution<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: read with `DistributionState::read_any_version`, as older
+    /// layouts do not deserialize as the current one.
+    #[account(mut, owner = crate::ID)]
+    pub distribution_state: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// One `Allocation` PDA per entry is passed in `remaining_accounts`, in entry order.
+#[derive(Accounts)]
+pub struct BatchSetContributions<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub distribution_state: Account<'info, DistributionState>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// The next chunk of `Allocation` PDAs is passed in `remaining_accounts`, in index order.
+#[derive(Accounts)]
+pub struct CalculateAllocations<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub distribution_state: Account<'info, DistributionState>,
+
+    /// CHECK: PDA that owns the distribution's token vault and signs
+    /// transfers out of it; holds no data.
+    #[account(seeds = [b"vault", distribution_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = token_account.owner == vault_authority.key() @ DistributionError::InvalidVault,
+        constraint = token_account.mint == distribution_state.token_mint @ DistributionError::InvalidTokenMint
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeAllocations<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub distribution_state: Account<'info, DistributionState>,
+}
+
+#[derive(Accounts)]
+pub struct SetToken<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub distribution_state: Account<'info, DistributionState>,
+}
+
+#[derive(Accounts)]
+pub struct SetVesting<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub distribution_state: Account<'info, DistributionState>,
+}
+
+#[derive(Accounts)]
+pub struct SetTranches<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub distribution_state: Account<'info, DistributionState>,
+}
+
+#[derive(Accounts)]
+pub struct SetMerkleRoot<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub distribution_state: Account<'info, DistributionState>,
+}
+
+/// Shared by `open_claims` and `close_claims`.
+#[derive(Accounts)]
+pub struct SetClaimWindow<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub distribution_state: Account<'info, DistributionState>,
+}
+
+/// Shared by `freeze_merkle_claim` and `unfreeze_merkle_claim`.
+#[derive(Accounts)]
+pub struct SetMerkleClaimFreeze<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub distribution_state: Account<'info, DistributionState>,
+
+    /// CHECK: The user's `ClaimStatus` PDA, created here if they have not claimed yet.
+    #[account(mut)]
+    pub claim_status: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Shared by `freeze_claim` and `unfreeze_claim`.
+#[derive(Accounts)]
+pub struct SetClaimFreeze<'info> {
+    pub authority: Signer<'info>,
+
+    pub distribution_state: Account<'info, DistributionState>,
+
+    #[account(
+        mut,
+        constraint = allocation.distribution == distribution_state.key() @ DistributionError::InvalidAllocationAccount
+    )]
+    pub allocation: Account<'info, Allocation>,
+}
+
+/// `Allocation` PDAs to zero may be passed in `remaining_accounts`.
+#[derive(Accounts)]
+pub struct ResetAllocations<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub distribution_state: Account<'info, DistributionState>,
+}
+
+#[derive(Accounts)]
+pub struct Claim<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub distribution_state: Account<'info, DistributionState>,
+
+    #[account(
+        mut,
+        seeds = [b"allocation", distribution_state.key().as_ref(), authority.key().as_ref()],
+        bump = allocation.bump
     )]
+    pub allocation: Account<'info, Allocation>,
+
+    /// CHECK: PDA that owns the distribution's token vault and signs
+    /// transfers out of it; holds no data.
+    #[account(seeds = [b"vault", distribution_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = from.owner == vault_authority.key() @ DistributionError::InvalidVault,
+        constraint = from.mint == distribution_state.token_mint @ DistributionError::InvalidTokenMint
+    )]
+    pub from: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = to.owner == authority.key() @ DistributionError::InvalidTokenAccount,
+        constraint = to.mint == distribution_state.token_mint @ DistributionError::InvalidTokenMint
+    )]
+    pub to: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimWithProof<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
     pub distribution_state: Account<'info, DistributionState>,
 
+    /// CHECK: The claimant's `ClaimStatus` PDA, created on first claim and checked in the handler.
+    #[account(mut)]
+    pub claim_status: UncheckedAccount<'info>,
+
+    /// CHECK: PDA that owns the distribution's token vault and signs
+    /// transfers out of it; holds no data.
+    #[account(seeds = [b"vault", distribution_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = from.owner == vault_authority.key() @ DistributionError::InvalidVault,
+        constraint = from.mint == distribution_state.token_mint @ DistributionError::InvalidTokenMint
+    )]
+    pub from: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = to.owner == authority.key() @ DistributionError::InvalidTokenAccount,
+        constraint = to.mint == distribution_state.token_mint @ DistributionError::InvalidTokenMint
+    )]
+    pub to: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+
     pub system_program: Program<'info, System>,
 }
 
+#[error_code]
+pub enum DistributionError {
+    #[msg("Only the owner can perform this action.")]
+    NotOwner,
+    #[msg("The contract is paused.")]
+    ContractPaused,
+    #[msg("Batch size must be greater than zero.")]
+    InvalidBatchSize,
+    #[msg("Batch exceeds the maximum batch size.")]
+    BatchTooLarge,
+    #[msg("Token mint is invalid.")]
+    InvalidTokenMint,
+    #[msg("Amount is invalid.")]
+    InvalidAmount,
+    #[msg("Contributor appears more than once in the batch.")]
+    DuplicateContributor,
+    #[msg("There are no contributions.")]
+    NoContributions,
+    #[msg("The distribution token account has no balance.")]
+    NoTokenBalance,
+    #[msg("Allocations exceed the token balance.")]
+    AllocationExceedsBalance,
+    #[msg("Allocations are already calculated.")]
+    AllocationAlreadyCalculated,
+    #[msg("Allocations are not calculated.")]
+    AllocationNotCalculated,
+    #[msg("An allocation calculation is in progress.")]
+    AllocationInProgress,
+    #[msg("Not every allocation has been calculated.")]
+    AllocationIncomplete,
+    #[msg("Allocation account is invalid.")]
+    InvalidAllocationAccount,
+    #[msg("Claim status account is invalid.")]
+    InvalidClaimStatusAccount,
+    #[msg("The claim period is active.")]
+    ClaimPeriodActive,
+    #[msg("The claim period is closed.")]
+    ClaimPeriodClosed,
+    #[msg("Claiming is not enabled.")]
+    ClaimingNotEnabled,
+    #[msg("Claims have already been made.")]
+    ClaimsAlreadyMade,
+    #[msg("Claim window is invalid.")]
+    InvalidClaimWindow,
+    #[msg("Claim is frozen.")]
+    ClaimFrozen,
+    #[msg("Claim is already frozen.")]
+    ClaimAlreadyFrozen,
+    #[msg("Claim is not frozen.")]
+    ClaimNotFrozen,
+    #[msg("Nothing to claim.")]
+    NothingToClaim,
+    #[msg("Vesting schedule is invalid.")]
+    InvalidVestingSchedule,
+    #[msg("Tranches are invalid.")]
+    InvalidTranches,
+    #[msg("Too many tranches.")]
+    TooManyTranches,
+    #[msg("Merkle root is not set.")]
+    MerkleRootNotSet,
+    #[msg("Merkle proof is invalid.")]
+    InvalidMerkleProof,
+    #[msg("Distribution is already at the current version.")]
+    AlreadyMigrated,
+    #[msg("Distribution has contributors that cannot be migrated.")]
+    ContributorsNotMigratable,
+    #[msg("Arithmetic overflow.")]
+    Overflow,
+    #[msg("Token account is not the distribution vault.")]
+    InvalidVault,
+    #[msg("Token account does not belong to the claimant.")]
+    InvalidTokenAccount,
+}
+
+/// Every event the distribution emits, logged under a single discriminator.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub enum DistributionEvent {
+    Initialized { owner: Pubkey, max_batch_size: u64 },
+    Migrated { from_version: u8, to_version: u8 },
+    TokenUpdated { token_mint: Pubkey },
+    VestingUpdated { tge_unlock_bps: u16, tge_timestamp: i64, cliff_seconds: i64, vesting_duration: i64 },
+    TranchesUpdated { tranches: Vec<Tranche> },
+    MerkleRootUpdated { merkle_root: [u8; 32] },
+    ContributionsUpdated,
+    AllocationChunkCalculated { cursor: u32, contributor_count: u32 },
+    AllocationsCalculated { total_raised: u64 },
+    AllocationsReset { zeroed: u32 },
+    ClaimsOpened { claim_start: i64, claim_end: i64 },
+    ClaimsClosed,
+    ClaimFrozen { user: Pubkey },
+    ClaimUnfrozen { user: Pubkey },
+    Claimed { user: Pubkey, amount: u64 },
+}
+
+impl Discriminator for DistributionEvent {
+    // sha256("event:DistributionEvent")[..8], as `#[event]` would derive it.
+    const DISCRIMINATOR: [u8; 8] = [15, 143, 204, 204, 42, 49, 139, 252];
+}
+
+impl Event for DistributionEvent {
+    fn data(&self) -> Vec<u8> {
+        let mut data = Self::DISCRIMINATOR.to_vec();
+        data.append(&mut self.try_to_vec().unwrap());
+        data
+    }
+}
+
 #[program]
 mod secure_distribution {
     use super::*;
@@ -57,12 +617,90 @@ mod secure_distribution {
         state.max_batch_size = max_batch_size;
         state.claim_period_open = false;
         state.paused = false;
-        state.contributors = vec![];
+        state.contributor_count = 0;
+        state.version = DISTRIBUTION_VERSION;
+        state.allocation_cursor = 0;
+        state.allocation_total_tokens = 0;
+        state.allocated_amount = 0;
+        state.vesting = VestingSchedule::default();
+        state.tranches = vec![];
+        state.merkle_root = [0u8; 32];
+        state.claim_start = 0;
+        state.claim_end = 0;
+        state.total_claimed = 0;
         
         emit!(DistributionEvent::Initialized { owner, max_batch_size });
         Ok(())
     }
 
+    pub fn migrate_distribution(ctx: Context<MigrateDistribution>) -> Result<()> {
+        let info = ctx.accounts.distribution_state.to_account_info();
+        let mut state = DistributionState::read_any_version(&info.try_borrow_data()?)?;
+        require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
+        require!(state.version < DISTRIBUTION_VERSION, DistributionError::AlreadyMigrated);
+
+        let from_version = state.version;
+        if state.version < 1 {
+            // Version 1 only introduced the version field itself.
+            state.version = 1;
+        }
+        if state.version < 2 {
+            // Version 2 moved contributors into `Allocation` PDAs. Only a distribution
+            // with no contributors can migrate, which `read_any_version` checks.
+            state.version = 2;
+        }
+        if state.version < 3 {
+            // Version 3 appended the allocation cursor fields, which start zeroed.
+            state.version = 3;
+        }
+        if state.version < 4 {
+            // Version 4 appended the vesting schedule; zeroed, it unlocks everything at once.
+            state.version = 4;
+        }
+        if state.version < 5 {
+            // Version 5 appended the tranche list, which starts empty.
+            state.version = 5;
+        }
+        if state.version < 6 {
+            // Version 6 appended the merkle root, which starts zeroed (disabled).
+            state.version = 6;
+        }
+        if state.version < 7 {
+            // Version 7 appended the claim window, which `open_claims` sets.
+            state.version = 7;
+        }
+        if state.version < 8 {
+            // Version 8 appended `total_claimed`; claims made before it are not counted.
+            state.version = 8;
+        }
+
+        // Versions 2 to 7 are smaller than the current layout and the
+        // inline-contributor ones far larger; the difference in rent is
+        // settled with the authority.
+        let rent = Rent::get()?.minimum_balance(DistributionState::LEN);
+        let current = info.lamports();
+        if rent > current {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: info.clone(),
+                    },
+                ),
+                rent - current,
+            )?;
+        } else if rent < current {
+            **info.try_borrow_mut_lamports()? -= current - rent;
+            **ctx.accounts.authority.to_account_info().try_borrow_mut_lamports()? += current - rent;
+        }
+        info.realloc(DistributionState::LEN, true)?;
+        state.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        emit!(DistributionEvent::Migrated { from_version, to_version: state.version });
+        Ok(())
+    }
+
     pub fn set_token(ctx: Context<SetToken>, token_mint: Pubkey) -> Result<()> {
         let state = &mut ctx.accounts.distribution_state;
         require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
@@ -76,41 +714,143 @@ mod secure_distribution {
         Ok(())
     }
 
-    pub fn batch_set_contributions(
-        ctx: Context<BatchSetContributions>,
-        users: Vec<Pubkey>,
-        amounts: Vec<u64>,
+    pub fn set_vesting(ctx: Context<SetVesting>, vesting: VestingSchedule) -> Result<()> {
+        let state = &mut ctx.accounts.distribution_state;
+        require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
+        require!(!state.paused, DistributionError::ContractPaused);
+        require!(!state.claim_period_open, DistributionError::ClaimPeriodActive);
+
+        require!(
+            vesting.tge_unlock_bps <= 10_000
+                && vesting.cliff_seconds >= 0
+                && vesting.vesting_duration >= 0,
+            DistributionError::InvalidVestingSchedule
+        );
+        state.vesting = vesting;
+        emit!(DistributionEvent::VestingUpdated {
+            tge_unlock_bps: vesting.tge_unlock_bps,
+            tge_timestamp: vesting.tge_timestamp,
+            cliff_seconds: vesting.cliff_seconds,
+            vesting_duration: vesting.vesting_duration,
+        });
+        Ok(())
+    }
+
+    /// Replaces the unlock tranches; an empty list falls back to `vesting`.
+    pub fn set_tranches(ctx: Context<SetTranches>, tranches: Vec<Tranche>) -> Result<()> {
+        let state = &mut ctx.accounts.distribution_state;
+        require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
+        require!(!state.paused, DistributionError::ContractPaused);
+        require!(!state.claim_period_open, DistributionError::ClaimPeriodActive);
+        require!(tranches.len() <= MAX_TRANCHES, DistributionError::TooManyTranches);
+
+        if !tranches.is_empty() {
+            let mut total_bps: u32 = 0;
+            for (i, tranche) in tranches.iter().enumerate() {
+                require!(tranche.bps > 0, DistributionError::InvalidTranches);
+                require!(
+                    i == 0 || tranche.unlock_timestamp > tranches[i - 1].unlock_timestamp,
+                    DistributionError::InvalidTranches
+                );
+                total_bps += tranche.bps as u32;
+            }
+            require!(total_bps == 10_000, DistributionError::InvalidTranches);
+        }
+
+        state.tranches = tranches.clone();
+        emit!(DistributionEvent::TranchesUpdated { tranches });
+        Ok(())
+    }
+
+    pub fn set_merkle_root(ctx: Context<SetMerkleRoot>, merkle_root: [u8; 32]) -> Result<()> {
+        let state = &mut ctx.accounts.distribution_state;
+        require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
+        require!(!state.paused, DistributionError::ContractPaused);
+        require!(!state.claim_period_open, DistributionError::ClaimPeriodActive);
+
+        state.merkle_root = merkle_root;
+        emit!(DistributionEvent::MerkleRootUpdated { merkle_root });
+        Ok(())
+    }
+
+    /// Creates or overwrites each entry's `Allocation`. A zero amount is only
+    /// accepted for an existing allocation, to remove that contribution.
+    pub fn batch_set_contributions<'info>(
+        ctx: Context<'_, '_, '_, 'info, BatchSetContributions<'info>>,
+        entries: Vec<ContributionEntry>,
     ) -> Result<()> {
         let state = &mut ctx.accounts.distribution_state;
         require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
         require!(!state.paused, DistributionError::ContractPaused);
         require!(!state.allocation_calculated, DistributionError::AllocationAlreadyCalculated);
-        require_eq!(users.len(), amounts.len(), DistributionError::ArrayLengthMismatch);
-        require!(users.len() as u64 <= state.max_batch_size, DistributionError::BatchTooLarge);
+        require!(state.allocation_cursor == 0, DistributionError::AllocationInProgress);
+        require!(entries.len() as u64 <= state.max_batch_size, DistributionError::BatchTooLarge);
+        require!(
+            ctx.remaining_accounts.len() == entries.len(),
+            DistributionError::InvalidAllocationAccount
+        );
 
+        let state_key = state.key();
         let mut seen_users = std::collections::HashSet::new();
-        for (i, user) in users.iter().enumerate() {
+        for (&ContributionEntry { user, amount }, info) in
+            entries.iter().zip(ctx.remaining_accounts.iter())
+        {
             require!(seen_users.insert(user), DistributionError::DuplicateContributor);
-            let amount = amounts[i];
-            require!(amount > 0, DistributionError::InvalidAmount);
 
-            if let Some(contributor) = state.contributors.iter_mut().find(|c| c.user == *user) {
-                state.total_raised = state.total_raised - contributor.contribution + amount;
-                contributor.contribution = amount;
-            } else {
-                state.contributors.push(Contributor {
-                    user: *user,
+            let (expected, bump) = Pubkey::find_program_address(
+                &[b"allocation", state_key.as_ref(), user.as_ref()],
+                ctx.program_id,
+            );
+            require_keys_eq!(info.key(), expected, DistributionError::InvalidAllocationAccount);
+
+            let allocation = if info.data_is_empty() {
+                require!(amount > 0, DistributionError::InvalidAmount);
+                create_pda_account(
+                    info,
+                    &ctx.accounts.authority,
+                    &ctx.accounts.system_program,
+                    ctx.program_id,
+                    &[b"allocation", state_key.as_ref(), user.as_ref(), &[bump]],
+                    Allocation::LEN,
+                )?;
+                let allocation = Allocation {
+                    distribution: state_key,
+                    user,
+                    index: state.contributor_count,
                     contribution: amount,
                     allocation: 0,
-                });
-                state.total_raised += amount;
-            }
+                    claimed_amount: 0,
+                    frozen: false,
+                    bump,
+                };
+                state.contributor_count = state
+                    .contributor_count
+                    .checked_add(1)
+                    .ok_or(DistributionError::Overflow)?;
+                state.total_raised = state
+                    .total_raised
+                    .checked_add(amount)
+                    .ok_or(DistributionError::Overflow)?;
+                allocation
+            } else {
+                let mut allocation = Allocation::try_deserialize(&mut &info.data.borrow()[..])?;
+                state.total_raised = state
+                    .total_raised
+                    .checked_sub(allocation.contribution)
+                    .and_then(|total| total.checked_add(amount))
+                    .ok_or(DistributionError::Overflow)?;
+                allocation.contribution = amount;
+                allocation
+            };
+            allocation.try_serialize(&mut &mut info.data.borrow_mut()[..])?;
         }
 
         emit!(DistributionEvent::ContributionsUpdated);
         Ok(())
     }
 
+    /// Computes allocations for the next chunk of contributors, starting at
+    /// `allocation_cursor`. `finalize_allocations` completes the calculation.
     pub fn calculate_allocations(ctx: Context<CalculateAllocations>) -> Result<()> {
         let state = &mut ctx.accounts.distribution_state;
         require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
@@ -119,60 +859,721 @@ mod secure_distribution {
         require!(state.total_raised > 0, DistributionError::NoContributions);
         require!(!state.allocation_calculated, DistributionError::AllocationAlreadyCalculated);
 
-        let token_account = &ctx.accounts.token_account;
-        let total_tokens = token_account.amount;
-        require!(total_tokens > 0, DistributionError::NoTokenBalance);
-
-        let mut allocated_amount: u64 = 0;
-        for contributor in state.contributors.iter_mut() {
-            if contributor.contribution > 0 {
-                let allocation = contributor
-                    .contribution
-                    .checked_mul(total_tokens)
-                    .ok_or(DistributionError::Overflow)?
-                    / state.total_raised;
-                contributor.allocation = allocation;
-                allocated_amount = allocated_amount
-                    .checked_add(allocation)
-                    .ok_or(DistributionError::Overflow)?;
-            }
+        let chunk = ctx.remaining_accounts;
+        require!(chunk.len() as u64 <= state.max_batch_size, DistributionError::BatchTooLarge);
+        let end = state
+            .allocation_cursor
+            .checked_add(chunk.len() as u32)
+            .ok_or(DistributionError::Overflow)?;
+        require!(end <= state.contributor_count, DistributionError::InvalidAllocationAccount);
+
+        if state.allocation_cursor == 0 {
+            let total_tokens = ctx.accounts.token_account.amount;
+            require!(total_tokens > 0, DistributionError::NoTokenBalance);
+            state.allocation_total_tokens = total_tokens;
+            state.allocated_amount = 0;
+        }
+
+        let state_key = state.key();
+        for info in chunk.iter() {
+            let mut allocation = load_allocation(info, &state_key, ctx.program_id)?;
+            state.allocate_next(&mut allocation)?;
+            allocation.try_serialize(&mut &mut info.data.borrow_mut()[..])?;
         }
 
-        require!(allocated_amount <= total_tokens, DistributionError::AllocationExceedsBalance);
+        emit!(DistributionEvent::AllocationChunkCalculated {
+            cursor: end,
+            contributor_count: state.contributor_count,
+        });
+        Ok(())
+    }
+
+    pub fn finalize_allocations(ctx: Context<FinalizeAllocations>) -> Result<()> {
+        let state = &mut ctx.accounts.distribution_state;
+        require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
+        require!(!state.paused, DistributionError::ContractPaused);
+        require!(state.total_raised > 0, DistributionError::NoContributions);
+        require!(!state.allocation_calculated, DistributionError::AllocationAlreadyCalculated);
+        require!(
+            state.allocation_cursor == state.contributor_count,
+            DistributionError::AllocationIncomplete
+        );
+        require!(
+            state.allocated_amount <= state.allocation_total_tokens,
+            DistributionError::AllocationExceedsBalance
+        );
 
         state.allocation_calculated = true;
         emit!(DistributionEvent::AllocationsCalculated { total_raised: state.total_raised });
         Ok(())
     }
 
-    pub fn claim(ctx: Context<Claim>) -> Result<()> {
+    /// Clears a calculated (or partly calculated) allocation so contributions
+    /// can be corrected and allocations recalculated. Allowed only before any
+    /// claim and while the claim period is closed; may be repeated to zero
+    /// allocations in chunks.
+    pub fn reset_allocations(ctx: Context<ResetAllocations>) -> Result<()> {
+        let state = &mut ctx.accounts.distribution_state;
+        require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
+        require!(!state.claim_period_open, DistributionError::ClaimPeriodActive);
+        require!(state.total_claimed == 0, DistributionError::ClaimsAlreadyMade);
+        require!(
+            ctx.remaining_accounts.len() as u64 <= state.max_batch_size,
+            DistributionError::BatchTooLarge
+        );
+
+        let state_key = state.key();
+        for info in ctx.remaining_accounts.iter() {
+            let mut allocation = load_allocation(info, &state_key, ctx.program_id)?;
+            require!(allocation.claimed_amount == 0, DistributionError::ClaimsAlreadyMade);
+            allocation.allocation = 0;
+            allocation.try_serialize(&mut &mut info.data.borrow_mut()[..])?;
+        }
+
+        state.clear_allocations();
+        emit!(DistributionEvent::AllocationsReset {
+            zeroed: ctx.remaining_accounts.len() as u32,
+        });
+        Ok(())
+    }
+
+    /// Enables claiming between `claim_start` and `claim_end`.
+    pub fn open_claims(ctx: Context<SetClaimWindow>, claim_start: i64, claim_end: i64) -> Result<()> {
         let state = &mut ctx.accounts.distribution_state;
+        require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
         require!(!state.paused, DistributionError::ContractPaused);
-        require!(state.claim_enabled, DistributionError::ClaimingNotEnabled);
+        require!(state.token_mint != Pubkey::default(), DistributionError::InvalidTokenMint);
+        require!(
+            state.allocation_calculated || state.merkle_root != [0u8; 32],
+            DistributionError::AllocationNotCalculated
+        );
+        require!(claim_start < claim_end, DistributionError::InvalidClaimWindow);
+
+        state.claim_enabled = true;
+        state.claim_period_open = true;
+        state.claim_start = claim_start;
+        state.claim_end = claim_end;
+        emit!(DistributionEvent::ClaimsOpened { claim_start, claim_end });
+        Ok(())
+    }
+
+    pub fn close_claims(ctx: Context<SetClaimWindow>) -> Result<()> {
+        let state = &mut ctx.accounts.distribution_state;
+        require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
         require!(state.claim_period_open, DistributionError::ClaimPeriodClosed);
 
+        state.claim_enabled = false;
+        state.claim_period_open = false;
+        emit!(DistributionEvent::ClaimsClosed);
+        Ok(())
+    }
+
+    pub fn freeze_claim(ctx: Context<SetClaimFreeze>) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.distribution_state.owner,
+            ctx.accounts.authority.key(),
+            DistributionError::NotOwner
+        );
+        let allocation = &mut ctx.accounts.allocation;
+        require!(!allocation.frozen, DistributionError::ClaimAlreadyFrozen);
+
+        allocation.frozen = true;
+        emit!(DistributionEvent::ClaimFrozen { user: allocation.user });
+        Ok(())
+    }
+
+    pub fn unfreeze_claim(ctx: Context<SetClaimFreeze>) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.distribution_state.owner,
+            ctx.accounts.authority.key(),
+            DistributionError::NotOwner
+        );
+        let allocation = &mut ctx.accounts.allocation;
+        require!(allocation.frozen, DistributionError::ClaimNotFrozen);
+
+        allocation.frozen = false;
+        emit!(DistributionEvent::ClaimUnfrozen { user: allocation.user });
+        Ok(())
+    }
+
+    /// Holds `user`'s merkle claims, creating their `ClaimStatus` if needed.
+    pub fn freeze_merkle_claim(ctx: Context<SetMerkleClaimFreeze>, user: Pubkey) -> Result<()> {
+        let state = &ctx.accounts.distribution_state;
+        require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
+
+        let info = ctx.accounts.claim_status.to_account_info();
+        let mut status = load_or_create_claim_status(
+            &info,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+            ctx.program_id,
+            state.key(),
+            user,
+        )?;
+        require!(!status.frozen, DistributionError::ClaimAlreadyFrozen);
+
+        status.frozen = true;
+        status.try_serialize(&mut &mut info.data.borrow_mut()[..])?;
+        emit!(DistributionEvent::ClaimFrozen { user });
+        Ok(())
+    }
+
+    pub fn unfreeze_merkle_claim(ctx: Context<SetMerkleClaimFreeze>, user: Pubkey) -> Result<()> {
+        let state = &ctx.accounts.distribution_state;
+        require_keys_eq!(state.owner, ctx.accounts.authority.key(), DistributionError::NotOwner);
+
+        let info = ctx.accounts.claim_status.to_account_info();
+        let mut status = load_or_create_claim_status(
+            &info,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+            ctx.program_id,
+            state.key(),
+            user,
+        )?;
+        require!(status.frozen, DistributionError::ClaimNotFrozen);
+
+        status.frozen = false;
+        status.try_serialize(&mut &mut info.data.borrow_mut()[..])?;
+        emit!(DistributionEvent::ClaimUnfrozen { user });
+        Ok(())
+    }
+
+    pub fn claim(ctx: Context<Claim>) -> Result<()> {
+        let state = &mut ctx.accounts.distribution_state;
+        let now = Clock::get()?.unix_timestamp;
+        state.require_claimable(now)?;
+
         let authority_key = ctx.accounts.authority.key();
-        let contributor = state
-            .contributors
-            .iter_mut()
-            .find(|c| c.user == authority_key)
-            .ok_or(DistributionError::NotContributor)?;
-        
-        let claim_amount = contributor.allocation;
-        require!(claim_amount > 0, DistributionError::NothingToClaim);
-        contributor.allocation = 0; // Reset before transferring
+        let allocation = &mut ctx.accounts.allocation;
+        require!(!allocation.frozen, DistributionError::ClaimFrozen);
+
+        // Recorded before transferring
+        let claim_amount = state.take_claim(allocation.allocation, &mut allocation.claimed_amount, now)?;
+        state.total_claimed = state
+            .total_claimed
+            .checked_add(claim_amount)
+            .ok_or(DistributionError::Overflow)?;
 
-        let transfer_cpi_ctx = CpiContext::new(
+        let state_key = state.key();
+        let seeds = &[b"vault".as_ref(), state_key.as_ref(), &[*ctx.bumps.get("vault_authority").unwrap()]];
+        let signer = &[&seeds[..]];
+        let transfer_cpi_ctx = CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             Transfer {
                 from: ctx.accounts.from.to_account_info(),
                 to: ctx.accounts.to.to_account_info(),
-                authority: ctx.accounts.from.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
             },
+            signer,
         );
 
         token::transfer(transfer_cpi_ctx, claim_amount)?;
         emit!(DistributionEvent::Claimed { user: authority_key, amount: claim_amount });
         Ok(())
     }
+
+    /// Claims an `allocation` proven against `merkle_root` rather than stored
+    /// on-chain. Vesting and tranches apply as in `claim`.
+    pub fn claim_with_proof(
+        ctx: Context<ClaimWithProof>,
+        allocation: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.distribution_state;
+        let now = Clock::get()?.unix_timestamp;
+        state.require_claimable(now)?;
+        require!(state.merkle_root != [0u8; 32], DistributionError::MerkleRootNotSet);
+
+        let authority_key = ctx.accounts.authority.key();
+        let leaf = hashv(&[authority_key.as_ref(), &allocation.to_le_bytes()]).to_bytes();
+        require!(
+            verify_merkle_proof(&proof, state.merkle_root, leaf),
+            DistributionError::InvalidMerkleProof
+        );
+
+        let info = ctx.accounts.claim_status.to_account_info();
+        let mut status = load_or_create_claim_status(
+            &info,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+            ctx.program_id,
+            state.key(),
+            authority_key,
+        )?;
+        require!(!status.frozen, DistributionError::ClaimFrozen);
+
+        // Recorded before transferring
+        let claim_amount = state.take_claim(allocation, &mut status.claimed_amount, now)?;
+        status.try_serialize(&mut &mut info.data.borrow_mut()[..])?;
+        state.total_claimed = state
+            .total_claimed
+            .checked_add(claim_amount)
+            .ok_or(DistributionError::Overflow)?;
+
+        let state_key = state.key();
+        let seeds = &[b"vault".as_ref(), state_key.as_ref(), &[*ctx.bumps.get("vault_authority").unwrap()]];
+        let signer = &[&seeds[..]];
+        let transfer_cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.from.to_account_info(),
+                to: ctx.accounts.to.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            },
+            signer,
+        );
+
+        token::transfer(transfer_cpi_ctx, claim_amount)?;
+        emit!(DistributionEvent::Claimed { user: authority_key, amount: claim_amount });
+        Ok(())
+    }
+}
+
+/// Allocates a `space`-byte PDA at `info`, paid for by `payer`. An address
+/// that already holds lamports is topped up, allocated and assigned instead,
+/// as Anchor's `init` does, so pre-funding it cannot block creation.
+fn create_pda_account<'info>(
+    info: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    program_id: &Pubkey,
+    seeds: &[&[u8]],
+    space: usize,
+) -> Result<()> {
+    let lamports = Rent::get()?.minimum_balance(space);
+    let current = info.lamports();
+    if current == 0 {
+        return system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::CreateAccount {
+                    from: payer.to_account_info(),
+                    to: info.clone(),
+                },
+                &[seeds],
+            ),
+            lamports,
+            space as u64,
+            program_id,
+        );
+    }
+
+    let shortfall = lamports.saturating_sub(current);
+    if shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: info.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            system_program::Allocate { account_to_allocate: info.clone() },
+            &[seeds],
+        ),
+        space as u64,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            system_program::Assign { account_to_assign: info.clone() },
+            &[seeds],
+        ),
+        program_id,
+    )
+}
+
+/// Reads `user`'s `ClaimStatus` at `info`, creating it if it does not exist
+/// yet. Statuses written before `frozen` was appended are grown to the
+/// current size, with `payer` covering the extra rent.
+fn load_or_create_claim_status<'info>(
+    info: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    program_id: &Pubkey,
+    distribution: Pubkey,
+    user: Pubkey,
+) -> Result<ClaimStatus> {
+    let (expected, bump) = Pubkey::find_program_address(
+        &[b"claim_status", distribution.as_ref(), user.as_ref()],
+        program_id,
+    );
+    require_keys_eq!(info.key(), expected, DistributionError::InvalidClaimStatusAccount);
+
+    if info.data_is_empty() {
+        create_pda_account(
+            info,
+            payer,
+            system_program,
+            program_id,
+            &[b"claim_status", distribution.as_ref(), user.as_ref(), &[bump]],
+            ClaimStatus::LEN,
+        )?;
+        return Ok(ClaimStatus {
+            distribution,
+            user,
+            claimed_amount: 0,
+            bump,
+            frozen: false,
+        });
+    }
+
+    require_keys_eq!(*info.owner, *program_id, DistributionError::InvalidClaimStatusAccount);
+    if info.data_len() < ClaimStatus::LEN {
+        let shortfall = Rent::get()?
+            .minimum_balance(ClaimStatus::LEN)
+            .saturating_sub(info.lamports());
+        if shortfall > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: payer.to_account_info(),
+                        to: info.clone(),
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+        info.realloc(ClaimStatus::LEN, true)?;
+    }
+    ClaimStatus::try_deserialize(&mut &info.data.borrow()[..])
+}
+
+/// Reads the `Allocation` at `info`, checking it is `distribution`'s PDA for its user.
+fn load_allocation(info: &AccountInfo, distribution: &Pubkey, program_id: &Pubkey) -> Result<Allocation> {
+    require_keys_eq!(*info.owner, *program_id, DistributionError::InvalidAllocationAccount);
+    let allocation = Allocation::try_deserialize(&mut &info.data.borrow()[..])?;
+    require_keys_eq!(allocation.distribution, *distribution, DistributionError::InvalidAllocationAccount);
+    let expected = Pubkey::create_program_address(
+        &[b"allocation", distribution.as_ref(), allocation.user.as_ref(), &[allocation.bump]],
+        program_id,
+    )
+    .map_err(|_| DistributionError::InvalidAllocationAccount)?;
+    require_keys_eq!(info.key(), expected, DistributionError::InvalidAllocationAccount);
+    Ok(allocation)
+}
+
+/// Checks `proof` links `leaf` to `root`, hashing each pair in sorted order.
+fn verify_merkle_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        if node <= *sibling {
+            hashv(&[&node, sibling]).to_bytes()
+        } else {
+            hashv(&[sibling, &node]).to_bytes()
+        }
+    });
+    computed == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Inline contributor entry of versions 0 and 1.
+    #[derive(AnchorSerialize, Clone)]
+    struct Contributor {
+        user: Pubkey,
+        contribution: u64,
+        allocation: u64,
+    }
+
+    /// A distribution account as `version` wrote it, at that version's size.
+    fn old_account(version: u8, owner: Pubkey, contributors: Vec<Contributor>) -> Vec<u8> {
+        let mut data = DistributionState::DISCRIMINATOR.to_vec();
+        (owner, Pubkey::new_unique(), 5_000u64, true, false, 100u64, false, false)
+            .serialize(&mut data)
+            .unwrap();
+        if version < 2 {
+            contributors.serialize(&mut data).unwrap();
+        } else {
+            3u32.serialize(&mut data).unwrap();
+        }
+        let mut len = match version {
+            0 => INLINE_CONTRIBUTORS_LEN,
+            1 => INLINE_CONTRIBUTORS_LEN + 1,
+            _ => 8 + 32 + 32 + 8 + 1 + 1 + 8 + 1 + 1 + 4 + 1,
+        };
+        if version >= 1 {
+            version.serialize(&mut data).unwrap();
+        }
+        if version >= 3 {
+            (2u32, 1_000_000u64, 400_000u64).serialize(&mut data).unwrap();
+            len += 4 + 8 + 8;
+        }
+        if version >= 4 {
+            VestingSchedule { tge_unlock_bps: 1_000, tge_timestamp: 100, cliff_seconds: 10, vesting_duration: 1_000 }
+                .serialize(&mut data)
+                .unwrap();
+            len += VestingSchedule::LEN;
+        }
+        if version >= 5 {
+            vec![Tranche { unlock_timestamp: 500, bps: 10_000 }].serialize(&mut data).unwrap();
+            len += 4 + (MAX_TRANCHES * Tranche::LEN);
+        }
+        if version >= 6 {
+            [7u8; 32].serialize(&mut data).unwrap();
+            len += 32;
+        }
+        if version >= 7 {
+            (600i64, 900i64).serialize(&mut data).unwrap();
+            len += 8 + 8;
+        }
+        if version >= 8 {
+            250u64.serialize(&mut data).unwrap();
+            len += 8;
+        }
+        data.resize(len, 0);
+        data
+    }
+
+    #[test]
+    fn reads_every_version() {
+        for version in 0..=DISTRIBUTION_VERSION {
+            let owner = Pubkey::new_unique();
+            let state = DistributionState::read_any_version(&old_account(version, owner, vec![])).unwrap();
+            assert_eq!(state.version, version);
+            assert_eq!(state.owner, owner);
+            assert_eq!(state.total_raised, 5_000);
+            assert!(state.allocation_calculated);
+            assert_eq!(state.max_batch_size, 100);
+            assert_eq!(state.contributor_count, if version < 2 { 0 } else { 3 });
+            assert_eq!(state.allocated_amount, if version < 3 { 0 } else { 400_000 });
+            assert_eq!(state.vesting.vesting_duration, if version < 4 { 0 } else { 1_000 });
+            assert_eq!(state.tranches.len(), if version < 5 { 0 } else { 1 });
+            assert_eq!(state.merkle_root, if version < 6 { [0; 32] } else { [7; 32] });
+            assert_eq!(state.claim_end, if version < 7 { 0 } else { 900 });
+            assert_eq!(state.total_claimed, if version < 8 { 0 } else { 250 });
+        }
+    }
+
+    #[test]
+    fn reads_the_current_layout_as_anchor_does() {
+        let data = old_account(DISTRIBUTION_VERSION, Pubkey::new_unique(), vec![]);
+        assert_eq!(data.len(), DistributionState::LEN);
+        let state = DistributionState::read_any_version(&data).unwrap();
+        let mut written = Vec::new();
+        state.try_serialize(&mut written).unwrap();
+        assert_eq!(written, data[..written.len()]);
+    }
+
+    #[test]
+    fn rejects_inline_contributors() {
+        let contributor = Contributor { user: Pubkey::new_unique(), contribution: 10, allocation: 0 };
+        let data = old_account(1, Pubkey::new_unique(), vec![contributor]);
+        let err = DistributionState::read_any_version(&data).err().unwrap();
+        assert_eq!(err, DistributionError::ContributorsNotMigratable.into());
+    }
+
+    fn vesting() -> VestingSchedule {
+        VestingSchedule { tge_unlock_bps: 1_000, tge_timestamp: 100, cliff_seconds: 10, vesting_duration: 1_000 }
+    }
+
+    #[test]
+    fn vesting_unlocks_at_tge_then_linearly_after_the_cliff() {
+        let vesting = vesting();
+        assert_eq!(vesting.vested_amount(10_000, 99), 0);
+        assert_eq!(vesting.vested_amount(10_000, 100), 1_000);
+        assert_eq!(vesting.vested_amount(10_000, 109), 1_000);
+        assert_eq!(vesting.vested_amount(10_000, 110), 1_000);
+        assert_eq!(vesting.vested_amount(10_000, 610), 5_500);
+        assert_eq!(vesting.vested_amount(10_000, 1_109), 9_991);
+        assert_eq!(vesting.vested_amount(10_000, 1_110), 10_000);
+        assert_eq!(vesting.vested_amount(10_000, i64::MAX), 10_000);
+    }
+
+    #[test]
+    fn vesting_rounds_down_and_never_overflows() {
+        let vesting = vesting();
+        assert_eq!(vesting.vested_amount(3, 100), 0);
+        assert_eq!(vesting.vested_amount(3, 610), 1);
+        assert_eq!(vesting.vested_amount(u64::MAX, 610), u64::MAX / 10 + (u64::MAX - u64::MAX / 10) / 2);
+    }
+
+    #[test]
+    fn default_vesting_unlocks_everything_at_once() {
+        let vesting = VestingSchedule::default();
+        assert_eq!(vesting.vested_amount(10_000, -1), 0);
+        assert_eq!(vesting.vested_amount(10_000, 0), 10_000);
+    }
+
+    #[test]
+    fn tranches_unlock_their_share_once_matured() {
+        let state = DistributionState {
+            vesting: vesting(),
+            tranches: vec![
+                Tranche { unlock_timestamp: 300, bps: 5_000 },
+                Tranche { unlock_timestamp: 100, bps: 2_500 },
+                Tranche { unlock_timestamp: 200, bps: 2_500 },
+            ],
+            ..DistributionState::default()
+        };
+        assert_eq!(state.vested_amount(1_000, 99), 0);
+        assert_eq!(state.vested_amount(1_000, 100), 250);
+        assert_eq!(state.vested_amount(1_000, 250), 500);
+        assert_eq!(state.vested_amount(1_000, 300), 1_000);
+        assert_eq!(state.vested_amount(3, 100), 0);
+        assert_eq!(state.vested_amount(u64::MAX, 300), u64::MAX);
+    }
+
+    #[test]
+    fn without_tranches_the_vesting_schedule_applies() {
+        let state = DistributionState { vesting: vesting(), ..DistributionState::default() };
+        assert_eq!(state.vested_amount(10_000, 610), vesting().vested_amount(10_000, 610));
+    }
+
+    #[test]
+    fn repeated_claims_release_only_newly_vested_tokens() {
+        let state = DistributionState {
+            claim_enabled: true,
+            claim_period_open: true,
+            claim_start: 0,
+            claim_end: 2_000,
+            vesting: vesting(),
+            ..DistributionState::default()
+        };
+        let mut claimed = 0;
+
+        assert_eq!(state.take_claim(10_000, &mut claimed, 100).unwrap(), 1_000);
+        assert_eq!(
+            state.take_claim(10_000, &mut claimed, 105).unwrap_err(),
+            DistributionError::NothingToClaim.into()
+        );
+        assert_eq!(claimed, 1_000);
+        assert_eq!(state.take_claim(10_000, &mut claimed, 610).unwrap(), 4_500);
+        assert_eq!(state.take_claim(10_000, &mut claimed, 1_110).unwrap(), 4_500);
+        assert_eq!(claimed, 10_000);
+        assert!(state.take_claim(10_000, &mut claimed, 1_500).is_err());
+
+        assert!(state.require_claimable(1_999).is_ok());
+        assert_eq!(
+            state.require_claimable(2_000).unwrap_err(),
+            DistributionError::ClaimPeriodClosed.into()
+        );
+    }
+
+    fn allocations(contributions: &[u64]) -> Vec<Allocation> {
+        contributions
+            .iter()
+            .enumerate()
+            .map(|(index, &contribution)| Allocation { index: index as u32, contribution, ..Allocation::default() })
+            .collect()
+    }
+
+    #[test]
+    fn allocations_are_calculated_in_chunks_without_overflowing() {
+        // contribution * total_tokens exceeds u64 for every entry.
+        let mut state = DistributionState {
+            total_raised: 4_000_000_000_000,
+            contributor_count: 3,
+            allocation_total_tokens: 1_000_000_000_000_000,
+            ..DistributionState::default()
+        };
+        let mut entries = allocations(&[1_000_000_000_000, 1_000_000_000_000, 2_000_000_000_000]);
+
+        state.allocate_next(&mut entries[0]).unwrap();
+        state.allocate_next(&mut entries[1]).unwrap();
+        assert_eq!(state.allocation_cursor, 2);
+        assert_eq!(
+            state.allocate_next(&mut entries[0]).unwrap_err(),
+            DistributionError::InvalidAllocationAccount.into()
+        );
+        state.allocate_next(&mut entries[2]).unwrap();
+
+        assert_eq!(state.allocation_cursor, state.contributor_count);
+        assert_eq!(entries[0].allocation, 250_000_000_000_000);
+        assert_eq!(entries[1].allocation, 250_000_000_000_000);
+        assert_eq!(entries[2].allocation, 500_000_000_000_000);
+        assert_eq!(state.allocated_amount, state.allocation_total_tokens);
+    }
+
+    #[test]
+    fn an_allocation_too_large_for_u64_is_rejected() {
+        let mut state = DistributionState {
+            total_raised: 1,
+            allocation_total_tokens: u64::MAX,
+            ..DistributionState::default()
+        };
+        let mut entries = allocations(&[2]);
+        assert_eq!(
+            state.allocate_next(&mut entries[0]).unwrap_err(),
+            DistributionError::Overflow.into()
+        );
+    }
+
+    #[test]
+    fn recalculating_after_a_reset_overwrites_stale_allocations() {
+        let mut state = DistributionState {
+            total_raised: 100,
+            contributor_count: 2,
+            allocation_total_tokens: 1_000,
+            ..DistributionState::default()
+        };
+        let mut entries = allocations(&[60, 40]);
+        for entry in entries.iter_mut() {
+            state.allocate_next(entry).unwrap();
+        }
+        state.allocation_calculated = true;
+        state.claim_enabled = true;
+
+        state.clear_allocations();
+        assert!(!state.allocation_calculated && !state.claim_enabled);
+        assert_eq!((state.allocation_cursor, state.allocated_amount), (0, 0));
+
+        // The second contribution is removed without zeroing its allocation.
+        entries[1].contribution = 0;
+        state.total_raised = 60;
+        state.allocation_total_tokens = 1_000;
+        for entry in entries.iter_mut() {
+            state.allocate_next(entry).unwrap();
+        }
+        assert_eq!(entries[0].allocation, 1_000);
+        assert_eq!(entries[1].allocation, 0);
+        assert_eq!(state.allocated_amount, 1_000);
+    }
+
+    fn hash_pair(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        if a <= b {
+            hashv(&[&a, &b]).to_bytes()
+        } else {
+            hashv(&[&b, &a]).to_bytes()
+        }
+    }
+
+    #[test]
+    fn merkle_proofs_link_every_leaf_to_the_root() {
+        let leaves: Vec<[u8; 32]> = (1..=4u64)
+            .map(|allocation| hashv(&[Pubkey::new_unique().as_ref(), &allocation.to_le_bytes()]).to_bytes())
+            .collect();
+        let left = hash_pair(leaves[0], leaves[1]);
+        let right = hash_pair(leaves[2], leaves[3]);
+        let root = hash_pair(left, right);
+
+        assert!(verify_merkle_proof(&[leaves[1], right], root, leaves[0]));
+        assert!(verify_merkle_proof(&[leaves[0], right], root, leaves[1]));
+        assert!(verify_merkle_proof(&[leaves[3], left], root, leaves[2]));
+        assert!(verify_merkle_proof(&[leaves[2], left], root, leaves[3]));
+        assert!(verify_merkle_proof(&[], leaves[0], leaves[0]));
+    }
+
+    #[test]
+    fn merkle_proofs_reject_wrong_leaves_and_siblings() {
+        let leaves: Vec<[u8; 32]> = (1..=4u8).map(|byte| [byte; 32]).collect();
+        let left = hash_pair(leaves[0], leaves[1]);
+        let right = hash_pair(leaves[2], leaves[3]);
+        let root = hash_pair(left, right);
+
+        assert!(!verify_merkle_proof(&[leaves[1], right], root, leaves[2]));
+        assert!(!verify_merkle_proof(&[leaves[2], right], root, leaves[0]));
+        assert!(!verify_merkle_proof(&[leaves[1]], root, leaves[0]));
+        assert!(!verify_merkle_proof(&[leaves[1], right, left], root, leaves[0]));
+        assert!(!verify_merkle_proof(&[], root, leaves[0]));
+    }
 }
@@ -1,182 +1,1883 @@
-use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, TokenAccount, Mint};
-use crate::state::*;
-
-#[derive(Accounts)]
-#[instruction(
-    tier_names: Vec<String>,
-    tier_max_contributions: Vec<u64>,
-    min_contribution: u64,
-    hard_cap: u64,
-)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = owner,
-        space = 8 + Presale::LEN,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    #[account(mut)]
-    pub owner: Signer<'info>,
-    pub usdt_mint: Account<'info, Mint>,
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-}
-
-#[derive(Accounts)]
-pub struct CreateTier<'info> {
-    #[account(
-        mut,
-        has_one = owner,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    pub owner: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct AssignTier<'info> {
-    #[account(
-        mut,
-        has_one = owner,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    pub owner: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct BulkAssignTiers<'info> {
-    #[account(
-        mut,
-        has_one = owner,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    pub owner: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct RemoveUser<'info> {
-    #[account(
-        mut,
-        has_one = owner,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    pub owner: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct UpdateUserTier<'info> {
-    #[account(
-        mut,
-        has_one = owner,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    pub owner: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct Contribute<'info> {
-    #[account(mut, seeds = [b"presale", owner.key().as_ref()], bump)]
-    pub presale: Account<'info, Presale>,
-    pub owner: UncheckedAccount<'info>,
-    pub user: Signer<'info>,
-    #[account(mut, constraint = user_usdt.mint == presale.usdt_mint)]
-    pub user_usdt: Account<'info, TokenAccount>,
-    #[account(mut, constraint = presale_usdt.owner == presale.key(), constraint = presale_usdt.mint == presale.usdt_mint)]
-    pub presale_usdt: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
-}
-
-#[derive(Accounts)]
-pub struct ClosePresale<'info> {
-    #[account(
-        mut,
-        has_one = owner,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    pub owner: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct WithdrawFunds<'info> {
-    #[account(
-        mut,
-        has_one = owner,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    #[account(mut, constraint = presale_usdt.owner == presale.key(), constraint = presale_usdt.mint == presale.usdt_mint)]
-    pub presale_usdt: Account<'info, TokenAccount>,
-    #[account(mut, constraint = owner_usdt.mint == presale.usdt_mint)]
-    pub owner_usdt: Account<'info, TokenAccount>,
-    pub owner: Signer<'info>,
-    pub token_program: Program<'info, Token>,
-}
-
-#[derive(Accounts)]
-pub struct Refund<'info> {
-    #[account(
-        mut,
-        has_one = owner,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    pub owner: UncheckedAccount<'info>,
-    pub user: Signer<'info>,
-    #[account(mut, constraint = presale_usdt.owner == presale.key(), constraint = presale_usdt.mint == presale.usdt_mint)]
-    pub presale_usdt: Account<'info, TokenAccount>,
-    #[account(mut, constraint = user_usdt.mint == presale.usdt_mint)]
-    pub user_usdt: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
-}
-
-#[derive(Accounts)]
-pub struct UpdatePresale<'info> {
-    #[account(
-        mut,
-        has_one = owner,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    pub owner: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct PausePresale<'info> {
-    #[account(
-        mut,
-        has_one = owner,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    pub owner: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct UnpausePresale<'info> {
-    #[account(
-        mut,
-        has_one = owner,
-        seeds = [b"presale", owner.key().as_ref()],
-        bump
-    )]
-    pub presale: Account<'info, Presale>,
-    pub owner: Signer<'info>,
-} 
\ No newline at end of file
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{Token, TokenAccount, Mint};
+use anchor_spl::token_interface::{self, TokenInterface};
+use crate::state::*;
+use crate::error::*;
+
+#[derive(Accounts)]
+#[instruction(
+    tier_names: Vec<String>,
+    tier_max_contributions: Vec<u64>,
+    tier_tokens_per_usdt: Vec<u64>,
+    min_contribution: u64,
+    hard_cap: u64,
+    soft_cap: u64,
+    start_time: i64,
+    end_time: i64,
+    name: String,
+    symbol: String,
+    description: String,
+    sale_id: u64,
+)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = SaleConfig::LEN,
+        seeds = [b"presale", owner.key().as_ref(), &sale_id.to_le_bytes()],
+        bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        init,
+        payer = owner,
+        space = Registry::LEN,
+        seeds = [b"sale_registry", presale.key().as_ref()],
+        bump
+    )]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// CHECK: The owner's `SaleCounter` PDA, created by their first presale;
+    /// checked by the handler.
+    #[account(mut)]
+    pub sale_counter: UncheckedAccount<'info>,
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [b"registry"], bump = registry.bump)]
+    pub registry: Account<'info, PresaleRegistry>,
+    #[account(
+        init,
+        payer = owner,
+        space = RegistryEntry::LEN,
+        seeds = [b"registry_entry", registry.presale_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub registry_entry: Account<'info, RegistryEntry>,
+    pub usdt_mint: InterfaceAccount<'info, token_interface::Mint>,
+    /// CHECK: The vault authority's USDT associated token account, created
+    /// by the handler; the associated token program checks the address.
+    #[account(mut)]
+    pub presale_usdt: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = GlobalConfig::LEN,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, GlobalConfig>,
+    #[account(
+        init,
+        payer = authority,
+        space = PresaleRegistry::LEN,
+        seeds = [b"registry"],
+        bump
+    )]
+    pub registry: Account<'info, PresaleRegistry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, GlobalConfig>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePresale<'info> {
+    /// CHECK: May still hold a layout `AccountLoader` cannot read; the
+    /// handler checks the discriminator, the PDA and the owner.
+    #[account(mut, owner = crate::ID)]
+    pub presale: UncheckedAccount<'info>,
+    /// CHECK: The presale's `Registry` PDA, created by the handler when the
+    /// presale predates it.
+    #[account(mut, seeds = [b"sale_registry", presale.key().as_ref()], bump)]
+    pub sale_registry: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateTier<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut, seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// Matured queued change for this call; required while a timelock
+    /// is configured. Closed once applied.
+    #[account(mut, close = owner)]
+    pub queued_change: Option<Account<'info, QueuedChange>>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct AssignTier<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.is_operator(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(
+        init,
+        payer = authority,
+        space = WhitelistEntry::LEN,
+        seeds = [b"wl", presale.key().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+    /// CHECK: The user's whitelist audit trail PDA; created on first use.
+    #[account(
+        mut,
+        seeds = [b"audit", presale.key().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub audit_trail: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BulkAssignTiers<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.is_operator(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct RemoveUser<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.is_operator(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"wl", presale.key().as_ref(), user.as_ref()],
+        bump = whitelist_entry.bump
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BulkRemoveUsers<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.is_operator(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct UpdateUserTier<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.is_operator(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(
+        mut,
+        seeds = [b"wl", presale.key().as_ref(), user.as_ref()],
+        bump = whitelist_entry.bump
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+    /// CHECK: The user's `ContributionAccount` PDA; uninitialized until they
+    /// contribute, otherwise moved to the new tier by `update_user_tier`.
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub contribution_account: UncheckedAccount<'info>,
+    /// CHECK: The user's whitelist audit trail PDA; created on first use.
+    #[account(
+        mut,
+        seeds = [b"audit", presale.key().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub audit_trail: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Contribute<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: The user's `ContributionAccount` PDA, created on first contribution.
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub contribution_account: UncheckedAccount<'info>,
+    /// CHECK: The user's whitelist entry PDA. It may be uninitialized when
+    /// the presale is in public sale mode; `contribute` deserializes it otherwise.
+    #[account(
+        mut,
+        seeds = [b"wl", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub whitelist_entry: UncheckedAccount<'info>,
+    /// CHECK: The user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    #[account(constraint = presale.load()?.payment_mints().contains(&payment_mint.key()))]
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(mut, constraint = user_usdt.mint == payment_mint.key())]
+    pub user_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    /// The user's account of the gating token, used to resolve balance-gated tiers.
+    pub gating_token_account: Option<Account<'info, TokenAccount>>,
+    /// Required when the presale has a `kyc_issuer` configured.
+    pub kyc_attestation: Option<Account<'info, KycAttestation>>,
+    /// Receipt accounts; required when the presale has a `receipt_mint`.
+    #[account(mut)]
+    pub receipt_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub user_receipt: Option<Account<'info, TokenAccount>>,
+    pub receipt_token_program: Option<Program<'info, Token>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ContributeWithPermit<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    /// CHECK: The contributor; authorizes the contribution with an Ed25519
+    /// signature over the permit instead of signing the transaction.
+    pub user: UncheckedAccount<'info>,
+    /// Submits the transaction and pays its fees.
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+    /// CHECK: The user's `ContributionAccount` PDA, created on first contribution.
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub contribution_account: UncheckedAccount<'info>,
+    /// CHECK: The user's whitelist entry PDA; deserialized by `contribute_with_permit`.
+    #[account(
+        mut,
+        seeds = [b"wl", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub whitelist_entry: UncheckedAccount<'info>,
+    /// CHECK: The user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    #[account(constraint = presale.load()?.payment_mints().contains(&payment_mint.key()))]
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        constraint = user_usdt.mint == payment_mint.key(),
+        constraint = user_usdt.owner == user.key() @ PresaleError::InvalidUserUsdtAccount
+    )]
+    pub user_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    /// The user's account of the gating token, used to resolve balance-gated tiers.
+    pub gating_token_account: Option<Account<'info, TokenAccount>>,
+    /// Required when the presale has a `kyc_issuer` configured.
+    pub kyc_attestation: Option<Account<'info, KycAttestation>>,
+    /// CHECK: The instructions sysvar, used to find the Ed25519 signature check.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    /// Receipt accounts; required when the presale has a `receipt_mint`.
+    #[account(mut)]
+    pub receipt_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub user_receipt: Option<Account<'info, TokenAccount>>,
+    pub receipt_token_program: Option<Program<'info, Token>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct ContributeFor<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    pub owner: Signer<'info>,
+    /// The settlement wallet funding the contribution; may be the owner.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: The credited user's `ContributionAccount` PDA, created on
+    /// their first contribution.
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub contribution_account: UncheckedAccount<'info>,
+    /// CHECK: The credited user's whitelist entry PDA; deserialized by `contribute_for`.
+    #[account(
+        mut,
+        seeds = [b"wl", presale.key().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub whitelist_entry: UncheckedAccount<'info>,
+    /// CHECK: The credited user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    #[account(constraint = presale.load()?.payment_mints().contains(&payment_mint.key()))]
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        constraint = payer_usdt.mint == payment_mint.key(),
+        constraint = payer_usdt.owner == payer.key()
+    )]
+    pub payer_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    /// Required when the presale has a `kyc_issuer` configured.
+    pub kyc_attestation: Option<Account<'info, KycAttestation>>,
+    /// Receipt accounts; required when the presale has a `receipt_mint`.
+    #[account(mut)]
+    pub receipt_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub user_receipt: Option<Account<'info, TokenAccount>>,
+    pub receipt_token_program: Option<Program<'info, Token>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CommitContribution<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: The user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = user,
+        space = ContributionCommitment::LEN,
+        seeds = [b"commit", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub commitment: Account<'info, ContributionCommitment>,
+    #[account(constraint = presale.load()?.payment_mints().contains(&payment_mint.key()))]
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        constraint = user_usdt.mint == payment_mint.key(),
+        constraint = user_usdt.owner == user.key() @ PresaleError::InvalidUserUsdtAccount
+    )]
+    pub user_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevealContribution<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: The user's `ContributionAccount` PDA, created on first contribution.
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub contribution_account: UncheckedAccount<'info>,
+    /// CHECK: The user's whitelist entry PDA; deserialized by `reveal_contribution`.
+    #[account(
+        mut,
+        seeds = [b"wl", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub whitelist_entry: UncheckedAccount<'info>,
+    /// CHECK: The user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"commit", presale.key().as_ref(), user.key().as_ref()],
+        bump = commitment.bump,
+        constraint = commitment.payment_mint == payment_mint.key()
+    )]
+    pub commitment: Account<'info, ContributionCommitment>,
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        constraint = user_usdt.mint == payment_mint.key(),
+        constraint = user_usdt.owner == user.key() @ PresaleError::InvalidUserUsdtAccount
+    )]
+    pub user_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    /// The user's account of the gating token, used to resolve balance-gated tiers.
+    pub gating_token_account: Option<Account<'info, TokenAccount>>,
+    /// Required when the presale has a `kyc_issuer` configured.
+    pub kyc_attestation: Option<Account<'info, KycAttestation>>,
+    /// Receipt accounts; required when the presale has a `receipt_mint`.
+    #[account(mut)]
+    pub receipt_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub user_receipt: Option<Account<'info, TokenAccount>>,
+    pub receipt_token_program: Option<Program<'info, Token>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimCommitment<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"commit", presale.key().as_ref(), user.key().as_ref()],
+        bump = commitment.bump,
+        constraint = commitment.payment_mint == payment_mint.key()
+    )]
+    pub commitment: Account<'info, ContributionCommitment>,
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        constraint = user_usdt.mint == payment_mint.key(),
+        constraint = user_usdt.owner == user.key() @ PresaleError::InvalidUserUsdtAccount
+    )]
+    pub user_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct BuyLotteryTicket<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: The user's whitelist entry PDA; deserialized by `buy_lottery_ticket`.
+    #[account(
+        seeds = [b"wl", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub whitelist_entry: UncheckedAccount<'info>,
+    /// CHECK: The user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = user,
+        space = LotteryTicket::LEN,
+        seeds = [b"ticket", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub ticket: Account<'info, LotteryTicket>,
+    #[account(constraint = presale.load()?.payment_mints().contains(&payment_mint.key()))]
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        constraint = user_usdt.mint == payment_mint.key(),
+        constraint = user_usdt.owner == user.key() @ PresaleError::InvalidUserUsdtAccount
+    )]
+    pub user_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    /// The user's account of the gating token, used to resolve balance-gated tiers.
+    pub gating_token_account: Option<Account<'info, TokenAccount>>,
+    /// Required when the presale has a `kyc_issuer` configured.
+    pub kyc_attestation: Option<Account<'info, KycAttestation>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DrawLottery<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    pub owner: Signer<'info>,
+    /// CHECK: The SlotHashes sysvar, read raw by `lottery_entropy`.
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub slot_hashes: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SettleLotteryTicket<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// CHECK: The ticket holder; receives the refund or the ticket's rent.
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    /// Pays for a winner's `ContributionAccount` when it does not exist yet.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: The holder's `ContributionAccount` PDA, created when a winning
+    /// ticket is their first contribution.
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub contribution_account: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"ticket", presale.key().as_ref(), user.key().as_ref()],
+        bump = ticket.bump,
+        constraint = ticket.payment_mint == payment_mint.key()
+    )]
+    pub ticket: Account<'info, LotteryTicket>,
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        constraint = user_usdt.mint == payment_mint.key(),
+        constraint = user_usdt.owner == user.key() @ PresaleError::InvalidUserUsdtAccount
+    )]
+    pub user_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    /// Receipt accounts; required for winners when the presale has a `receipt_mint`.
+    #[account(mut)]
+    pub receipt_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub user_receipt: Option<Account<'info, TokenAccount>>,
+    pub receipt_token_program: Option<Program<'info, Token>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefundLotteryTicket<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// CHECK: The ticket holder; receives the refund and the ticket's rent.
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"ticket", presale.key().as_ref(), user.key().as_ref()],
+        bump = ticket.bump,
+        constraint = ticket.payment_mint == payment_mint.key()
+    )]
+    pub ticket: Account<'info, LotteryTicket>,
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        constraint = user_usdt.mint == payment_mint.key(),
+        constraint = user_usdt.owner == user.key() @ PresaleError::InvalidUserUsdtAccount
+    )]
+    pub user_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ContributeSol<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: The user's `ContributionAccount` PDA, created on first contribution.
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub contribution_account: UncheckedAccount<'info>,
+    /// CHECK: The user's whitelist entry PDA. It may be uninitialized when
+    /// the presale is in public sale mode; `contribute_sol` deserializes it otherwise.
+    #[account(
+        mut,
+        seeds = [b"wl", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub whitelist_entry: UncheckedAccount<'info>,
+    /// CHECK: The user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"sol_vault", presale.key().as_ref()], bump = presale.load()?.sol_vault_bump)]
+    pub sol_vault: SystemAccount<'info>,
+    /// CHECK: Pyth SOL/USD price account; checked against `presale.sol_price_feed`
+    /// and parsed by the oracle adapter.
+    pub sol_price_feed: Option<UncheckedAccount<'info>>,
+    /// The user's account of the gating token, used to resolve balance-gated tiers.
+    pub gating_token_account: Option<Account<'info, TokenAccount>>,
+    /// Required when the presale has a `kyc_issuer` configured.
+    pub kyc_attestation: Option<Account<'info, KycAttestation>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePresale<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"registry_entry", registry_entry.index.to_le_bytes().as_ref()],
+        bump = registry_entry.bump,
+        constraint = registry_entry.presale == presale.key() @ PresaleError::PresaleNotRegistered
+    )]
+    pub registry_entry: Account<'info, RegistryEntry>,
+    /// Council-approved proposal for this action; required when the
+    /// presale has a council.
+    #[account(mut)]
+    pub proposal: Option<Account<'info, AdminProposal>>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePresaleAccount<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        close = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut, close = owner, seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(seeds = [b"sol_vault", presale.key().as_ref()], bump = presale.load()?.sol_vault_bump)]
+    pub sol_vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"registry_entry", registry_entry.index.to_le_bytes().as_ref()],
+        bump = registry_entry.bump,
+        constraint = registry_entry.presale == presale.key() @ PresaleError::PresaleNotRegistered
+    )]
+    pub registry_entry: Account<'info, RegistryEntry>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizePresale<'info> {
+    #[account(
+        mut,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        mut,
+        seeds = [b"registry_entry", registry_entry.index.to_le_bytes().as_ref()],
+        bump = registry_entry.bump,
+        constraint = registry_entry.presale == presale.key() @ PresaleError::PresaleNotRegistered
+    )]
+    pub registry_entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeAllocation<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefundSol<'info> {
+    #[account(
+        mut,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: The user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    /// CHECK: The user's `RefundTicket` PDA, created on first refund.
+    #[account(
+        mut,
+        seeds = [b"refund", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub refund_ticket: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.key().as_ref()],
+        bump = contribution_account.bump
+    )]
+    pub contribution_account: Account<'info, ContributionAccount>,
+    #[account(mut, seeds = [b"sol_vault", presale.key().as_ref()], bump = presale.load()?.sol_vault_bump)]
+    pub sol_vault: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BulkRefund<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(constraint = presale.load()?.payment_mints().contains(&payment_mint.key()))]
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimExcess<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// CHECK: The contributor the excess is returned to.
+    pub user: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.key().as_ref()],
+        bump = contribution_account.bump
+    )]
+    pub contribution_account: Account<'info, ContributionAccount>,
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        constraint = user_usdt.mint == payment_mint.key(),
+        constraint = user_usdt.owner == user.key() @ PresaleError::InvalidUserUsdtAccount
+    )]
+    pub user_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFunds<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.is_treasurer(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(constraint = presale.load()?.payment_mints().contains(&payment_mint.key()))]
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = owner_usdt.mint == payment_mint.key())]
+    pub owner_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    /// Platform fee destination; required when a platform fee is configured.
+    #[account(
+        mut,
+        constraint = platform_fee_usdt.mint == payment_mint.key(),
+        constraint = platform_fee_usdt.owner == presale.load()?.platform_fee_recipient @ PresaleError::InvalidPlatformFeeAccount
+    )]
+    pub platform_fee_usdt: Option<InterfaceAccount<'info, token_interface::TokenAccount>>,
+    pub authority: Signer<'info>,
+    /// Council-approved proposal for this action; required when the
+    /// presale has a council.
+    #[account(mut)]
+    pub proposal: Option<Account<'info, AdminProposal>>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteEmergencyWithdraw<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub owner: Signer<'info>,
+    #[account(constraint = presale.load()?.payment_mints().contains(&payment_mint.key()))]
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(
+        mut,
+        constraint = destination_usdt.mint == payment_mint.key(),
+        constraint = destination_usdt.owner == presale.load()?.emergency_withdraw_destination
+            @ PresaleError::InvalidEmergencyWithdrawDestination
+    )]
+    pub destination_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RescueTokens<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    pub owner: Signer<'info>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(constraint = !presale.load()?.payment_mints().contains(&mint.key()) @ PresaleError::CannotRescuePaymentMint)]
+    pub mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(mut, constraint = presale_token.owner == vault_authority.key(), constraint = presale_token.mint == mint.key())]
+    pub presale_token: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut, constraint = destination_token.mint == mint.key())]
+    pub destination_token: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SweepUnclaimedRefunds<'info> {
+    #[account(
+        mut,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(constraint = presale.load()?.payment_mints().contains(&payment_mint.key()))]
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(
+        mut,
+        constraint = treasury_usdt.mint == payment_mint.key(),
+        constraint = Some(treasury_usdt.owner) == presale.load()?.treasury() @ PresaleError::InvalidTreasury
+    )]
+    pub treasury_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawSol<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.is_treasurer(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut, seeds = [b"sol_vault", presale.key().as_ref()], bump = presale.load()?.sol_vault_bump)]
+    pub sol_vault: SystemAccount<'info>,
+    /// Receives the platform fee; required when the presale charges one.
+    #[account(
+        mut,
+        address = presale.load()?.platform_fee_recipient @ PresaleError::InvalidPlatformFeeAccount
+    )]
+    pub platform_fee_recipient: Option<SystemAccount<'info>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Council-approved proposal for this action; required when the
+    /// presale has a council.
+    #[account(mut)]
+    pub proposal: Option<Account<'info, AdminProposal>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Refund<'info> {
+    #[account(
+        mut,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: The user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    /// CHECK: The user's `RefundTicket` PDA, created on first refund.
+    #[account(
+        mut,
+        seeds = [b"refund", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub refund_ticket: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.key().as_ref()],
+        bump = contribution_account.bump
+    )]
+    pub contribution_account: Account<'info, ContributionAccount>,
+    #[account(constraint = presale.load()?.payment_mints().contains(&payment_mint.key()))]
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    /// Refund destination. It may be any account of the payment mint owned
+    /// by the user, or one owned by `destination_authority` when that owner
+    /// co-signs (e.g. the user's original account was closed).
+    #[account(mut, constraint = user_usdt.mint == payment_mint.key())]
+    pub user_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub destination_authority: Option<Signer<'info>>,
+    /// Receipt accounts; required when the presale has a `receipt_mint`.
+    #[account(mut)]
+    pub receipt_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub user_receipt: Option<Account<'info, TokenAccount>>,
+    pub receipt_token_program: Option<Program<'info, Token>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ComplianceClawback<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// CHECK: The flagged wallet being refunded.
+    pub user: UncheckedAccount<'info>,
+    /// CHECK: The user's `RefundTicket` PDA, created on first refund.
+    #[account(
+        mut,
+        seeds = [b"refund", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub refund_ticket: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.key().as_ref()],
+        bump = contribution_account.bump
+    )]
+    pub contribution_account: Account<'info, ContributionAccount>,
+    #[account(constraint = presale.load()?.payment_mints().contains(&payment_mint.key()))]
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(mut, constraint = presale.load()?.payment_vault(&payment_mint.key()) == Some(presale_usdt.key()) @ PresaleError::InvalidVaultAccount)]
+    pub presale_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_usdt.mint == payment_mint.key(),
+        constraint = user_usdt.owner == user.key() @ PresaleError::InvalidUserUsdtAccount
+    )]
+    pub user_usdt: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseRefundTicket<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"refund", presale.key().as_ref(), user.key().as_ref()],
+        bump = refund_ticket.bump
+    )]
+    pub refund_ticket: Account<'info, RefundTicket>,
+}
+
+#[derive(Accounts)]
+pub struct CloseUserAccount<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        mut,
+        close = user,
+        has_one = user,
+        seeds = [b"contribution", presale.key().as_ref(), user.key().as_ref()],
+        bump = contribution_account.bump
+    )]
+    pub contribution_account: Account<'info, ContributionAccount>,
+    /// The user's whitelist entry, if any; closed along with the account.
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"wl", presale.key().as_ref(), user.key().as_ref()],
+        bump = whitelist_entry.bump
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+    /// CHECK: The contributor the account belongs to; receives the rent.
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    /// The user, or the presale owner once the claim deadline has passed.
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct AddToBlacklist<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.is_operator(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        init,
+        payer = authority,
+        space = BlacklistEntry::LEN,
+        seeds = [b"bl", presale.key().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub blacklist_entry: Account<'info, BlacklistEntry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct RemoveFromBlacklist<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.is_operator(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"bl", presale.key().as_ref(), user.as_ref()],
+        bump = blacklist_entry.bump
+    )]
+    pub blacklist_entry: Account<'info, BlacklistEntry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RequestWhitelist<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        init,
+        payer = user,
+        space = RegistrationRequest::LEN,
+        seeds = [b"reg", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub registration: Account<'info, RegistrationRequest>,
+    /// CHECK: The user's whitelist entry PDA; it must not exist yet.
+    #[account(
+        seeds = [b"wl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = whitelist_entry.data_is_empty() @ PresaleError::UserAlreadyWhitelisted
+    )]
+    pub whitelist_entry: UncheckedAccount<'info>,
+    /// CHECK: The user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveRegistration<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.is_operator(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"reg", presale.key().as_ref(), user.key().as_ref()],
+        bump = registration.bump
+    )]
+    pub registration: Account<'info, RegistrationRequest>,
+    #[account(
+        init,
+        payer = authority,
+        space = WhitelistEntry::LEN,
+        seeds = [b"wl", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+    /// CHECK: The user's whitelist audit trail PDA; created on first use.
+    #[account(
+        mut,
+        seeds = [b"audit", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub audit_trail: UncheckedAccount<'info>,
+    /// CHECK: The registering wallet; receives the registration rent back.
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RejectRegistration<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.is_operator(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"reg", presale.key().as_ref(), user.key().as_ref()],
+        bump = registration.bump
+    )]
+    pub registration: Account<'info, RegistrationRequest>,
+    /// CHECK: The registering wallet; receives the registration rent back.
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateReceiptMint<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"receipt", presale.key().as_ref()],
+        bump,
+        mint::decimals = 6,
+        mint::authority = presale
+    )]
+    pub receipt_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct MintReceiptNft<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        seeds = [b"contribution", presale.key().as_ref(), user.key().as_ref()],
+        bump = contribution_account.bump
+    )]
+    pub contribution_account: Account<'info, ContributionAccount>,
+    /// CHECK: The user's whitelist entry PDA; deserialized by `mint_receipt_nft`.
+    #[account(
+        seeds = [b"wl", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub whitelist_entry: UncheckedAccount<'info>,
+    /// The user's account of the gating token, used to resolve balance-gated tiers.
+    pub gating_token_account: Option<Account<'info, TokenAccount>>,
+    /// Required when the presale has a `kyc_issuer` configured.
+    pub kyc_attestation: Option<Account<'info, KycAttestation>>,
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"receipt_nft", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = presale,
+        mint::freeze_authority = presale
+    )]
+    pub receipt_nft: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = user,
+        associated_token::mint = receipt_nft,
+        associated_token::authority = user
+    )]
+    pub user_receipt_nft: Account<'info, TokenAccount>,
+    /// CHECK: Metadata PDA of `receipt_nft`; validated by the Token Metadata program.
+    #[account(mut)]
+    pub metadata: UncheckedAccount<'info>,
+    /// CHECK: Master edition PDA of `receipt_nft`; validated by the Token Metadata program.
+    #[account(mut)]
+    pub master_edition: UncheckedAccount<'info>,
+    /// CHECK: The Token Metadata program.
+    #[account(address = mpl_token_metadata::ID)]
+    pub token_metadata_program: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(tier: u8)]
+pub struct CreateTierPass<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"pass", presale.key().as_ref(), &[tier]],
+        bump,
+        mint::decimals = 0,
+        mint::authority = presale
+    )]
+    pub pass_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(tier: u8)]
+pub struct MintTierPass<'info> {
+    #[account(
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(
+        mut,
+        seeds = [b"pass", presale.key().as_ref(), &[tier]],
+        bump
+    )]
+    pub pass_mint: Account<'info, Mint>,
+    #[account(mut, constraint = destination.mint == pass_mint.key())]
+    pub destination: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(tier: u8)]
+pub struct RedeemPass<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(
+        mut,
+        seeds = [b"pass", presale.key().as_ref(), &[tier]],
+        bump
+    )]
+    pub pass_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = user_pass.mint == pass_mint.key(),
+        constraint = user_pass.owner == user.key()
+    )]
+    pub user_pass: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = user,
+        space = WhitelistEntry::LEN,
+        seeds = [b"wl", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+    /// CHECK: The user's whitelist audit trail PDA; created on first use.
+    #[account(
+        mut,
+        seeds = [b"audit", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub audit_trail: UncheckedAccount<'info>,
+    /// CHECK: The user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemCnftPass<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    /// CHECK: Bubblegum tree config PDA; validated by the Bubblegum burn CPI.
+    pub tree_config: UncheckedAccount<'info>,
+    /// CHECK: The configured pass merkle tree; the proof is verified by Bubblegum.
+    #[account(
+        mut,
+        constraint = Some(merkle_tree.key()) == sale_registry.load()?.pass_merkle_tree() @ PresaleError::InvalidPassMerkleTree
+    )]
+    pub merkle_tree: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = user,
+        space = WhitelistEntry::LEN,
+        seeds = [b"wl", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+    /// CHECK: The user's whitelist audit trail PDA; created on first use.
+    #[account(
+        mut,
+        seeds = [b"audit", presale.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub audit_trail: UncheckedAccount<'info>,
+    /// CHECK: The user's blacklist entry PDA; it must not exist.
+    #[account(
+        seeds = [b"bl", presale.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = blacklist_entry.data_is_empty() @ PresaleError::UserBlacklisted
+    )]
+    pub blacklist_entry: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: Bubblegum program.
+    #[account(address = mpl_bubblegum::ID)]
+    pub bubblegum_program: UncheckedAccount<'info>,
+    /// CHECK: SPL noop program used by Bubblegum for logging.
+    #[account(address = spl_noop::id())]
+    pub log_wrapper: UncheckedAccount<'info>,
+    /// CHECK: SPL account compression program.
+    #[account(address = spl_account_compression::id())]
+    pub compression_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct IssueKycAttestation<'info> {
+    #[account(
+        init,
+        payer = issuer,
+        space = KycAttestation::LEN,
+        seeds = [b"kyc", issuer.key().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub kyc_attestation: Account<'info, KycAttestation>,
+    #[account(mut)]
+    pub issuer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct RevokeKycAttestation<'info> {
+    #[account(
+        mut,
+        close = issuer,
+        has_one = issuer,
+        seeds = [b"kyc", issuer.key().as_ref(), user.as_ref()],
+        bump = kyc_attestation.bump
+    )]
+    pub kyc_attestation: Account<'info, KycAttestation>,
+    #[account(mut)]
+    pub issuer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct SetUserLimit<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// CHECK: The wallet's `ContributionAccount` PDA, which holds the limit;
+    /// created here when the wallet has not contributed yet.
+    #[account(
+        mut,
+        seeds = [b"contribution", presale.key().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub contribution_account: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePresale<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    pub owner: Signer<'info>,
+}
+
+/// Owner update of the tier definitions or whitelist settings kept in the
+/// presale's `Registry`.
+#[derive(Accounts)]
+pub struct UpdateRegistry<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut, seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    pub owner: Signer<'info>,
+}
+
+/// Owner-driven status change (cancel, reopen) mirrored into the registry.
+#[derive(Accounts)]
+pub struct SetPresaleStatus<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"registry_entry", registry_entry.index.to_le_bytes().as_ref()],
+        bump = registry_entry.bump,
+        constraint = registry_entry.presale == presale.key() @ PresaleError::PresaleNotRegistered
+    )]
+    pub registry_entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+pub struct GovernedUpdate<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// Council-approved proposal for this action; required when the
+    /// presale has a council.
+    #[account(mut)]
+    pub proposal: Option<Account<'info, AdminProposal>>,
+    /// Matured queued change for this call; required while a timelock
+    /// is configured. Closed once applied.
+    #[account(mut, close = owner)]
+    pub queued_change: Option<Account<'info, QueuedChange>>,
+}
+
+#[derive(Accounts)]
+pub struct TimelockedUpdate<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// Matured queued change for this call; required while a timelock
+    /// is configured. Closed once applied.
+    #[account(mut, close = owner)]
+    pub queued_change: Option<Account<'info, QueuedChange>>,
+}
+
+/// `TimelockedUpdate` for changes to a tier's definition.
+#[derive(Accounts)]
+pub struct TimelockedTierUpdate<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(mut, seeds = [b"sale_registry", presale.key().as_ref()], bump = sale_registry.load()?.bump)]
+    pub sale_registry: AccountLoader<'info, Registry>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// Matured queued change for this call; required while a timelock
+    /// is configured. Closed once applied.
+    #[account(mut, close = owner)]
+    pub queued_change: Option<Account<'info, QueuedChange>>,
+}
+
+#[derive(Accounts)]
+pub struct QueueChange<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        init,
+        payer = owner,
+        space = QueuedChange::LEN,
+        seeds = [b"change", presale.key().as_ref(), presale.load()?.change_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub queued_change: Account<'info, QueuedChange>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelChange<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"change", presale.key().as_ref(), queued_change.index.to_le_bytes().as_ref()],
+        bump = queued_change.bump
+    )]
+    pub queued_change: Account<'info, QueuedChange>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateAdminProposal<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        init,
+        payer = member,
+        space = AdminProposal::LEN,
+        seeds = [b"proposal", presale.key().as_ref(), presale.load()?.proposal_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub proposal: Account<'info, AdminProposal>,
+    #[account(
+        mut,
+        constraint = presale.load()?.council().contains(&member.key()) @ PresaleError::NotCouncilMember
+    )]
+    pub member: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveAdminProposal<'info> {
+    #[account(mut, seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()], bump = presale.load()?.bump)]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    #[account(
+        mut,
+        seeds = [b"proposal", presale.key().as_ref(), proposal.index.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, AdminProposal>,
+    #[account(constraint = presale.load()?.council().contains(&member.key()) @ PresaleError::NotCouncilMember)]
+    pub member: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddPaymentMint<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    /// CHECK: PDA that owns the presale's token vaults and signs transfers
+    /// out of them; holds no data.
+    #[account(seeds = [b"vault", presale.key().as_ref()], bump = presale.load()?.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = presale.load()?.config)]
+    pub config: Account<'info, GlobalConfig>,
+    pub payment_mint: InterfaceAccount<'info, token_interface::Mint>,
+    /// CHECK: The vault authority's associated token account for
+    /// `payment_mint`, created by the handler; the associated token program
+    /// checks the address.
+    #[account(mut)]
+    pub presale_vault: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct PausePresale<'info> {
+    #[account(
+        mut,
+        constraint = presale.load()?.can_pause(&authority.key()) @ PresaleError::Unauthorized,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOwnership<'info> {
+    #[account(
+        mut,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump,
+        constraint = presale.load()?.pending_owner() == Some(new_owner.key()) @ PresaleError::NotPendingOwner
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    pub new_owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TriggerEmergency<'info> {
+    #[account(
+        mut,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump,
+        constraint = presale.load()?.guardian() == Some(guardian.key()) @ PresaleError::NotGuardian
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    pub guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnpausePresale<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    pub owner: Signer<'info>,
+} 
+
+#[derive(Accounts)]
+pub struct TakeSnapshot<'info> {
+    #[account(
+        mut,
+        seeds = [b"presale", presale.load()?.creator.as_ref(), &presale.load()?.sale_id.to_le_bytes()],
+        bump = presale.load()?.bump
+    )]
+    pub presale: AccountLoader<'info, SaleConfig>,
+    /// CHECK: The presale's `PresaleSnapshots` PDA, created by the first
+    /// snapshot; checked by the handler.
+    #[account(mut)]
+    pub snapshots: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
\ No newline at end of file
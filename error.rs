@@ -1,87 +1,421 @@
-use anchor_lang::prelude::*;
-
-#[error_code]
-pub enum PresaleError {
-    #[msg("Presale is not active.")]
-    PresaleNotActive,
-    #[msg("Presale is closed.")]
-    PresaleClosed,
-    #[msg("User is not whitelisted.")]
-    UserNotWhitelisted,
-    #[msg("Tier does not exist.")]
-    TierDoesNotExist,
-    #[msg("Contribution exceeds hard cap.")]
-    ExceedsHardCap,
-    #[msg("Contribution below minimum limit.")]
-    BelowMinContribution,
-    #[msg("Contribution above maximum limit.")]
-    AboveMaxContribution,
-    #[msg("Tier data mismatch.")]
-    TierDataMismatch,
-    #[msg("Tier already exists.")]
-    TierAlreadyExists,
-    #[msg("Cannot assign to a non-existent tier.")]
-    InvalidTierName,
-    #[msg("Number of users and tiers do not match.")]
-    MismatchUsersTiers,
-    #[msg("User is already whitelisted.")]
-    UserAlreadyWhitelisted,
-    #[msg("No funds to withdraw.")]
-    NoFundsToWithdraw,
-    #[msg("Presale must be closed to withdraw funds.")]
-    PresaleNotClosed,
-    #[msg("Refunds are not allowed.")]
-    RefundsNotAllowed,
-    #[msg("No contributions to refund.")]
-    NoContributionsToRefund,
-    #[msg("Already refunded.")]
-    AlreadyRefunded,
-    #[msg("Invalid minimum contribution.")]
-    InvalidMinContribution,
-    #[msg("Invalid hard cap.")]
-    InvalidHardCap,
-    #[msg("Presale is already initialized.")]
-    PresaleAlreadyInitialized,
-    #[msg("Exceeds maximum number of tiers.")]
-    ExceedsMaxTiers,
-    #[msg("Exceeds maximum number of users.")]
-    ExceedsMaxUsers,
-    #[msg("Exceeds maximum bulk assign limit.")]
-    ExceedsBulkAssignLimit,
-    #[msg("Overflow occurred during calculation.")]
-    Overflow,
-    #[msg("User's new tier does not accommodate their current contributions.")]
-    ExceedsNewTierMaxContribution,
-    #[msg("Invalid user USDT account.")]
-    InvalidUserUsdtAccount,
-    #[msg("Tier name exceeds maximum allowed length.")]
-    TierNameTooLong,
-    #[msg("Presale is already paused.")]
-    PresaleAlreadyPaused,
-    #[msg("Presale is not paused.")]
-    PresaleNotPaused,
-    #[msg("Presale is paused.")]
-    PresalePaused,
-    #[msg("Contribution too small.")]
-    ContributionTooSmall,
-    #[msg("Invalid tier name format.")]
-    InvalidTierNameFormat,
-    #[msg("Hard cap must be greater than or equal to total contributions.")]
-    HardCapLessThanTotal,
-    #[msg("Arithmetic overflow occurred")]
-    Overflow,
-    #[msg("Hard cap must be less than tier maximum")]
-    HardCapLessThanTierMax,
-    #[msg("Invalid maximum contribution")]
-    InvalidMaxContribution,
-    #[msg("Presale is already closed")]
-    PresaleAlreadyClosed,
-}
-
-pub fn validate_tier_name(name: &str) -> Result<()> {
-    require!(
-        name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
-        PresaleError::InvalidTierNameFormat
-    );
-    Ok(())
-} 
\ No newline at end of file
+use anchor_lang::prelude::*;
+use crate::state::PayoutRecipient;
+use crate::{
+    MAX_ALLOWED_PAYMENT_MINTS, MAX_COUNCIL_MEMBERS, MAX_PAYOUT_RECIPIENTS, MAX_PRESALE_DESCRIPTION_LENGTH,
+    MAX_PRESALE_NAME_LENGTH, MAX_PRESALE_SYMBOL_LENGTH,
+};
+
+#[error_code]
+pub enum PresaleError {
+    #[msg("Presale is not active.")]
+    PresaleNotActive,
+    #[msg("Presale is closed.")]
+    PresaleClosed,
+    #[msg("User is not whitelisted.")]
+    UserNotWhitelisted,
+    #[msg("Tier does not exist.")]
+    TierDoesNotExist,
+    #[msg("Contribution exceeds hard cap.")]
+    ExceedsHardCap,
+    #[msg("Contribution below minimum limit.")]
+    BelowMinContribution,
+    #[msg("Contribution above maximum limit.")]
+    AboveMaxContribution,
+    #[msg("Tier data mismatch.")]
+    TierDataMismatch,
+    #[msg("Tier already exists.")]
+    TierAlreadyExists,
+    #[msg("Cannot assign to a non-existent tier.")]
+    InvalidTierName,
+    #[msg("Number of users and tiers do not match.")]
+    MismatchUsersTiers,
+    #[msg("User is already whitelisted.")]
+    UserAlreadyWhitelisted,
+    #[msg("No funds to withdraw.")]
+    NoFundsToWithdraw,
+    #[msg("Presale must be closed to withdraw funds.")]
+    PresaleNotClosed,
+    #[msg("Refunds are not allowed.")]
+    RefundsNotAllowed,
+    #[msg("No contributions to refund.")]
+    NoContributionsToRefund,
+    #[msg("Already refunded.")]
+    AlreadyRefunded,
+    #[msg("Invalid minimum contribution.")]
+    InvalidMinContribution,
+    #[msg("Invalid hard cap.")]
+    InvalidHardCap,
+    #[msg("Presale is already initialized.")]
+    PresaleAlreadyInitialized,
+    #[msg("Exceeds maximum number of tiers.")]
+    ExceedsMaxTiers,
+    #[msg("Exceeds maximum number of users.")]
+    ExceedsMaxUsers,
+    #[msg("Exceeds maximum bulk assign limit.")]
+    ExceedsBulkAssignLimit,
+    #[msg("Overflow occurred during calculation.")]
+    Overflow,
+    #[msg("User's new tier does not accommodate their current contributions.")]
+    ExceedsNewTierMaxContribution,
+    #[msg("Invalid user USDT account.")]
+    InvalidUserUsdtAccount,
+    #[msg("Tier name exceeds maximum allowed length.")]
+    TierNameTooLong,
+    #[msg("Presale is already paused.")]
+    PresaleAlreadyPaused,
+    #[msg("Presale is not paused.")]
+    PresaleNotPaused,
+    #[msg("Presale is paused.")]
+    PresalePaused,
+    #[msg("Contribution too small.")]
+    ContributionTooSmall,
+    #[msg("Invalid tier name format.")]
+    InvalidTierNameFormat,
+    #[msg("Hard cap must be greater than or equal to total contributions.")]
+    HardCapLessThanTotal,
+    #[msg("Hard cap must be less than tier maximum")]
+    HardCapLessThanTierMax,
+    #[msg("Invalid maximum contribution")]
+    InvalidMaxContribution,
+    #[msg("Presale is already closed")]
+    PresaleAlreadyClosed,
+    #[msg("Whitelist entry account does not match the expected PDA")]
+    InvalidWhitelistEntry,
+    #[msg("Whitelist validity window is invalid")]
+    InvalidValidityWindow,
+    #[msg("Whitelist entry is not yet valid")]
+    WhitelistEntryNotYetValid,
+    #[msg("Whitelist entry has expired")]
+    WhitelistEntryExpired,
+    #[msg("User is blacklisted")]
+    UserBlacklisted,
+    #[msg("Invalid gating token account")]
+    InvalidGatingTokenAccount,
+    #[msg("Tier has no remaining member slots")]
+    TierCapacityReached,
+    #[msg("Tier member cap is below its current member count")]
+    InvalidMaxMembers,
+    #[msg("Audit trail account does not match the expected PDA")]
+    InvalidAuditTrail,
+    #[msg("Audit trail is full")]
+    AuditTrailFull,
+    #[msg("Merkle tree is not the configured pass tree")]
+    InvalidPassMerkleTree,
+    #[msg("KYC attestation is required")]
+    KycRequired,
+    #[msg("Invalid KYC attestation")]
+    InvalidKycAttestation,
+    #[msg("KYC attestation has expired")]
+    KycAttestationExpired,
+    #[msg("SOL contributions are disabled")]
+    SolContributionsDisabled,
+    #[msg("Payment mint is already accepted")]
+    PaymentMintAlreadyAccepted,
+    #[msg("Exceeds maximum number of payment mints")]
+    ExceedsMaxPaymentMints,
+    #[msg("Payment mints must use 6 decimals")]
+    InvalidPaymentMintDecimals,
+    #[msg("Invalid price feed")]
+    InvalidPriceFeed,
+    #[msg("Price is stale")]
+    StalePrice,
+    #[msg("Price confidence interval is too wide")]
+    PriceConfidenceTooLow,
+    #[msg("Invalid tier token price")]
+    InvalidTokenPrice,
+    #[msg("Contribution exceeds the tier's total cap")]
+    ExceedsTierCap,
+    #[msg("Contribution cooldown is still active")]
+    CooldownActive,
+    #[msg("Invalid contribution cooldown")]
+    InvalidCooldown,
+    #[msg("Missing or invalid permit signature")]
+    InvalidPermitSignature,
+    #[msg("Permit has expired")]
+    PermitExpired,
+    #[msg("Invalid permit nonce")]
+    InvalidPermitNonce,
+    #[msg("Presale is not an approved delegate of the token account")]
+    InvalidDelegate,
+    #[msg("Presale already has contributions")]
+    PresaleHasContributions,
+    #[msg("Missing or invalid receipt token account")]
+    InvalidReceiptAccount,
+    #[msg("Receipt NFTs are not enabled")]
+    ReceiptNftsDisabled,
+    #[msg("Receipt NFT URI is too long")]
+    ReceiptNftUriTooLong,
+    #[msg("User has not contributed")]
+    NotAContributor,
+    #[msg("Invalid commit-reveal window")]
+    InvalidCommitRevealWindow,
+    #[msg("Direct contributions are disabled during commit-reveal")]
+    CommitRevealActive,
+    #[msg("Commit phase is not active")]
+    CommitPhaseNotActive,
+    #[msg("Reveal phase is not active")]
+    RevealPhaseNotActive,
+    #[msg("Reveal phase has not ended")]
+    RevealPhaseNotOver,
+    #[msg("Revealed amount does not match the commitment")]
+    CommitmentMismatch,
+    #[msg("Revealed amount exceeds the escrowed amount")]
+    RevealExceedsEscrow,
+    #[msg("Hard cap has been reached")]
+    HardCapReached,
+    #[msg("Contribution is not a multiple of the contribution increment")]
+    InvalidContributionIncrement,
+    #[msg("Contribution is below the dust threshold")]
+    ContributionBelowDustThreshold,
+    #[msg("Soft cap must not exceed the hard cap")]
+    InvalidSoftCap,
+    #[msg("Soft cap was not reached")]
+    SoftCapNotReached,
+    #[msg("Invalid sale window")]
+    InvalidSaleWindow,
+    #[msg("Sale has already started")]
+    SaleAlreadyStarted,
+    #[msg("Outside of the sale window")]
+    OutsideSaleWindow,
+    #[msg("Sale has not ended")]
+    SaleNotEnded,
+    #[msg("Invalid sale phase schedule")]
+    InvalidSalePhases,
+    #[msg("Too many sale phases")]
+    ExceedsMaxSalePhases,
+    #[msg("No sale phase is active")]
+    NoActivePhase,
+    #[msg("Direct contributions are disabled in lottery mode")]
+    LotteryModeActive,
+    #[msg("Lottery mode is not enabled")]
+    LotteryNotEnabled,
+    #[msg("Lottery has already been drawn")]
+    LotteryAlreadyDrawn,
+    #[msg("Lottery has not been drawn")]
+    LotteryNotDrawn,
+    #[msg("Revealed seed does not match the lottery commitment")]
+    InvalidLotterySeed,
+    #[msg("Lottery tickets are still unsettled")]
+    LotteryNotSettled,
+    #[msg("Allocation has already been finalized")]
+    AllocationAlreadyFinalized,
+    #[msg("Allocation has not been finalized")]
+    AllocationNotFinalized,
+    #[msg("No excess contribution to claim")]
+    NoExcessToClaim,
+    #[msg("Excess contributions are still unclaimed")]
+    ExcessNotClaimed,
+    #[msg("Invalid pricing curve")]
+    InvalidPricingCurve,
+    #[msg("Invalid early-bird bonus configuration")]
+    InvalidEarlyBirdBonus,
+    #[msg("Refund window has closed")]
+    RefundWindowClosed,
+    #[msg("Refund window is still open")]
+    RefundWindowOpen,
+    #[msg("Invalid refund period")]
+    InvalidRefundPeriod,
+    #[msg("Exceeds maximum bulk refund limit")]
+    ExceedsBulkRefundLimit,
+    #[msg("Bulk refunds are unavailable while receipts are enabled")]
+    ReceiptsRequireUserRefund,
+    #[msg("Refund destination is not owned by the user or a co-signing authority")]
+    InvalidRefundDestination,
+    #[msg("Soft cap was reached")]
+    SoftCapReached,
+    #[msg("Invalid refund fee")]
+    InvalidRefundFee,
+    #[msg("Presale is in emergency mode")]
+    EmergencyMode,
+    #[msg("Signer is not the presale guardian")]
+    NotGuardian,
+    #[msg("Invalid refund ticket")]
+    InvalidRefundTicket,
+    #[msg("Treasury account does not match the configured treasury")]
+    InvalidTreasury,
+    #[msg("Signer is not the pending owner")]
+    NotPendingOwner,
+    #[msg("Invalid council configuration")]
+    InvalidCouncil,
+    #[msg("Signer is not a council member")]
+    NotCouncilMember,
+    #[msg("Council approval required")]
+    CouncilApprovalRequired,
+    #[msg("Proposal does not match this action")]
+    InvalidProposal,
+    #[msg("Proposal already executed")]
+    ProposalAlreadyExecuted,
+    #[msg("Proposal already approved by this member")]
+    ProposalAlreadyApproved,
+    #[msg("Not enough council approvals")]
+    InsufficientApprovals,
+    #[msg("Change must be queued while the timelock is active")]
+    ChangeNotQueued,
+    #[msg("Queued change does not match this action")]
+    InvalidQueuedChange,
+    #[msg("Timelock has not elapsed")]
+    TimelockNotElapsed,
+    #[msg("Invalid timelock delay")]
+    InvalidTimelockDelay,
+    #[msg("Signer is not authorized for this action")]
+    Unauthorized,
+    #[msg("Tier is still in use")]
+    TierInUse,
+    #[msg("Every whitelist entry of the tier must be passed")]
+    TierMembersMissing,
+    #[msg("Whitelist is frozen")]
+    WhitelistFrozen,
+    #[msg("Emergency withdraw already initiated")]
+    EmergencyWithdrawInitiated,
+    #[msg("Emergency withdraw not initiated")]
+    EmergencyWithdrawNotInitiated,
+    #[msg("Emergency withdraw delay has not elapsed")]
+    EmergencyWithdrawDelayActive,
+    #[msg("Destination does not match the emergency withdraw destination")]
+    InvalidEmergencyWithdrawDestination,
+    #[msg("Invalid platform fee")]
+    InvalidPlatformFee,
+    #[msg("Invalid platform fee account")]
+    InvalidPlatformFeeAccount,
+    #[msg("Invalid payout recipients")]
+    InvalidPayoutRecipients,
+    #[msg("Invalid payout account")]
+    InvalidPayoutAccount,
+    #[msg("Amount exceeds the vault balance")]
+    InsufficientVaultBalance,
+    #[msg("Accepted payment mints cannot be rescued")]
+    CannotRescuePaymentMint,
+    #[msg("Vault still holds funds")]
+    VaultNotEmpty,
+    #[msg("Account is not a presale vault")]
+    InvalidVaultAccount,
+    #[msg("Invalid global config")]
+    InvalidConfig,
+    #[msg("Payment mint is not allowed by the global config")]
+    PaymentMintNotAllowed,
+    #[msg("Hard cap exceeds the global config limit")]
+    HardCapAboveConfigLimit,
+    #[msg("Presale is already on the current version")]
+    AlreadyMigrated,
+    #[msg("Refunds or withdrawals have already been made")]
+    SettlementStarted,
+    #[msg("Presale has been cancelled")]
+    PresaleCancelled,
+    #[msg("Presale name, symbol or description is too long")]
+    PresaleMetadataTooLong,
+    #[msg("Invalid contribution account")]
+    InvalidContributionAccount,
+    #[msg("Invalid presale account size")]
+    InvalidPresaleSpace,
+    #[msg("Sale id is not the creator's next sale id")]
+    InvalidSaleId,
+    #[msg("Invalid sale counter account")]
+    InvalidSaleCounter,
+    #[msg("Presale is not in the registry")]
+    PresaleNotRegistered,
+    #[msg("Invalid snapshot account")]
+    InvalidSnapshotAccount,
+    #[msg("Too soon since the last snapshot")]
+    SnapshotTooSoon,
+    #[msg("User account is still needed for refunds")]
+    UserAccountInUse,
+    #[msg("Allocation is in progress")]
+    AllocationInProgress,
+    #[msg("Oversubscription is not enabled")]
+    OversubscriptionNotEnabled,
+    #[msg("Contribution account is out of allocation order")]
+    InvalidAllocationCursor,
+    #[msg("Presale has user entries that cannot be migrated")]
+    ContributorsNotMigratable,
+    #[msg("Presale account layout is not supported by migrate")]
+    UnsupportedLayout,
+    #[msg("The claim deadline has not passed")]
+    ClaimDeadlineNotReached,
+    #[msg("The lottery draw deadline has passed")]
+    LotteryDrawExpired,
+    #[msg("The lottery draw deadline has not passed")]
+    LotteryDrawPending,
+    #[msg("Slot hashes sysvar is invalid")]
+    InvalidSlotHashes,
+}
+
+pub fn validate_tier_name(name: &str) -> Result<()> {
+    require!(
+        name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        PresaleError::InvalidTierNameFormat
+    );
+    Ok(())
+}
+
+pub fn validate_contribution_amount(amount: u64, increment: u64, dust_threshold: u64) -> Result<()> {
+    require!(amount >= dust_threshold, PresaleError::ContributionBelowDustThreshold);
+    if increment > 0 {
+        require!(amount.is_multiple_of(increment), PresaleError::InvalidContributionIncrement);
+    }
+    Ok(())
+}
+
+pub fn validate_sale_window(start_time: i64, end_time: i64) -> Result<()> {
+    require!(start_time < end_time, PresaleError::InvalidSaleWindow);
+    Ok(())
+}
+
+pub fn validate_council(members: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(members.len() <= MAX_COUNCIL_MEMBERS, PresaleError::InvalidCouncil);
+    require!(
+        (members.is_empty() && threshold == 0)
+            || (threshold > 0 && threshold as usize <= members.len()),
+        PresaleError::InvalidCouncil
+    );
+    for (i, member) in members.iter().enumerate() {
+        require!(!members[..i].contains(member), PresaleError::InvalidCouncil);
+    }
+    Ok(())
+}
+
+pub fn validate_payout_recipients(recipients: &[PayoutRecipient]) -> Result<()> {
+    require!(recipients.len() <= MAX_PAYOUT_RECIPIENTS, PresaleError::InvalidPayoutRecipients);
+    let mut total_bps: u32 = 0;
+    for (i, recipient) in recipients.iter().enumerate() {
+        require!(recipient.share_bps > 0, PresaleError::InvalidPayoutRecipients);
+        require!(
+            !recipients[..i].iter().any(|r| r.wallet == recipient.wallet),
+            PresaleError::InvalidPayoutRecipients
+        );
+        total_bps += recipient.share_bps as u32;
+    }
+    require!(
+        recipients.is_empty() || total_bps == 10_000,
+        PresaleError::InvalidPayoutRecipients
+    );
+    Ok(())
+}
+
+pub fn validate_config(platform_fee_bps: u16, allowed_payment_mints: &[Pubkey]) -> Result<()> {
+    require!(platform_fee_bps <= 10_000, PresaleError::InvalidPlatformFee);
+    require!(
+        allowed_payment_mints.len() <= MAX_ALLOWED_PAYMENT_MINTS,
+        PresaleError::InvalidConfig
+    );
+    for (i, mint) in allowed_payment_mints.iter().enumerate() {
+        require!(!allowed_payment_mints[..i].contains(mint), PresaleError::InvalidConfig);
+    }
+    Ok(())
+}
+
+pub fn validate_presale_metadata(name: &str, symbol: &str, description: &str) -> Result<()> {
+    require!(
+        name.len() <= MAX_PRESALE_NAME_LENGTH
+            && symbol.len() <= MAX_PRESALE_SYMBOL_LENGTH
+            && description.len() <= MAX_PRESALE_DESCRIPTION_LENGTH,
+        PresaleError::PresaleMetadataTooLong
+    );
+    Ok(())
+}
+
+pub fn validate_validity_window(valid_from: Option<i64>, valid_until: Option<i64>) -> Result<()> {
+    if let (Some(from), Some(until)) = (valid_from, valid_until) {
+        require!(from < until, PresaleError::InvalidValidityWindow);
+    }
+    Ok(())
+}
\ No newline at end of file
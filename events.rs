@@ -1,14 +1,21 @@
 use anchor_lang::prelude::*;
+use crate::state::{ParamChange, PayoutRecipient};
 
 #[event]
 pub struct Contribution {
+    pub presale: Pubkey,
+    pub sequence: u64,
     pub contributor: Pubkey,
+    pub mint: Pubkey,
     pub amount: u64,
+    pub tokens_purchased: u64,
     pub timestamp: u64,
 }
 
 #[event]
 pub struct UserLimitSet {
+    pub presale: Pubkey,
+    pub sequence: u64,
     pub user: Pubkey,
     pub max_contribution: u64,
     pub timestamp: u64,
@@ -16,47 +23,913 @@ pub struct UserLimitSet {
 
 #[event]
 pub struct PresaleClosed {
+    pub presale: Pubkey,
+    pub sequence: u64,
     pub timestamp: u64,
     pub refunds_allowed: bool,
+    pub soft_cap_reached: bool,
+    pub refund_deadline: i64,
 }
 
 #[event]
 pub struct FundsWithdrawn {
+    pub presale: Pubkey,
+    pub sequence: u64,
     pub amount: u64,
     pub timestamp: u64,
 }
 
 #[event]
 pub struct Refund {
+    pub presale: Pubkey,
+    pub sequence: u64,
     pub contributor: Pubkey,
+    pub mint: Pubkey,
     pub amount: u64,
     pub timestamp: u64,
 }
 
 #[event]
 pub struct UserRemoved {
+    pub presale: Pubkey,
+    pub sequence: u64,
     pub user: Pubkey,
     pub timestamp: u64,
 }
 
 #[event]
 pub struct MinContributionUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub old_min_contribution: u64,
     pub new_min_contribution: u64,
     pub timestamp: u64,
 }
 
 #[event]
 pub struct HardCapUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub old_hard_cap: u64,
     pub new_hard_cap: u64,
     pub timestamp: u64,
 }
 
 #[event]
 pub struct PresalePaused {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    /// Owner or guardian that paused the sale.
+    pub authority: Pubkey,
+    pub was_paused: bool,
     pub timestamp: u64,
 }
 
 #[event]
 pub struct PresaleUnpaused {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub was_paused: bool,
     pub timestamp: u64,
-} 
+}
+
+#[event]
+pub struct PublicSaleUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub public_sale: bool,
+    pub default_tier: Option<u8>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UserBlacklisted {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub user: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UserUnblacklisted {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub user: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RegistrationRequested {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub user: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RegistrationApproved {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub user: Pubkey,
+    pub tier: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RegistrationRejected {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub user: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct BalanceTiersUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub gating_mint: Option<Pubkey>,
+    pub tier_count: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TierFull {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub tier: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TierMaxMembersUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub tier: u8,
+    pub old_max_members: Option<u32>,
+    pub max_members: Option<u32>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TierPassCreated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub tier: u8,
+    pub mint: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PassRedeemed {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub user: Pubkey,
+    pub tier: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PassMerkleTreeUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub merkle_tree: Option<Pubkey>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct KycIssuerUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub kyc_issuer: Option<Pubkey>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct KycAttested {
+    pub issuer: Pubkey,
+    pub user: Pubkey,
+    pub expires_at: Option<i64>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct KycRevoked {
+    pub issuer: Pubkey,
+    pub user: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SolContribution {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub lamports: u64,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SolUsdtRateUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub sol_usdt_rate: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SolWithdrawn {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub lamports: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PaymentMintAdded {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub mint: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SolPriceFeedUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub sol_price_feed: Option<Pubkey>,
+    pub max_price_staleness: u64,
+    pub max_price_confidence_bps: u16,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TierCreated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub tier: u8,
+    pub name: String,
+    pub max_contribution: u64,
+    pub tokens_per_usdt: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TierTotalCapUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub tier: u8,
+    pub old_total_cap: Option<u64>,
+    pub total_cap: Option<u64>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ContributionCooldownUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub old_contribution_cooldown: i64,
+    pub contribution_cooldown: i64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct OtcContribution {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub payer: Pubkey,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PermitContribution {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub relayer: Pubkey,
+    pub amount: u64,
+    pub nonce: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ReceiptMintCreated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub mint: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ReceiptNftUriUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub uri: Option<String>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ReceiptNftMinted {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub mint: Pubkey,
+    pub tier: u8,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CommitRevealWindowUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub commit_end: i64,
+    pub reveal_end: i64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ContributionCommitted {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub mint: Pubkey,
+    pub escrowed: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ContributionRevealed {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub amount: u64,
+    pub returned: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CommitmentReclaimed {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UpgradeThresholdsUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub threshold_count: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TierUpgraded {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub user: Pubkey,
+    pub from_tier: u8,
+    pub to_tier: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PartialFillUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub partial_fill: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PartialFill {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub requested: u64,
+    pub accepted: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ContributionRulesUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contribution_increment: u64,
+    pub dust_threshold: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SaleWindowUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub old_start_time: i64,
+    pub old_end_time: i64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SalePhasesUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub phase_count: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct LotteryConfigured {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub ticket_amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct LotteryTicketPurchased {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct LotteryDrawn {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub entrants: u32,
+    pub winners: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct LotteryTicketSettled {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub won: bool,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct OversubscriptionUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub oversubscription: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct AllocationFinalized {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub total_requested: u64,
+    pub total_accepted: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ExcessRefunded {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PricingCurveUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub enabled: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct EarlyBirdBonusUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub bonus_bps: u16,
+    pub cutoff: i64,
+    pub fill_bps: u16,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct EarlyBirdBonus {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub bonus_tokens: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PartialRefundsUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub enabled: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RefundPeriodUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub old_refund_period: i64,
+    pub refund_period: i64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SolRefunded {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub lamports: u64,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SaleFailed {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub total_contributions: u64,
+    pub soft_cap: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RefundFeeUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub old_refund_fee_bps: u16,
+    pub refund_fee_bps: u16,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RefundFeeCharged {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub mint: Pubkey,
+    pub fee: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct GuardianUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub guardian: Option<Pubkey>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct EmergencyTriggered {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub guardian: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RefundsRestoreCapacityUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub refunds_restore_capacity: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RefundTicketClosed {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub user: Pubkey,
+    pub refunded_amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UserAccountClosed {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub user: Pubkey,
+    pub closed_by: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ComplianceRefund {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub contributor: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub remaining_contribution: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TreasuryUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub treasury: Option<Pubkey>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UnclaimedRefundsSwept {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub non_claimers: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct OwnershipProposed {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub owner: Pubkey,
+    pub pending_owner: Option<Pubkey>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct OwnershipTransferred {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub previous_owner: Pubkey,
+    pub new_owner: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CouncilUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub members: Vec<Pubkey>,
+    pub threshold: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct AdminProposalCreated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub proposal: Pubkey,
+    pub index: u64,
+    pub proposer: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct AdminProposalApproved {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub proposal: Pubkey,
+    pub member: Pubkey,
+    pub approvals: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ChangeQueued {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub index: u64,
+    pub change: ParamChange,
+    pub eta: i64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ChangeCancelled {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub index: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TimelockDelayUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub old_delay: i64,
+    pub delay: i64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct OperatorUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub operator: Option<Pubkey>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TreasurerUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub treasurer: Option<Pubkey>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TierUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub tier: u8,
+    pub old_max_contribution: u64,
+    pub max_contribution: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TierRemoved {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub tier: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TierRenamed {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub tier: u8,
+    pub old_name: String,
+    pub new_name: String,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct WhitelistFrozen {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct EmergencyWithdrawInitiated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub destination: Pubkey,
+    pub executable_at: i64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct EmergencyWithdrawExecuted {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub destination: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PlatformFeePaid {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PayoutRecipientsUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub recipients: Vec<PayoutRecipient>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PayoutDistributed {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub recipient: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TokensRescued {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub destination: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PresaleAccountClosed {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub vaults_closed: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ConfigUpdated {
+    pub authority: Pubkey,
+    pub platform_fee_bps: u16,
+    pub platform_fee_recipient: Pubkey,
+    pub max_hard_cap: u64,
+    pub default_refund_period: i64,
+    pub default_contribution_cooldown: i64,
+    pub allowed_payment_mints: Vec<Pubkey>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PresaleMigrated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub from_version: u8,
+    pub to_version: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PresaleReopened {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PresaleCancelled {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UserLimitUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub user: Pubkey,
+    pub max_contribution: Option<u64>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PresaleMetadataUpdated {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub name: String,
+    pub symbol: String,
+    pub description: String,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PresaleResized {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub old_space: u64,
+    pub new_space: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SnapshotTaken {
+    pub presale: Pubkey,
+    pub sequence: u64,
+    pub index: u64,
+    pub total_contributions: u64,
+    pub total_sol_contributions: u64,
+    pub contributor_count: u32,
+    pub timestamp: u64,
+}
@@ -1,518 +1,6112 @@
-use anchor_lang::prelude::*;
-use anchor_spl::token;
-use crate::{state::*, error::*, events::*, context::*};
-
-#[program]
-pub mod presale {
-    use super::*;
-
-    pub fn initialize(
-        ctx: Context<Initialize>,
-        tier_names: Vec<String>,
-        tier_max_contributions: Vec<u64>,
-        min_contribution: u64,
-        hard_cap: u64,
-    ) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-
-        require!(
-            !presale.is_initialized,
-            PresaleError::PresaleAlreadyInitialized
-        );
-
-        require!(
-            min_contribution > 0,
-            PresaleError::InvalidMinContribution
-        );
-        require!(hard_cap > 0, PresaleError::InvalidHardCap);
-
-        require!(
-            tier_names.len() <= MAX_TIERS,
-            PresaleError::ExceedsMaxTiers
-        );
-
-        require!(
-            tier_names.len() == tier_max_contributions.len(),
-            PresaleError::TierDataMismatch
-        );
-
-        let sum_tier_max: u64 = tier_max_contributions.iter().sum();
-        require!(
-            hard_cap >= sum_tier_max,
-            PresaleError::HardCapLessThanTierMax
-        );
-
-        presale.owner = ctx.accounts.owner.key();
-        presale.usdt_mint = ctx.accounts.usdt_mint.key();
-        presale.min_contribution = min_contribution;
-        presale.hard_cap = hard_cap;
-        presale.total_contributions = 0;
-        presale.is_active = true;
-        presale.is_closed = false;
-        presale.refunds_allowed = false;
-        presale.paused = false;
-        presale.is_initialized = true;
-
-        for (i, tier_name) in tier_names.iter().enumerate() {
-            let max_contribution = tier_max_contributions[i];
-
-            require!(
-                tier_name.len() <= MAX_TIER_NAME_LENGTH,
-                PresaleError::TierNameTooLong
-            );
-
-            let normalized_tier = tier_name.trim().to_lowercase();
-
-            require!(
-                !presale.tiers.contains_key(&normalized_tier),
-                PresaleError::TierAlreadyExists
-            );
-
-            require!(
-                max_contribution > 0,
-                PresaleError::InvalidMaxContribution
-            );
-
-            presale.tiers.insert(normalized_tier.clone(), max_contribution);
-        }
-
-        Ok(())
-    }
-
-    pub fn create_tier(
-        ctx: Context<CreateTier>,
-        tier_name: String,
-        max_contribution: u64,
-    ) -> Result<()> {
-        validate_tier_name(&tier_name)?;
-        let presale = &mut ctx.accounts.presale;
-
-        require!(
-            presale.tiers.len() < MAX_TIERS,
-            PresaleError::ExceedsMaxTiers
-        );
-
-        require!(
-            tier_name.len() <= MAX_TIER_NAME_LENGTH,
-            PresaleError::TierNameTooLong
-        );
-
-        require!(
-            max_contribution > 0,
-            PresaleError::InvalidMaxContribution
-        );
-
-        let normalized_tier = tier_name.trim().to_lowercase();
-
-        require!(
-            !presale.tiers.contains_key(&normalized_tier),
-            PresaleError::TierAlreadyExists
-        );
-
-        presale.tiers.insert(normalized_tier.clone(), max_contribution);
-
-        emit!(UserLimitSet {
-            user: ctx.accounts.owner.key(),
-            max_contribution,
-            timestamp: Clock::get()?.unix_timestamp as u64,
-        });
-
-        Ok(())
-    }
-
-    pub fn assign_tier(
-        ctx: Context<AssignTier>,
-        user: Pubkey,
-        tier_name: String,
-    ) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-
-        require!(
-            tier_name.len() <= MAX_TIER_NAME_LENGTH,
-            PresaleError::TierNameTooLong
-        );
-
-        let normalized_tier = tier_name.trim().to_lowercase();
-
-        require!(
-            presale.tiers.contains_key(&normalized_tier),
-            PresaleError::TierDoesNotExist
-        );
-
-        require!(
-            !presale.whitelist.contains_key(&user),
-            PresaleError::UserAlreadyWhitelisted
-        );
-
-        require!(
-            presale.whitelist.len() < MAX_USERS,
-            PresaleError::ExceedsMaxUsers
-        );
-
-        let max_contribution = presale.tiers.get(&normalized_tier).unwrap();
-        presale.whitelist.insert(user, normalized_tier);
-
-        emit!(UserLimitSet {
-            user,
-            max_contribution: *max_contribution,
-            timestamp: Clock::get()?.unix_timestamp as u64,
-        });
-
-        Ok(())
-    }
-
-    pub fn bulk_assign_tiers(
-        ctx: Context<BulkAssignTiers>,
-        users: Vec<Pubkey>,
-        tiers: Vec<String>,
-    ) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-
-        require!(
-            users.len() == tiers.len(),
-            PresaleError::MismatchUsersTiers
-        );
-
-        require!(
-            users.len() <= MAX_BULK_ASSIGN,
-            PresaleError::ExceedsBulkAssignLimit
-        );
-
-        require!(
-            presale.whitelist.len() + users.len() <= MAX_USERS,
-            PresaleError::ExceedsMaxUsers
-        );
-
-        for (tier_name, user) in tiers.iter().zip(users.iter()) {
-            require!(
-                tier_name.len() <= MAX_TIER_NAME_LENGTH,
-                PresaleError::TierNameTooLong
-            );
-
-            let normalized_tier = tier_name.trim().to_lowercase();
-
-            require!(
-                presale.tiers.contains_key(&normalized_tier),
-                PresaleError::TierDoesNotExist
-            );
-
-            require!(
-                !presale.whitelist.contains_key(user),
-                PresaleError::UserAlreadyWhitelisted
-            );
-        }
-
-        for (user, tier) in users.iter().zip(tiers.iter()) {
-            let normalized_tier = tier.trim().to_lowercase();
-            let max_contribution = *presale.tiers.get(&normalized_tier).unwrap();
-            
-            presale.whitelist.insert(*user, normalized_tier);
-
-            emit!(UserLimitSet {
-                user: *user,
-                max_contribution,
-                timestamp: Clock::get()?.unix_timestamp as u64,
-            });
-        }
-
-        Ok(())
-    }
-
-    pub fn remove_user_from_whitelist(
-        ctx: Context<RemoveUser>,
-        user: Pubkey,
-    ) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-
-        require!(
-            presale.whitelist.contains_key(&user),
-            PresaleError::UserNotWhitelisted
-        );
-
-        presale.whitelist.remove(&user);
-
-        emit!(UserRemoved {
-            user,
-            timestamp: Clock::get()?.unix_timestamp as u64,
-        });
-
-        Ok(())
-    }
-
-    pub fn update_user_tier(
-        ctx: Context<UpdateUserTier>,
-        user: Pubkey,
-        new_tier: String,
-    ) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-
-        require!(
-            new_tier.len() <= MAX_TIER_NAME_LENGTH,
-            PresaleError::TierNameTooLong
-        );
-
-        let normalized_tier = new_tier.trim().to_lowercase();
-
-        require!(
-            presale.tiers.contains_key(&normalized_tier),
-            PresaleError::TierDoesNotExist
-        );
-
-        require!(
-            presale.whitelist.contains_key(&user),
-            PresaleError::UserNotWhitelisted
-        );
-
-        let current_tier = presale.whitelist.get(&user).ok_or(PresaleError::UserNotWhitelisted)?;
-        
-        if current_tier == &normalized_tier {
-            return Ok(());
-        }
-
-        let user_contribution = presale.contributions.get(&user).copied().unwrap_or(0);
-        let new_tier_max = presale.tiers.get(&normalized_tier).ok_or(PresaleError::TierDoesNotExist)?;
-
-        require!(
-            user_contribution <= *new_tier_max,
-            PresaleError::ExceedsNewTierMaxContribution
-        );
-
-        if user_contribution > 0 {
-            if let Some(old_tier_total) = presale.tier_total_contributions.get_mut(current_tier) {
-                *old_tier_total = old_tier_total.checked_sub(user_contribution).ok_or(PresaleError::Overflow)?;
-            }
-            
-            let new_tier_total = presale.tier_total_contributions
-                .entry(normalized_tier.clone())
-                .or_insert(0);
-            *new_tier_total = new_tier_total.checked_add(user_contribution).ok_or(PresaleError::Overflow)?;
-        }
-
-        presale.whitelist.insert(user, normalized_tier.clone());
-
-        emit!(UserLimitSet {
-            user,
-            max_contribution: *new_tier_max,
-            timestamp: Clock::get()?.unix_timestamp as u64,
-        });
-
-        Ok(())
-    }
-
-    pub fn contribute(
-        ctx: Context<Contribute>,
-        amount: u64,
-    ) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-        let user = ctx.accounts.user.key();
-
-        require!(!presale.paused, PresaleError::PresalePaused);
-        require!(presale.is_active, PresaleError::PresaleNotActive);
-        require!(!presale.is_closed, PresaleError::PresaleClosed);
-
-        let user_tier = presale.whitelist.get(&user).ok_or(PresaleError::UserNotWhitelisted)?;
-        let tier_max = presale.tiers.get(user_tier).ok_or(PresaleError::TierDoesNotExist)?;
-
-        require!(
-            presale.total_contributions.checked_add(amount).ok_or(PresaleError::Overflow)? <= presale.hard_cap,
-            PresaleError::ExceedsHardCap
-        );
-
-        let previous_contribution = *presale.contributions.get(&user).unwrap_or(&0);
-        let user_contribution = previous_contribution.checked_add(amount).ok_or(PresaleError::Overflow)?;
-
-        require!(
-            user_contribution >= presale.min_contribution,
-            PresaleError::BelowMinContribution
-        );
-        require!(
-            user_contribution <= *tier_max,
-            PresaleError::AboveMaxContribution
-        );
-
-        require!(
-            ctx.accounts.user_usdt.owner == ctx.accounts.user.key(),
-            PresaleError::InvalidUserUsdtAccount
-        );
-
-        if previous_contribution == 0 {
-            presale.contributors.push(user);
-        }
-        presale.contributions.insert(user, user_contribution);
-        presale.total_contributions = presale
-            .total_contributions
-            .checked_add(amount)
-            .ok_or(PresaleError::Overflow)?;
-
-        let cpi_accounts = token::Transfer {
-            from: ctx.accounts.user_usdt.to_account_info(),
-            to: ctx.accounts.presale_usdt.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, amount)?;
-
-        emit!(Contribution {
-            contributor: user,
-            amount,
-            timestamp: Clock::get()?.unix_timestamp as u64,
-        });
-
-        Ok(())
-    }
-
-    pub fn close_presale(
-        ctx: Context<ClosePresale>,
-        refunds_allowed: bool,
-    ) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-
-        require!(!presale.paused, PresaleError::PresalePaused);
-        require!(presale.is_active, PresaleError::PresaleNotActive);
-        require!(!presale.is_closed, PresaleError::PresaleAlreadyClosed);
-
-        presale.is_closed = true;
-        presale.is_active = false;
-        presale.refunds_allowed = refunds_allowed;
-
-        emit!(PresaleClosed {
-            timestamp: Clock::get()?.unix_timestamp as u64,
-            refunds_allowed,
-        });
-
-        Ok(())
-    }
-
-    pub fn withdraw_funds(ctx: Context<WithdrawFunds>) -> Result<()> {
-        let presale = &ctx.accounts.presale;
-
-        require!(!presale.paused, PresaleError::PresalePaused);
-        require!(presale.is_closed, PresaleError::PresaleNotClosed);
-
-        let usdt_balance = ctx.accounts.presale_usdt.amount;
-        require!(usdt_balance > 0, PresaleError::NoFundsToWithdraw);
-
-        let seeds = &[b"presale", &[ctx.bumps.get("presale").unwrap()]];
-        let signer = &[&seeds[..]];
-
-        let cpi_accounts = token::Transfer {
-            from: ctx.accounts.presale_usdt.to_account_info(),
-            to: ctx.accounts.owner_usdt.to_account_info(),
-            authority: ctx.accounts.presale.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-        token::transfer(cpi_ctx, usdt_balance)?;
-
-        emit!(FundsWithdrawn {
-            amount: usdt_balance,
-            timestamp: Clock::get()?.unix_timestamp as u64,
-        });
-
-        Ok(())
-    }
-
-    pub fn refund(ctx: Context<Refund>) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-        let user = ctx.accounts.user.key();
-
-        require!(!presale.paused, PresaleError::PresalePaused);
-        require!(presale.is_closed, PresaleError::PresaleNotClosed);
-        require!(presale.refunds_allowed, PresaleError::RefundsNotAllowed);
-
-        let contribution = presale.contributions.get(&user).copied().unwrap_or(0);
-        require!(contribution > 0, PresaleError::NoContributionsToRefund);
-        require!(
-            !presale.refunded.get(&user).copied().unwrap_or(false),
-            PresaleError::AlreadyRefunded
-        );
-
-        presale.contributions.insert(user, 0);
-        presale.refunded.insert(user, true);
-
-        let seeds = &[b"presale", &[ctx.bumps.get("presale").unwrap()]];
-        let signer = &[&seeds[..]];
-
-        let cpi_accounts = token::Transfer {
-            from: ctx.accounts.presale_usdt.to_account_info(),
-            to: ctx.accounts.user_usdt.to_account_info(),
-            authority: ctx.accounts.presale.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-        token::transfer(cpi_ctx, contribution)?;
-
-        emit!(Refund {
-            contributor: user,
-            amount: contribution,
-            timestamp: Clock::get()?.unix_timestamp as u64,
-        });
-
-        Ok(())
-    }
-
-    pub fn set_min_contribution(
-        ctx: Context<UpdatePresale>,
-        new_min: u64,
-    ) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-        require!(new_min > 0, PresaleError::InvalidMinContribution);
-
-        presale.min_contribution = new_min;
-
-        emit!(MinContributionUpdated {
-            new_min_contribution: new_min,
-            timestamp: Clock::get()?.unix_timestamp as u64,
-        });
-
-        Ok(())
-    }
-
-    pub fn set_hard_cap(
-        ctx: Context<UpdatePresale>,
-        new_hard_cap: u64,
-    ) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-        require!(new_hard_cap > 0, PresaleError::InvalidHardCap);
-        require!(
-            new_hard_cap >= presale.total_contributions,
-            PresaleError::HardCapLessThanTotal
-        );
-
-        presale.hard_cap = new_hard_cap;
-
-        emit!(HardCapUpdated {
-            new_hard_cap,
-            timestamp: Clock::get()?.unix_timestamp as u64,
-        });
-
-        Ok(())
-    }
-
-    pub fn pause_presale(ctx: Context<PausePresale>) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-        require!(!presale.paused, PresaleError::PresaleAlreadyPaused);
-
-        presale.paused = true;
-
-        emit!(PresalePaused {
-            timestamp: Clock::get()?.unix_timestamp as u64,
-        });
-
-        Ok(())
-    }
-
-    pub fn unpause_presale(ctx: Context<UnpausePresale>) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-        require!(presale.paused, PresaleError::PresaleNotPaused);
-
-        presale.paused = false;
-
-        emit!(PresaleUnpaused {
-            timestamp: Clock::get()?.unix_timestamp as u64,
-        });
-
-        Ok(())
-    }
-} 
\ No newline at end of file
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{ed25519_program, hash::hashv, program_option::COption, sysvar::instructions as ix_sysvar};
+use anchor_lang::system_program;
+use anchor_spl::associated_token;
+use anchor_spl::token;
+use anchor_spl::token_interface;
+use mpl_bubblegum::hash::{hash_creators, hash_metadata};
+use mpl_bubblegum::instructions::BurnCpiBuilder;
+use mpl_bubblegum::types::MetadataArgs;
+use mpl_token_metadata::instructions::{CreateMasterEditionV3CpiBuilder, CreateMetadataAccountV3CpiBuilder};
+use mpl_token_metadata::types::DataV2;
+use anchor_lang::Discriminator;
+use crate::{state::*, error::*, events, events::*, context::*, legacy::{LegacyPresale, PRESALE_DISCRIMINATOR}, oracle, pricing::PricingCurve};
+use crate::context::Refund;
+use crate::{
+    EMERGENCY_WITHDRAW_DELAY, MAX_AUDIT_RECORDS, MAX_BULK_ASSIGN, MAX_BULK_REFUND, MAX_PAYMENT_MINTS,
+    MAX_RECEIPT_URI_LENGTH, MAX_SALE_PHASES, MAX_TIERS, MAX_TIER_NAME_LENGTH,
+    MIN_SNAPSHOT_INTERVAL, PRESALE_VERSION, ID,
+};
+
+#[program]
+pub mod presale {
+    use super::*;
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        tier_names: Vec<String>,
+        tier_max_contributions: Vec<u64>,
+        tier_tokens_per_usdt: Vec<u64>,
+        min_contribution: u64,
+        hard_cap: u64,
+        soft_cap: u64,
+        start_time: i64,
+        end_time: i64,
+        name: String,
+        symbol: String,
+        description: String,
+        sale_id: u64,
+    ) -> Result<()> {
+        let next_sale_id = take_sale_id(
+            &ctx.accounts.sale_counter.to_account_info(),
+            &ctx.accounts.owner.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.owner.key(),
+        )?;
+        require!(sale_id == next_sale_id, PresaleError::InvalidSaleId);
+
+        let usdt_vault = create_payment_vault(
+            &ctx.accounts.presale_usdt.to_account_info(),
+            &ctx.accounts.owner.to_account_info(),
+            &ctx.accounts.vault_authority.to_account_info(),
+            &ctx.accounts.usdt_mint.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.associated_token_program.to_account_info(),
+        )?;
+
+        let config = &ctx.accounts.config;
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_init()?;
+        let mut sale_registry = ctx.accounts.sale_registry.load_init()?;
+
+        require!(
+            !presale.is_initialized.get(),
+            PresaleError::PresaleAlreadyInitialized
+        );
+
+        require!(
+            min_contribution > 0,
+            PresaleError::InvalidMinContribution
+        );
+        require!(hard_cap > 0, PresaleError::InvalidHardCap);
+        require!(soft_cap <= hard_cap, PresaleError::InvalidSoftCap);
+        validate_sale_window(start_time, end_time)?;
+        validate_presale_metadata(&name, &symbol, &description)?;
+        require!(
+            config.max_hard_cap == 0 || hard_cap <= config.max_hard_cap,
+            PresaleError::HardCapAboveConfigLimit
+        );
+        require!(
+            ctx.accounts.usdt_mint.decimals == 6,
+            PresaleError::InvalidPaymentMintDecimals
+        );
+        require!(
+            config.allows_payment_mint(&ctx.accounts.usdt_mint.key()),
+            PresaleError::PaymentMintNotAllowed
+        );
+
+        require!(
+            tier_names.len() <= MAX_TIERS,
+            PresaleError::ExceedsMaxTiers
+        );
+
+        require!(
+            tier_names.len() == tier_max_contributions.len()
+                && tier_names.len() == tier_tokens_per_usdt.len(),
+            PresaleError::TierDataMismatch
+        );
+
+        let sum_tier_max: u64 = tier_max_contributions.iter().sum();
+        require!(
+            hard_cap >= sum_tier_max,
+            PresaleError::HardCapLessThanTierMax
+        );
+
+        presale.owner = ctx.accounts.owner.key();
+        presale.config = config.key();
+        presale.set_metadata(&name, &symbol, &description);
+        presale.creator = ctx.accounts.owner.key();
+        presale.sale_id = sale_id;
+        presale.bump = *ctx.bumps.get("presale").unwrap();
+        presale.sol_vault_bump = Pubkey::find_program_address(
+            &[b"sol_vault", presale_key.as_ref()],
+            ctx.program_id,
+        ).1;
+        presale.vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
+        presale.pending_owner = Pubkey::default();
+        presale.operator = Pubkey::default();
+        presale.treasurer = Pubkey::default();
+        presale.set_council(&[]);
+        presale.council_threshold = 0;
+        presale.proposal_count = 0;
+        presale.timelock_delay = 0;
+        presale.change_count = 0;
+        presale.add_payment_mint(ctx.accounts.usdt_mint.key(), usdt_vault)?;
+        presale.min_contribution = min_contribution;
+        presale.contribution_increment = 0;
+        presale.dust_threshold = 0;
+        presale.hard_cap = hard_cap;
+        presale.soft_cap = soft_cap;
+        presale.start_time = start_time;
+        presale.end_time = end_time;
+        presale.set_phases(&[]);
+        presale.total_contributions = 0;
+        presale.is_active = true.into();
+        presale.is_closed = false.into();
+        presale.is_cancelled = false.into();
+        presale.refunds_allowed = false.into();
+        presale.refund_period = config.default_refund_period;
+        presale.refund_deadline = 0;
+        presale.paused = false.into();
+        presale.partial_fill = false.into();
+        presale.oversubscription = false.into();
+        presale.partial_refunds_enabled = false.into();
+        presale.settlement_started = false.into();
+        presale.refund_fee_bps = 0;
+        presale.platform_fee_bps = config.platform_fee_bps;
+        presale.platform_fee_recipient = config.platform_fee_recipient;
+        presale.guardian = Pubkey::default();
+        presale.emergency = false.into();
+        presale.emergency_withdraw_at = 0;
+        presale.emergency_withdraw_destination = Pubkey::default();
+        presale.refunds_restore_capacity = true.into();
+        presale.treasury = Pubkey::default();
+        presale.set_payout_recipients(&[]);
+        presale.allocation_finalized = false.into();
+        presale.receipt_mint = Pubkey::default();
+        presale.set_receipt_nft_uri(None);
+        presale.pricing_curve.set(None);
+        presale.early_bird_bonus_bps = 0;
+        presale.early_bird_cutoff = 0;
+        presale.early_bird_fill_bps = 0;
+        presale.commit_end = 0;
+        presale.reveal_end = 0;
+        presale.lottery_ticket_amount = 0;
+        presale.sol_usdt_rate = 0;
+        presale.sol_price_feed = Pubkey::default();
+        presale.contribution_cooldown = config.default_contribution_cooldown;
+        presale.sequence = 0;
+        presale.version = PRESALE_VERSION;
+        presale.is_initialized = true.into();
+
+        sale_registry.presale = presale_key;
+        sale_registry.bump = *ctx.bumps.get("sale_registry").unwrap();
+        sale_registry.whitelist_frozen = false.into();
+        sale_registry.public_sale = false.into();
+        sale_registry.set_default_tier(None);
+        sale_registry.gating_mint = Pubkey::default();
+        sale_registry.set_upgrade_thresholds(&[]);
+        sale_registry.pass_merkle_tree = Pubkey::default();
+        sale_registry.kyc_issuer = Pubkey::default();
+        sale_registry.version = PRESALE_VERSION;
+
+        for (i, tier_name) in tier_names.iter().enumerate() {
+            let max_contribution = tier_max_contributions[i];
+            let tokens_per_usdt = tier_tokens_per_usdt[i];
+
+            let normalized_tier = tier_name.trim().to_lowercase();
+
+            require!(
+                normalized_tier.len() <= MAX_TIER_NAME_LENGTH,
+                PresaleError::TierNameTooLong
+            );
+
+            require!(
+                !sale_registry.has_tier(&normalized_tier),
+                PresaleError::TierAlreadyExists
+            );
+
+            require!(
+                max_contribution > 0,
+                PresaleError::InvalidMaxContribution
+            );
+
+            require!(
+                tokens_per_usdt > 0,
+                PresaleError::InvalidTokenPrice
+            );
+
+            sale_registry.insert_tier(TierInfo::new(&normalized_tier, max_contribution, tokens_per_usdt))?;
+        }
+
+        let registry = &mut ctx.accounts.registry;
+        let registry_entry = &mut ctx.accounts.registry_entry;
+        registry_entry.index = registry.presale_count;
+        registry_entry.presale = presale_key;
+        registry_entry.owner = presale.owner;
+        registry_entry.status = PresaleStatus::Active;
+        registry_entry.created_at = Clock::get()?.unix_timestamp;
+        registry_entry.bump = *ctx.bumps.get("registry_entry").unwrap();
+        registry.presale_count = registry.presale_count.checked_add(1).ok_or(PresaleError::Overflow)?;
+
+        emit!(PresaleMetadataUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            name,
+            symbol,
+            description,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Creates the program-wide config presales are initialized against.
+    /// Can only be called once; the caller becomes the platform authority.
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        platform_fee_bps: u16,
+        platform_fee_recipient: Pubkey,
+        max_hard_cap: u64,
+        default_refund_period: i64,
+        default_contribution_cooldown: i64,
+        allowed_payment_mints: Vec<Pubkey>,
+    ) -> Result<()> {
+        validate_config(platform_fee_bps, &allowed_payment_mints)?;
+        require!(
+            default_refund_period >= 0 && default_contribution_cooldown >= 0,
+            PresaleError::InvalidConfig
+        );
+
+        let config = &mut ctx.accounts.config;
+        config.authority = ctx.accounts.authority.key();
+        config.platform_fee_bps = platform_fee_bps;
+        config.platform_fee_recipient = platform_fee_recipient;
+        config.max_hard_cap = max_hard_cap;
+        config.default_refund_period = default_refund_period;
+        config.default_contribution_cooldown = default_contribution_cooldown;
+        config.allowed_payment_mints = allowed_payment_mints.clone();
+        config.bump = *ctx.bumps.get("config").unwrap();
+
+        ctx.accounts.registry.presale_count = 0;
+        ctx.accounts.registry.bump = *ctx.bumps.get("registry").unwrap();
+
+        emit!(ConfigUpdated {
+            authority: config.authority,
+            platform_fee_bps,
+            platform_fee_recipient,
+            max_hard_cap,
+            default_refund_period,
+            default_contribution_cooldown,
+            allowed_payment_mints,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Updates the global config. Existing presales keep the fee they were
+    /// created with; the new values apply to presales initialized afterwards.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        authority: Pubkey,
+        platform_fee_bps: u16,
+        platform_fee_recipient: Pubkey,
+        max_hard_cap: u64,
+        default_refund_period: i64,
+        default_contribution_cooldown: i64,
+        allowed_payment_mints: Vec<Pubkey>,
+    ) -> Result<()> {
+        validate_config(platform_fee_bps, &allowed_payment_mints)?;
+        require!(
+            default_refund_period >= 0 && default_contribution_cooldown >= 0,
+            PresaleError::InvalidConfig
+        );
+
+        let config = &mut ctx.accounts.config;
+        config.authority = authority;
+        config.platform_fee_bps = platform_fee_bps;
+        config.platform_fee_recipient = platform_fee_recipient;
+        config.max_hard_cap = max_hard_cap;
+        config.default_refund_period = default_refund_period;
+        config.default_contribution_cooldown = default_contribution_cooldown;
+        config.allowed_payment_mints = allowed_payment_mints.clone();
+
+        emit!(ConfigUpdated {
+            authority,
+            platform_fee_bps,
+            platform_fee_recipient,
+            max_hard_cap,
+            default_refund_period,
+            default_contribution_cooldown,
+            allowed_payment_mints,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Upgrades a presale created by an older program version, one version
+    /// step at a time. Accounts still in a `Presale` layout are decoded
+    /// from their raw bytes and rewritten as a `SaleConfig`, resized to
+    /// `SaleConfig::LEN`, plus a newly created `Registry`.
+    pub fn migrate(ctx: Context<MigratePresale>) -> Result<()> {
+        let presale_info = ctx.accounts.presale.to_account_info();
+        let sale_registry_info = ctx.accounts.sale_registry.to_account_info();
+        let presale_key = presale_info.key();
+        let old_space = presale_info.data_len();
+        let legacy = {
+            let data = presale_info.try_borrow_data()?;
+            require!(data.len() >= 8, ErrorCode::AccountDiscriminatorNotFound);
+            if data[..8] == SaleConfig::DISCRIMINATOR {
+                None
+            } else {
+                require!(data[..8] == PRESALE_DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+                Some(LegacyPresale::decode(&data[8..])?)
+            }
+        };
+
+        if let Some(legacy) = &legacy {
+            require!(legacy.version() < PRESALE_VERSION, PresaleError::AlreadyMigrated);
+            // Version 5 moves tiers and whitelist settings to the registry.
+            let bump = *ctx.bumps.get("sale_registry").unwrap();
+            create_pda_account(
+                &sale_registry_info,
+                &ctx.accounts.owner.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                &[b"sale_registry", presale_key.as_ref(), &[bump]],
+                Registry::LEN,
+            )?;
+            sale_registry_info.try_borrow_mut_data()?[..8].copy_from_slice(&Registry::DISCRIMINATOR);
+
+            let rent = Rent::get()?;
+            let shortfall = rent.minimum_balance(SaleConfig::LEN).saturating_sub(presale_info.lamports());
+            if shortfall > 0 {
+                let cpi_accounts = system_program::Transfer {
+                    from: ctx.accounts.owner.to_account_info(),
+                    to: presale_info.clone(),
+                };
+                let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+                system_program::transfer(cpi_ctx, shortfall)?;
+            }
+            presale_info.realloc(SaleConfig::LEN, false)?;
+            let mut data = presale_info.try_borrow_mut_data()?;
+            data[..8].copy_from_slice(&SaleConfig::DISCRIMINATOR);
+            data[8..].fill(0);
+        }
+
+        let presale_loader = AccountLoader::<SaleConfig>::try_from(&presale_info)?;
+        let sale_registry_loader = AccountLoader::<Registry>::try_from(&sale_registry_info)?;
+        let mut presale = presale_loader.load_mut()?;
+        let mut sale_registry = sale_registry_loader.load_mut()?;
+        if let Some(legacy) = &legacy {
+            legacy.migrate(&mut presale, &mut sale_registry)?;
+        }
+        let from_version = presale.version;
+        require!(from_version < PRESALE_VERSION, PresaleError::AlreadyMigrated);
+        require_keys_eq!(presale.owner, ctx.accounts.owner.key(), PresaleError::Unauthorized);
+        let (expected_key, _) = Pubkey::find_program_address(
+            &[b"presale", presale.creator.as_ref(), &presale.sale_id.to_le_bytes()],
+            ctx.program_id,
+        );
+        require_keys_eq!(presale_key, expected_key, ErrorCode::ConstraintSeeds);
+
+        if presale.version < 3 {
+            // Version 3 moved per-user ledgers into `ContributionAccount`
+            // PDAs; only a version 2 account without entries decodes.
+            presale.version = 3;
+        }
+
+        if presale.version < 4 {
+            // Version 4 made the account zero-copy.
+            presale.version = 4;
+        }
+
+        if presale.version < 5 {
+            // Version 5 is the `SaleConfig` and `Registry` split written above.
+            sale_registry.presale = presale_key;
+            sale_registry.bump = *ctx.bumps.get("sale_registry").unwrap();
+            presale.version = 5;
+        }
+
+        if presale.version < 6 {
+            // Version 6 records whether the soft cap was reached at close,
+            // in a byte taken from `_padding`. An already closed sale only
+            // has its current total to go by.
+            let soft_cap_reached = presale.is_closed.get() && presale.total_contributions >= presale.soft_cap;
+            presale.soft_cap_reached = soft_cap_reached.into();
+            presale.version = 6;
+        }
+        sale_registry.version = presale.version;
+
+        if old_space != SaleConfig::LEN {
+            emit!(PresaleResized {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                old_space: old_space as u64,
+                new_space: SaleConfig::LEN as u64,
+                timestamp: Clock::get()?.unix_timestamp as u64,
+            });
+        }
+
+        emit!(PresaleMigrated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            from_version,
+            to_version: presale.version,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::Migrate, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn create_tier(
+        ctx: Context<CreateTier>,
+        tier_name: String,
+        max_contribution: u64,
+        tokens_per_usdt: u64,
+    ) -> Result<()> {
+        validate_tier_name(&tier_name)?;
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        consume_timelock(
+            &presale,
+            presale_key,
+            ctx.accounts.queued_change.as_ref(),
+            ParamChange::CreateTier {
+                tier_name: tier_name.clone(),
+                max_contribution,
+                tokens_per_usdt,
+            },
+        )?;
+
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+        require!(
+            sale_registry.tier_count() < MAX_TIERS,
+            PresaleError::ExceedsMaxTiers
+        );
+
+        require!(
+            tier_name.len() <= MAX_TIER_NAME_LENGTH,
+            PresaleError::TierNameTooLong
+        );
+
+        require!(
+            max_contribution > 0,
+            PresaleError::InvalidMaxContribution
+        );
+
+        require!(
+            tokens_per_usdt > 0,
+            PresaleError::InvalidTokenPrice
+        );
+
+        let normalized_tier = tier_name.trim().to_lowercase();
+
+        require!(
+            !sale_registry.has_tier(&normalized_tier),
+            PresaleError::TierAlreadyExists
+        );
+
+        let tier = sale_registry.insert_tier(TierInfo::new(&normalized_tier, max_contribution, tokens_per_usdt))?;
+        *presale.tier_totals_mut(tier)? = TierTotals::default();
+
+        let timestamp = Clock::get()?.unix_timestamp as u64;
+
+        emit!(UserLimitSet {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user: ctx.accounts.owner.key(),
+            max_contribution,
+            timestamp,
+        });
+
+        emit!(TierCreated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            tier,
+            name: normalized_tier,
+            max_contribution,
+            tokens_per_usdt,
+            timestamp,
+        });
+
+        presale.log_admin_action(AdminOp::CreateTier, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Deletes a tier nobody is assigned to or has contributed under. The
+    /// default tier and tiers referenced by balance or upgrade rules must
+    /// be repointed first.
+    pub fn remove_tier(
+        ctx: Context<TimelockedTierUpdate>,
+        tier_name: String,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+        consume_timelock(
+            &presale,
+            presale_key,
+            ctx.accounts.queued_change.as_ref(),
+            ParamChange::RemoveTier { tier_name: tier_name.clone() },
+        )?;
+
+        let tier = sale_registry.resolve_tier_name(&tier_name)?;
+        let totals = presale.tier_totals(tier)?;
+        require!(
+            totals.member_count == 0
+                && totals.total_contributions == 0,
+            PresaleError::TierInUse
+        );
+        require!(
+            sale_registry.default_tier() != Some(tier)
+                && !sale_registry.balance_tiers().iter().any(|balance_tier| balance_tier.tier == tier)
+                && !sale_registry.upgrade_thresholds().iter().any(|threshold| threshold.tier == tier),
+            PresaleError::TierInUse
+        );
+
+        sale_registry.tiers[tier as usize] = TierInfo::default();
+
+        emit!(TierRemoved {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            tier,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::RemoveTier, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Renames a tier. Everything else, pass mints included, refers to the
+    /// tier by index, so only its slot changes.
+    pub fn rename_tier(
+        ctx: Context<TimelockedTierUpdate>,
+        old_tier_name: String,
+        new_tier_name: String,
+    ) -> Result<()> {
+        validate_tier_name(&new_tier_name)?;
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        consume_timelock(
+            &presale,
+            presale_key,
+            ctx.accounts.queued_change.as_ref(),
+            ParamChange::RenameTier {
+                old_tier_name: old_tier_name.clone(),
+                new_tier_name: new_tier_name.clone(),
+            },
+        )?;
+
+        require!(
+            new_tier_name.len() <= MAX_TIER_NAME_LENGTH,
+            PresaleError::TierNameTooLong
+        );
+
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+        let tier = sale_registry.resolve_tier_name(&old_tier_name)?;
+        let new_name = new_tier_name.trim().to_lowercase();
+
+        require!(
+            !sale_registry.has_tier(&new_name),
+            PresaleError::TierAlreadyExists
+        );
+        let tier_info = sale_registry.tier_at_mut(tier)?;
+        let old_name = tier_info.name().to_string();
+        tier_info.set_name(&new_name);
+
+        emit!(TierRenamed {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            tier,
+            old_name,
+            new_name,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::RenameTier, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn assign_tier(
+        ctx: Context<AssignTier>,
+        user: Pubkey,
+        tier_name: String,
+        valid_from: Option<i64>,
+        valid_until: Option<i64>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        require!(!sale_registry.whitelist_frozen.get(), PresaleError::WhitelistFrozen);
+
+        validate_validity_window(valid_from, valid_until)?;
+
+        let tier = sale_registry.resolve_tier_name(&tier_name)?;
+        let max_contribution = sale_registry.tier_at(tier)?.max_contribution;
+        let tier_full = presale.add_tier_member(&sale_registry, tier)?;
+
+        let whitelist_entry = &mut ctx.accounts.whitelist_entry;
+        whitelist_entry.presale = presale_key;
+        whitelist_entry.user = user;
+        whitelist_entry.tier = tier;
+        whitelist_entry.max_contribution = max_contribution;
+        whitelist_entry.valid_from = valid_from;
+        whitelist_entry.valid_until = valid_until;
+        whitelist_entry.bump = *ctx.bumps.get("whitelist_entry").unwrap();
+
+        let now = Clock::get()?.unix_timestamp;
+
+        record_whitelist_audit(
+            &ctx.accounts.audit_trail.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            presale_key,
+            user,
+            AuditRecord {
+                assigner: ctx.accounts.authority.key(),
+                tier,
+                timestamp: now,
+            },
+        )?;
+
+        let timestamp = now as u64;
+
+        emit!(UserLimitSet {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user,
+            max_contribution,
+            timestamp,
+        });
+
+        if tier_full {
+            emit!(TierFull {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                tier,
+                timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Whitelists each `(user, tier index)` pair in one pass. Either every
+    /// assignment succeeds or the instruction fails as a whole.
+    pub fn bulk_assign_tiers<'info>(
+        ctx: Context<'_, '_, '_, 'info, BulkAssignTiers<'info>>,
+        assignments: Vec<TierAssignment>,
+        valid_from: Option<i64>,
+        valid_until: Option<i64>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        require!(!sale_registry.whitelist_frozen.get(), PresaleError::WhitelistFrozen);
+
+        validate_validity_window(valid_from, valid_until)?;
+
+        require!(
+            assignments.len() <= MAX_BULK_ASSIGN,
+            PresaleError::ExceedsBulkAssignLimit
+        );
+
+        // A (whitelist entry, audit trail) PDA pair per user, in the same
+        // order as `assignments`.
+        require!(
+            ctx.remaining_accounts.len() == assignments.len() * 2,
+            PresaleError::MismatchUsersTiers
+        );
+
+        let authority_key = ctx.accounts.authority.key();
+        let authority_info = ctx.accounts.authority.to_account_info();
+        let system_program_info = ctx.accounts.system_program.to_account_info();
+        let now = Clock::get()?.unix_timestamp;
+        let timestamp = now as u64;
+
+        for (assignment, user_accounts) in assignments
+            .iter()
+            .zip(ctx.remaining_accounts.chunks(2))
+        {
+            let TierAssignment { user, tier } = *assignment;
+            let max_contribution = sale_registry.tier_at(tier)?.max_contribution;
+            let tier_full = presale.add_tier_member(&sale_registry, tier)?;
+
+            init_whitelist_entry(
+                &user_accounts[0],
+                &authority_info,
+                &system_program_info,
+                WhitelistEntry {
+                    presale: presale_key,
+                    user,
+                    tier,
+                    max_contribution,
+                    valid_from,
+                    valid_until,
+                    bump: 0,
+                },
+            )?;
+
+            record_whitelist_audit(
+                &user_accounts[1],
+                &authority_info,
+                &system_program_info,
+                presale_key,
+                user,
+                AuditRecord {
+                    assigner: authority_key,
+                    tier,
+                    timestamp: now,
+                },
+            )?;
+
+            emit!(UserLimitSet {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                user,
+                max_contribution,
+                timestamp,
+            });
+
+            if tier_full {
+                emit!(TierFull {
+                    presale: presale_key,
+                    sequence: presale.next_sequence(),
+                    tier,
+                    timestamp,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_user_from_whitelist(
+        ctx: Context<RemoveUser>,
+        user: Pubkey,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.whitelist_entry.user == user,
+            PresaleError::UserNotWhitelisted
+        );
+
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.remove_tier_member(ctx.accounts.whitelist_entry.tier);
+
+        emit!(UserRemoved {
+            presale: ctx.accounts.presale.key(),
+            sequence: presale.next_sequence(),
+            user,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    pub fn bulk_remove_users<'info>(
+        ctx: Context<'_, '_, '_, 'info, BulkRemoveUsers<'info>>,
+        users: Vec<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            users.len() <= MAX_BULK_ASSIGN,
+            PresaleError::ExceedsBulkAssignLimit
+        );
+
+        // One whitelist entry PDA per user, in the same order as `users`.
+        require!(
+            ctx.remaining_accounts.len() == users.len(),
+            PresaleError::MismatchUsersTiers
+        );
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let authority_info = ctx.accounts.authority.to_account_info();
+
+        for (user, entry_info) in users.iter().zip(ctx.remaining_accounts.iter()) {
+            let tier = close_whitelist_entry(entry_info, &authority_info, presale_key, *user)?;
+            presale.remove_tier_member(tier);
+
+            emit!(UserRemoved {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                user: *user,
+                timestamp: Clock::get()?.unix_timestamp as u64,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn add_to_blacklist(
+        ctx: Context<AddToBlacklist>,
+        user: Pubkey,
+    ) -> Result<()> {
+        let blacklist_entry = &mut ctx.accounts.blacklist_entry;
+        blacklist_entry.presale = ctx.accounts.presale.key();
+        blacklist_entry.user = user;
+        blacklist_entry.bump = *ctx.bumps.get("blacklist_entry").unwrap();
+
+        emit!(UserBlacklisted {
+            presale: ctx.accounts.presale.key(),
+            sequence: ctx.accounts.presale.load_mut()?.next_sequence(),
+            user,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    pub fn remove_from_blacklist(
+        ctx: Context<RemoveFromBlacklist>,
+        user: Pubkey,
+    ) -> Result<()> {
+        emit!(UserUnblacklisted {
+            presale: ctx.accounts.presale.key(),
+            sequence: ctx.accounts.presale.load_mut()?.next_sequence(),
+            user,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    pub fn request_whitelist(ctx: Context<RequestWhitelist>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let user = ctx.accounts.user.key();
+
+        require!(!presale.is_closed.get(), PresaleError::PresaleClosed);
+
+        let now = Clock::get()?.unix_timestamp;
+
+        let registration = &mut ctx.accounts.registration;
+        registration.presale = presale_key;
+        registration.user = user;
+        registration.requested_at = now;
+        registration.bump = *ctx.bumps.get("registration").unwrap();
+
+        emit!(RegistrationRequested {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user,
+            timestamp: now as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Enables contribution receipts. Must run before the first contribution
+    /// so every refundable contribution is backed by receipts.
+    pub fn create_receipt_mint(ctx: Context<CreateReceiptMint>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+
+        require!(presale.total_contributions == 0, PresaleError::PresaleHasContributions);
+
+        presale.receipt_mint = ctx.accounts.receipt_mint.key();
+
+        emit!(ReceiptMintCreated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            mint: ctx.accounts.receipt_mint.key(),
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::CreateReceiptMint, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Mints the contributor's one-off receipt NFT, recording their tier and
+    /// contribution in the metadata URI. Meant to be sent alongside the
+    /// user's first `contribute`; the mint PDA allows one NFT per contributor.
+    pub fn mint_receipt_nft(ctx: Context<MintReceiptNft>) -> Result<()> {
+        let presale = ctx.accounts.presale.load()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        let user = ctx.accounts.user.key();
+
+        let base_uri = presale
+            .receipt_nft_uri()
+            .ok_or(PresaleError::ReceiptNftsDisabled)?
+            .to_string();
+        let amount = ctx.accounts.contribution_account.amount;
+        require!(amount > 0, PresaleError::NotAContributor);
+
+        let (tier, _) = resolve_tier(
+            &presale,
+            &sale_registry,
+            user,
+            &ctx.accounts.whitelist_entry,
+            ctx.accounts.gating_token_account.as_ref(),
+            ctx.accounts.kyc_attestation.as_ref(),
+            None,
+        )?;
+        let tier_name = sale_registry.tier_at(tier)?.name().to_string();
+        let timestamp = Clock::get()?.unix_timestamp;
+
+        let creator = presale.creator;
+
+        let sale_id = presale.sale_id.to_le_bytes();
+        let bump = presale.bump;
+        // The presale signs the CPIs below, which cannot run while it is
+        // borrowed.
+        drop(presale);
+        let seeds = &[b"presale".as_ref(), creator.as_ref(), sale_id.as_ref(), &[bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = token::MintTo {
+            mint: ctx.accounts.receipt_nft.to_account_info(),
+            to: ctx.accounts.user_receipt_nft.to_account_info(),
+            authority: ctx.accounts.presale.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token::mint_to(cpi_ctx, 1)?;
+
+        let presale_info = ctx.accounts.presale.to_account_info();
+        let token_metadata_program = ctx.accounts.token_metadata_program.to_account_info();
+
+        CreateMetadataAccountV3CpiBuilder::new(&token_metadata_program)
+            .metadata(&ctx.accounts.metadata.to_account_info())
+            .mint(&ctx.accounts.receipt_nft.to_account_info())
+            .mint_authority(&presale_info)
+            .payer(&ctx.accounts.user.to_account_info())
+            .update_authority(&presale_info, true)
+            .system_program(&ctx.accounts.system_program.to_account_info())
+            .rent(Some(&ctx.accounts.rent.to_account_info()))
+            .data(DataV2 {
+                name: "Presale Receipt".to_string(),
+                symbol: "RCPT".to_string(),
+                uri: format!("{}?tier={}&amount={}&timestamp={}", base_uri, tier_name, amount, timestamp),
+                seller_fee_basis_points: 0,
+                creators: None,
+                collection: None,
+                uses: None,
+            })
+            .is_mutable(false)
+            .invoke_signed(signer)?;
+
+        CreateMasterEditionV3CpiBuilder::new(&token_metadata_program)
+            .edition(&ctx.accounts.master_edition.to_account_info())
+            .mint(&ctx.accounts.receipt_nft.to_account_info())
+            .update_authority(&presale_info)
+            .mint_authority(&presale_info)
+            .payer(&ctx.accounts.user.to_account_info())
+            .metadata(&ctx.accounts.metadata.to_account_info())
+            .token_program(&ctx.accounts.token_program.to_account_info())
+            .system_program(&ctx.accounts.system_program.to_account_info())
+            .rent(Some(&ctx.accounts.rent.to_account_info()))
+            .max_supply(0)
+            .invoke_signed(signer)?;
+
+        emit!(ReceiptNftMinted {
+            presale: ctx.accounts.presale.key(),
+            sequence: ctx.accounts.presale.load_mut()?.next_sequence(),
+            contributor: user,
+            mint: ctx.accounts.receipt_nft.key(),
+            tier,
+            amount,
+            timestamp: timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Creates the pass mint of `tier`. Pass mints are seeded by the tier's
+    /// index, so they keep working when the tier is renamed.
+    pub fn create_tier_pass(
+        ctx: Context<CreateTierPass>,
+        tier: u8,
+    ) -> Result<()> {
+        ctx.accounts.sale_registry.load()?.tier_at(tier)?;
+        let mut presale = ctx.accounts.presale.load_mut()?;
+
+        emit!(TierPassCreated {
+            presale: ctx.accounts.presale.key(),
+            sequence: presale.next_sequence(),
+            tier,
+            mint: ctx.accounts.pass_mint.key(),
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::CreateTierPass, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn mint_tier_pass(
+        ctx: Context<MintTierPass>,
+        _tier: u8,
+        amount: u64,
+    ) -> Result<()> {
+        let presale = ctx.accounts.presale.load()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        require!(!sale_registry.whitelist_frozen.get(), PresaleError::WhitelistFrozen);
+
+        let creator = presale.creator;
+
+        let sale_id = presale.sale_id.to_le_bytes();
+        let bump = presale.bump;
+        // The presale signs the mint, which it cannot do while borrowed.
+        drop(presale);
+        let seeds = &[b"presale".as_ref(), creator.as_ref(), sale_id.as_ref(), &[bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = token::MintTo {
+            mint: ctx.accounts.pass_mint.to_account_info(),
+            to: ctx.accounts.destination.to_account_info(),
+            authority: ctx.accounts.presale.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token::mint_to(cpi_ctx, amount)?;
+
+        ctx.accounts
+            .presale
+            .load_mut()?
+            .log_admin_action(AdminOp::MintTierPass, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn redeem_pass(
+        ctx: Context<RedeemPass>,
+        tier: u8,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        let user = ctx.accounts.user.key();
+
+        require!(!presale.is_closed.get(), PresaleError::PresaleClosed);
+
+        let max_contribution = sale_registry.tier_at(tier)?.max_contribution;
+        let tier_full = presale.add_tier_member(&sale_registry, tier)?;
+
+        let cpi_accounts = token::Burn {
+            mint: ctx.accounts.pass_mint.to_account_info(),
+            from: ctx.accounts.user_pass.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token::burn(cpi_ctx, 1)?;
+
+        let whitelist_entry = &mut ctx.accounts.whitelist_entry;
+        whitelist_entry.presale = presale_key;
+        whitelist_entry.user = user;
+        whitelist_entry.tier = tier;
+        whitelist_entry.max_contribution = max_contribution;
+        whitelist_entry.valid_from = None;
+        whitelist_entry.valid_until = None;
+        whitelist_entry.bump = *ctx.bumps.get("whitelist_entry").unwrap();
+
+        let now = Clock::get()?.unix_timestamp;
+
+        record_whitelist_audit(
+            &ctx.accounts.audit_trail.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            presale_key,
+            user,
+            AuditRecord {
+                assigner: user,
+                tier,
+                timestamp: now,
+            },
+        )?;
+
+        let timestamp = now as u64;
+
+        emit!(PassRedeemed {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user,
+            tier,
+            timestamp,
+        });
+
+        emit!(UserLimitSet {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user,
+            max_contribution,
+            timestamp,
+        });
+
+        if tier_full {
+            emit!(TierFull {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                tier,
+                timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Burns a compressed NFT pass from the configured merkle tree and
+    /// whitelists the holder in the tier named by the pass's symbol.
+    pub fn redeem_cnft_pass<'info>(
+        ctx: Context<'_, '_, '_, 'info, RedeemCnftPass<'info>>,
+        root: [u8; 32],
+        metadata: MetadataArgs,
+        nonce: u64,
+        index: u32,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        let user = ctx.accounts.user.key();
+
+        require!(!presale.is_closed.get(), PresaleError::PresaleClosed);
+
+        let tier = sale_registry.resolve_tier_name(&metadata.symbol)?;
+        let max_contribution = sale_registry.tier_at(tier)?.max_contribution;
+        let tier_full = presale.add_tier_member(&sale_registry, tier)?;
+
+        // Bubblegum verifies the proof against `root` and that `user` owns
+        // the leaf before burning it.
+        let data_hash = hash_metadata(&metadata)?;
+        let creator_hash = hash_creators(&metadata.creators);
+
+        let user_info = ctx.accounts.user.to_account_info();
+        let proof: Vec<(&AccountInfo<'info>, bool, bool)> = ctx
+            .remaining_accounts
+            .iter()
+            .map(|account| (account, false, false))
+            .collect();
+
+        BurnCpiBuilder::new(&ctx.accounts.bubblegum_program)
+            .tree_config(&ctx.accounts.tree_config)
+            .leaf_owner(&user_info, true)
+            .leaf_delegate(&user_info, false)
+            .merkle_tree(&ctx.accounts.merkle_tree)
+            .log_wrapper(&ctx.accounts.log_wrapper)
+            .compression_program(&ctx.accounts.compression_program)
+            .system_program(&ctx.accounts.system_program)
+            .add_remaining_accounts(&proof)
+            .root(root)
+            .data_hash(data_hash)
+            .creator_hash(creator_hash)
+            .nonce(nonce)
+            .index(index)
+            .invoke()?;
+
+        let whitelist_entry = &mut ctx.accounts.whitelist_entry;
+        whitelist_entry.presale = presale_key;
+        whitelist_entry.user = user;
+        whitelist_entry.tier = tier;
+        whitelist_entry.max_contribution = max_contribution;
+        whitelist_entry.valid_from = None;
+        whitelist_entry.valid_until = None;
+        whitelist_entry.bump = *ctx.bumps.get("whitelist_entry").unwrap();
+
+        let now = Clock::get()?.unix_timestamp;
+
+        record_whitelist_audit(
+            &ctx.accounts.audit_trail.to_account_info(),
+            &user_info,
+            &ctx.accounts.system_program.to_account_info(),
+            presale_key,
+            user,
+            AuditRecord {
+                assigner: user,
+                tier,
+                timestamp: now,
+            },
+        )?;
+
+        let timestamp = now as u64;
+
+        emit!(PassRedeemed {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user,
+            tier,
+            timestamp,
+        });
+
+        emit!(UserLimitSet {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user,
+            max_contribution,
+            timestamp,
+        });
+
+        if tier_full {
+            emit!(TierFull {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                tier,
+                timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn set_pass_merkle_tree(
+        ctx: Context<UpdateRegistry>,
+        merkle_tree: Option<Pubkey>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+        sale_registry.pass_merkle_tree = merkle_tree.unwrap_or_default();
+
+        emit!(PassMerkleTreeUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            merkle_tree,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetPassMerkleTree, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn approve_registration(
+        ctx: Context<ApproveRegistration>,
+        tier_name: String,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        require!(!sale_registry.whitelist_frozen.get(), PresaleError::WhitelistFrozen);
+        let user = ctx.accounts.user.key();
+
+        let tier = sale_registry.resolve_tier_name(&tier_name)?;
+        let max_contribution = sale_registry.tier_at(tier)?.max_contribution;
+        let tier_full = presale.add_tier_member(&sale_registry, tier)?;
+
+        let whitelist_entry = &mut ctx.accounts.whitelist_entry;
+        whitelist_entry.presale = presale_key;
+        whitelist_entry.user = user;
+        whitelist_entry.tier = tier;
+        whitelist_entry.max_contribution = max_contribution;
+        whitelist_entry.valid_from = None;
+        whitelist_entry.valid_until = None;
+        whitelist_entry.bump = *ctx.bumps.get("whitelist_entry").unwrap();
+
+        let now = Clock::get()?.unix_timestamp;
+
+        record_whitelist_audit(
+            &ctx.accounts.audit_trail.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            presale_key,
+            user,
+            AuditRecord {
+                assigner: ctx.accounts.authority.key(),
+                tier,
+                timestamp: now,
+            },
+        )?;
+
+        let timestamp = now as u64;
+
+        emit!(RegistrationApproved {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user,
+            tier,
+            timestamp,
+        });
+
+        emit!(UserLimitSet {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user,
+            max_contribution,
+            timestamp,
+        });
+
+        if tier_full {
+            emit!(TierFull {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                tier,
+                timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn reject_registration(ctx: Context<RejectRegistration>) -> Result<()> {
+        emit!(RegistrationRejected {
+            presale: ctx.accounts.presale.key(),
+            sequence: ctx.accounts.presale.load_mut()?.next_sequence(),
+            user: ctx.accounts.user.key(),
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    pub fn update_user_tier(
+        ctx: Context<UpdateUserTier>,
+        user: Pubkey,
+        new_tier: String,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        require!(!sale_registry.whitelist_frozen.get(), PresaleError::WhitelistFrozen);
+
+        let tier = sale_registry.resolve_tier_name(&new_tier)?;
+
+        let whitelist_entry = &mut ctx.accounts.whitelist_entry;
+
+        require!(
+            whitelist_entry.user == user,
+            PresaleError::UserNotWhitelisted
+        );
+
+        let current_tier = whitelist_entry.tier;
+
+        if current_tier == tier {
+            return Ok(());
+        }
+
+        let contribution_info = ctx.accounts.contribution_account.to_account_info();
+        let mut contribution = if contribution_info.data_is_empty() {
+            None
+        } else {
+            let data = contribution_info.try_borrow_data()?;
+            Some(ContributionAccount::try_deserialize(&mut &data[..])?)
+        };
+        let user_contribution = contribution.as_ref().map_or(0, |contribution| contribution.amount);
+        let new_tier_max = sale_registry.tier_at(tier)?.max_contribution;
+
+        require!(
+            user_contribution <= new_tier_max,
+            PresaleError::ExceedsNewTierMaxContribution
+        );
+
+        if let Some(contribution) = contribution.as_mut().filter(|contribution| contribution.amount > 0) {
+            if let Ok(old_totals) = presale.tier_totals_mut(current_tier) {
+                old_totals.total_contributions = old_totals
+                    .total_contributions
+                    .checked_sub(user_contribution)
+                    .ok_or(PresaleError::Overflow)?;
+            }
+
+            presale.add_tier_contribution(&sale_registry, tier, user_contribution)?;
+            contribution.tier = tier;
+            save_contribution_account(&contribution_info, contribution)?;
+        }
+
+        presale.remove_tier_member(current_tier);
+        let tier_full = presale.add_tier_member(&sale_registry, tier)?;
+
+        whitelist_entry.tier = tier;
+        whitelist_entry.max_contribution = new_tier_max;
+
+        let now = Clock::get()?.unix_timestamp;
+
+        record_whitelist_audit(
+            &ctx.accounts.audit_trail.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            presale_key,
+            user,
+            AuditRecord {
+                assigner: ctx.accounts.authority.key(),
+                tier,
+                timestamp: now,
+            },
+        )?;
+
+        let timestamp = now as u64;
+
+        emit!(UserLimitSet {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user,
+            max_contribution: new_tier_max,
+            timestamp,
+        });
+
+        if tier_full {
+            emit!(TierFull {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                tier,
+                timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn contribute(
+        ctx: Context<Contribute>,
+        amount: u64,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        let user = ctx.accounts.user.key();
+
+        require!(!presale.paused.get(), PresaleError::PresalePaused);
+        require!(!presale.emergency.get(), PresaleError::EmergencyMode);
+        require!(presale.is_active.get(), PresaleError::PresaleNotActive);
+        require!(!presale.is_closed.get(), PresaleError::PresaleClosed);
+        require!(presale.lottery_ticket_amount == 0, PresaleError::LotteryModeActive);
+        require!(
+            presale.in_sale_window(Clock::get()?.unix_timestamp),
+            PresaleError::OutsideSaleWindow
+        );
+        require!(
+            !presale.in_commit_reveal(Clock::get()?.unix_timestamp),
+            PresaleError::CommitRevealActive
+        );
+
+        let mut contribution = load_contribution_account(
+            &ctx.accounts.contribution_account.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &mut presale,
+            presale_key,
+            user,
+        )?;
+
+        let (tier, tier_max) = resolve_tier(
+            &presale,
+            &sale_registry,
+            user,
+            &ctx.accounts.whitelist_entry,
+            ctx.accounts.gating_token_account.as_ref(),
+            ctx.accounts.kyc_attestation.as_ref(),
+            contribution.user_limit,
+        )?;
+
+        require!(
+            ctx.accounts.user_usdt.owner == ctx.accounts.user.key(),
+            PresaleError::InvalidUserUsdtAccount
+        );
+
+        let requested = amount;
+        let remaining_capacity = presale.hard_cap.saturating_sub(presale.total_contributions);
+        let amount = if presale.partial_fill.get() && requested > remaining_capacity {
+            // Keep the reduced amount on the increment grid.
+            let accepted = match presale.contribution_increment {
+                0 => remaining_capacity,
+                increment => remaining_capacity - remaining_capacity % increment,
+            };
+            require!(accepted > 0, PresaleError::HardCapReached);
+            accepted
+        } else {
+            requested
+        };
+        validate_contribution_amount(amount, presale.contribution_increment, presale.dust_threshold)?;
+
+        let mint = ctx.accounts.payment_mint.key();
+        let vault_balance_before = ctx.accounts.presale_usdt.amount;
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.user_usdt.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            to: ctx.accounts.presale_usdt.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.payment_mint.decimals)?;
+
+        // Token-2022 mints may withhold a transfer fee, so credit what the
+        // vault actually received rather than the requested amount.
+        ctx.accounts.presale_usdt.reload()?;
+        let received = ctx
+            .accounts
+            .presale_usdt
+            .amount
+            .checked_sub(vault_balance_before)
+            .ok_or(PresaleError::Overflow)?;
+
+        let tokens_purchased = record_contribution(&mut presale, &sale_registry, presale_key, &mut contribution, mint, received, tier, tier_max)?;
+        upgrade_whitelist_tier(&mut presale, &sale_registry, &mut contribution, &ctx.accounts.whitelist_entry)?;
+        save_contribution_account(&ctx.accounts.contribution_account, &contribution)?;
+
+        if amount < requested {
+            emit!(PartialFill {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                contributor: user,
+                requested,
+                accepted: amount,
+                timestamp: Clock::get()?.unix_timestamp as u64,
+            });
+        }
+
+        emit!(Contribution {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            mint,
+            amount: received,
+            tokens_purchased,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        // The presale signs the receipt mint, which it cannot do while
+        // borrowed.
+        drop(presale);
+        mint_receipts(
+            &ctx.accounts.presale,
+            ctx.accounts.receipt_mint.as_ref(),
+            ctx.accounts.user_receipt.as_ref(),
+            ctx.accounts.receipt_token_program.as_ref(),
+            user,
+            received,
+        )?;
+
+        Ok(())
+    }
+
+    /// Gasless contribution: a relayer submits the transaction and pays its
+    /// fees, while the user's signed permit authorizes pulling `amount` from
+    /// their token account through a delegate approval to the presale PDA.
+    /// The transaction must carry an Ed25519 signature check of the permit
+    /// message (see `permit_message`) immediately before this instruction.
+    pub fn contribute_with_permit(
+        ctx: Context<ContributeWithPermit>,
+        amount: u64,
+        nonce: u64,
+        expiry: i64,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let user = ctx.accounts.user.key();
+        let mint = ctx.accounts.payment_mint.key();
+
+        require!(
+            Clock::get()?.unix_timestamp <= expiry,
+            PresaleError::PermitExpired
+        );
+        verify_permit_signature(
+            &ctx.accounts.instructions,
+            &user,
+            &permit_message(&presale_key, &mint, amount, nonce, expiry),
+        )?;
+        require!(
+            ctx.accounts.user_usdt.delegate == COption::Some(presale_key),
+            PresaleError::InvalidDelegate
+        );
+
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+
+        require!(!presale.paused.get(), PresaleError::PresalePaused);
+        require!(!presale.emergency.get(), PresaleError::EmergencyMode);
+        require!(presale.is_active.get(), PresaleError::PresaleNotActive);
+        require!(!presale.is_closed.get(), PresaleError::PresaleClosed);
+        require!(presale.lottery_ticket_amount == 0, PresaleError::LotteryModeActive);
+        require!(
+            presale.in_sale_window(Clock::get()?.unix_timestamp),
+            PresaleError::OutsideSaleWindow
+        );
+        require!(
+            !presale.in_commit_reveal(Clock::get()?.unix_timestamp),
+            PresaleError::CommitRevealActive
+        );
+
+        validate_contribution_amount(amount, presale.contribution_increment, presale.dust_threshold)?;
+
+        let mut contribution = load_contribution_account(
+            &ctx.accounts.contribution_account.to_account_info(),
+            &ctx.accounts.relayer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &mut presale,
+            presale_key,
+            user,
+        )?;
+
+        require!(nonce == contribution.permit_nonce, PresaleError::InvalidPermitNonce);
+        contribution.permit_nonce = nonce.checked_add(1).ok_or(PresaleError::Overflow)?;
+
+        let (tier, tier_max) = resolve_tier(
+            &presale,
+            &sale_registry,
+            user,
+            &ctx.accounts.whitelist_entry,
+            ctx.accounts.gating_token_account.as_ref(),
+            ctx.accounts.kyc_attestation.as_ref(),
+            contribution.user_limit,
+        )?;
+
+        let vault_balance_before = ctx.accounts.presale_usdt.amount;
+
+        let creator = presale.creator;
+
+        let sale_id = presale.sale_id.to_le_bytes();
+        let bump = presale.bump;
+        // The presale signs the transfer as the delegate, which it cannot
+        // do while borrowed.
+        drop(presale);
+        let seeds = &[b"presale".as_ref(), creator.as_ref(), sale_id.as_ref(), &[bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.user_usdt.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            to: ctx.accounts.presale_usdt.to_account_info(),
+            authority: ctx.accounts.presale.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.payment_mint.decimals)?;
+
+        ctx.accounts.presale_usdt.reload()?;
+        let received = ctx
+            .accounts
+            .presale_usdt
+            .amount
+            .checked_sub(vault_balance_before)
+            .ok_or(PresaleError::Overflow)?;
+
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let tokens_purchased = record_contribution(&mut presale, &sale_registry, presale_key, &mut contribution, mint, received, tier, tier_max)?;
+        upgrade_whitelist_tier(&mut presale, &sale_registry, &mut contribution, &ctx.accounts.whitelist_entry)?;
+        save_contribution_account(&ctx.accounts.contribution_account, &contribution)?;
+
+        let timestamp = Clock::get()?.unix_timestamp as u64;
+
+        emit!(PermitContribution {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            relayer: ctx.accounts.relayer.key(),
+            amount: received,
+            nonce,
+            timestamp,
+        });
+
+        emit!(Contribution {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            mint,
+            amount: received,
+            tokens_purchased,
+            timestamp,
+        });
+
+        // The presale signs the receipt mint, which it cannot do while
+        // borrowed.
+        drop(presale);
+        mint_receipts(
+            &ctx.accounts.presale,
+            ctx.accounts.receipt_mint.as_ref(),
+            ctx.accounts.user_receipt.as_ref(),
+            ctx.accounts.receipt_token_program.as_ref(),
+            user,
+            received,
+        )?;
+
+        Ok(())
+    }
+
+    /// Mirrors an off-chain (OTC) settlement: the owner's settlement wallet
+    /// pays, and the contribution is credited to `user`.
+    pub fn contribute_for(
+        ctx: Context<ContributeFor>,
+        user: Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+
+        require!(!presale.paused.get(), PresaleError::PresalePaused);
+        require!(!presale.emergency.get(), PresaleError::EmergencyMode);
+        require!(presale.is_active.get(), PresaleError::PresaleNotActive);
+        require!(!presale.is_closed.get(), PresaleError::PresaleClosed);
+        require!(presale.lottery_ticket_amount == 0, PresaleError::LotteryModeActive);
+        require!(
+            presale.in_sale_window(Clock::get()?.unix_timestamp),
+            PresaleError::OutsideSaleWindow
+        );
+        require!(
+            !presale.in_commit_reveal(Clock::get()?.unix_timestamp),
+            PresaleError::CommitRevealActive
+        );
+
+        validate_contribution_amount(amount, presale.contribution_increment, presale.dust_threshold)?;
+
+        let mut contribution = load_contribution_account(
+            &ctx.accounts.contribution_account.to_account_info(),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &mut presale,
+            presale_key,
+            user,
+        )?;
+
+        let (tier, tier_max) = resolve_tier(
+            &presale,
+            &sale_registry,
+            user,
+            &ctx.accounts.whitelist_entry,
+            None,
+            ctx.accounts.kyc_attestation.as_ref(),
+            contribution.user_limit,
+        )?;
+
+        let mint = ctx.accounts.payment_mint.key();
+        let vault_balance_before = ctx.accounts.presale_usdt.amount;
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.payer_usdt.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            to: ctx.accounts.presale_usdt.to_account_info(),
+            authority: ctx.accounts.payer.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.payment_mint.decimals)?;
+
+        ctx.accounts.presale_usdt.reload()?;
+        let received = ctx
+            .accounts
+            .presale_usdt
+            .amount
+            .checked_sub(vault_balance_before)
+            .ok_or(PresaleError::Overflow)?;
+
+        let tokens_purchased = record_contribution(&mut presale, &sale_registry, presale_key, &mut contribution, mint, received, tier, tier_max)?;
+        upgrade_whitelist_tier(&mut presale, &sale_registry, &mut contribution, &ctx.accounts.whitelist_entry)?;
+        save_contribution_account(&ctx.accounts.contribution_account, &contribution)?;
+
+        let timestamp = Clock::get()?.unix_timestamp as u64;
+
+        emit!(OtcContribution {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            payer: ctx.accounts.payer.key(),
+            amount: received,
+            timestamp,
+        });
+
+        emit!(Contribution {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            mint,
+            amount: received,
+            tokens_purchased,
+            timestamp,
+        });
+
+        // The presale signs the receipt mint, which it cannot do while
+        // borrowed.
+        drop(presale);
+        mint_receipts(
+            &ctx.accounts.presale,
+            ctx.accounts.receipt_mint.as_ref(),
+            ctx.accounts.user_receipt.as_ref(),
+            ctx.accounts.receipt_token_program.as_ref(),
+            user,
+            received,
+        )?;
+
+        Ok(())
+    }
+
+    /// Commit phase of a commit-reveal round: the user escrows up to
+    /// `max_amount` alongside a hash of the amount they intend to contribute,
+    /// so others cannot react to contribution sizes before the reveal.
+    pub fn commit_contribution(
+        ctx: Context<CommitContribution>,
+        commitment: [u8; 32],
+        max_amount: u64,
+    ) -> Result<()> {
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!presale.paused.get(), PresaleError::PresalePaused);
+        require!(!presale.emergency.get(), PresaleError::EmergencyMode);
+        require!(presale.is_active.get(), PresaleError::PresaleNotActive);
+        require!(!presale.is_closed.get(), PresaleError::PresaleClosed);
+        require!(presale.lottery_ticket_amount == 0, PresaleError::LotteryModeActive);
+        require!(presale.in_sale_window(now), PresaleError::OutsideSaleWindow);
+        require!(
+            presale.commit_end != 0 && now <= presale.commit_end,
+            PresaleError::CommitPhaseNotActive
+        );
+
+        let vault_balance_before = ctx.accounts.presale_usdt.amount;
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.user_usdt.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            to: ctx.accounts.presale_usdt.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token_interface::transfer_checked(cpi_ctx, max_amount, ctx.accounts.payment_mint.decimals)?;
+
+        ctx.accounts.presale_usdt.reload()?;
+        let escrowed = ctx
+            .accounts
+            .presale_usdt
+            .amount
+            .checked_sub(vault_balance_before)
+            .ok_or(PresaleError::Overflow)?;
+
+        let entry = &mut ctx.accounts.commitment;
+        entry.presale = ctx.accounts.presale.key();
+        entry.user = ctx.accounts.user.key();
+        entry.payment_mint = ctx.accounts.payment_mint.key();
+        entry.commitment = commitment;
+        entry.escrowed = escrowed;
+        entry.bump = *ctx.bumps.get("commitment").unwrap();
+
+        emit!(ContributionCommitted {
+            presale: ctx.accounts.presale.key(),
+            sequence: presale.next_sequence(),
+            contributor: entry.user,
+            mint: entry.payment_mint,
+            escrowed,
+            timestamp: now as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Reveal phase: checks `amount` and `salt` against the commitment,
+    /// records `amount` as a contribution and returns the excess escrow.
+    pub fn reveal_contribution(
+        ctx: Context<RevealContribution>,
+        amount: u64,
+        salt: [u8; 32],
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        let user = ctx.accounts.user.key();
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!presale.paused.get(), PresaleError::PresalePaused);
+        require!(!presale.emergency.get(), PresaleError::EmergencyMode);
+        require!(presale.is_active.get(), PresaleError::PresaleNotActive);
+        require!(!presale.is_closed.get(), PresaleError::PresaleClosed);
+        require!(
+            presale.commit_end != 0 && now > presale.commit_end && now <= presale.reveal_end,
+            PresaleError::RevealPhaseNotActive
+        );
+
+        validate_contribution_amount(amount, presale.contribution_increment, presale.dust_threshold)?;
+
+        let commitment = &ctx.accounts.commitment;
+        let expected = hashv(&[&amount.to_le_bytes(), &salt, user.as_ref()]).to_bytes();
+        require!(commitment.commitment == expected, PresaleError::CommitmentMismatch);
+        require!(amount <= commitment.escrowed, PresaleError::RevealExceedsEscrow);
+        let excess = commitment.escrowed - amount;
+
+        let mut contribution = load_contribution_account(
+            &ctx.accounts.contribution_account.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &mut presale,
+            presale_key,
+            user,
+        )?;
+
+        let (tier, tier_max) = resolve_tier(
+            &presale,
+            &sale_registry,
+            user,
+            &ctx.accounts.whitelist_entry,
+            ctx.accounts.gating_token_account.as_ref(),
+            ctx.accounts.kyc_attestation.as_ref(),
+            contribution.user_limit,
+        )?;
+
+        let mint = ctx.accounts.payment_mint.key();
+        let tokens_purchased = record_contribution(&mut presale, &sale_registry, presale_key, &mut contribution, mint, amount, tier, tier_max)?;
+        upgrade_whitelist_tier(&mut presale, &sale_registry, &mut contribution, &ctx.accounts.whitelist_entry)?;
+        save_contribution_account(&ctx.accounts.contribution_account, &contribution)?;
+
+        if excess > 0 {
+            let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+            let signer = &[&seeds[..]];
+
+            let cpi_accounts = token_interface::TransferChecked {
+                from: ctx.accounts.presale_usdt.to_account_info(),
+                mint: ctx.accounts.payment_mint.to_account_info(),
+                to: ctx.accounts.user_usdt.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            token_interface::transfer_checked(cpi_ctx, excess, ctx.accounts.payment_mint.decimals)?;
+        }
+
+        emit!(ContributionRevealed {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            amount,
+            returned: excess,
+            timestamp: now as u64,
+        });
+
+        emit!(Contribution {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            mint,
+            amount,
+            tokens_purchased,
+            timestamp: now as u64,
+        });
+
+        // The presale signs the receipt mint, which it cannot do while
+        // borrowed.
+        drop(presale);
+        mint_receipts(
+            &ctx.accounts.presale,
+            ctx.accounts.receipt_mint.as_ref(),
+            ctx.accounts.user_receipt.as_ref(),
+            ctx.accounts.receipt_token_program.as_ref(),
+            user,
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the full escrow of a commitment that was not revealed in time.
+    pub fn reclaim_commitment(ctx: Context<ReclaimCommitment>) -> Result<()> {
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(now > presale.reveal_end, PresaleError::RevealPhaseNotOver);
+
+        let escrowed = ctx.accounts.commitment.escrowed;
+
+        let presale_key = ctx.accounts.presale.key();
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.presale_usdt.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            to: ctx.accounts.user_usdt.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token_interface::transfer_checked(cpi_ctx, escrowed, ctx.accounts.payment_mint.decimals)?;
+
+        emit!(CommitmentReclaimed {
+            presale: ctx.accounts.presale.key(),
+            sequence: presale.next_sequence(),
+            contributor: ctx.accounts.user.key(),
+            amount: escrowed,
+            timestamp: now as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Lottery mode: buys the user's single fixed-price ticket, escrowing the
+    /// ticket amount until `draw_lottery` decides whether it is accepted.
+    pub fn buy_lottery_ticket(ctx: Context<BuyLotteryTicket>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        let user = ctx.accounts.user.key();
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!presale.paused.get(), PresaleError::PresalePaused);
+        require!(!presale.emergency.get(), PresaleError::EmergencyMode);
+        require!(presale.is_active.get(), PresaleError::PresaleNotActive);
+        require!(!presale.is_closed.get(), PresaleError::PresaleClosed);
+        require!(presale.in_sale_window(now), PresaleError::OutsideSaleWindow);
+        require!(presale.lottery_ticket_amount > 0, PresaleError::LotteryNotEnabled);
+        require!(!presale.lottery_drawn.get(), PresaleError::LotteryAlreadyDrawn);
+
+        let (tier, _) = resolve_tier(
+            &presale,
+            &sale_registry,
+            user,
+            &ctx.accounts.whitelist_entry,
+            ctx.accounts.gating_token_account.as_ref(),
+            ctx.accounts.kyc_attestation.as_ref(),
+            None,
+        )?;
+
+        let ticket_amount = presale.lottery_ticket_amount;
+        let vault_balance_before = ctx.accounts.presale_usdt.amount;
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.user_usdt.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            to: ctx.accounts.presale_usdt.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token_interface::transfer_checked(cpi_ctx, ticket_amount, ctx.accounts.payment_mint.decimals)?;
+
+        ctx.accounts.presale_usdt.reload()?;
+        let received = ctx
+            .accounts
+            .presale_usdt
+            .amount
+            .checked_sub(vault_balance_before)
+            .ok_or(PresaleError::Overflow)?;
+
+        let index = presale.lottery_ticket_count;
+        presale.lottery_ticket_count = index.checked_add(1).ok_or(PresaleError::Overflow)?;
+        presale.lottery_unsettled = presale.lottery_unsettled.checked_add(1).ok_or(PresaleError::Overflow)?;
+
+        let ticket = &mut ctx.accounts.ticket;
+        ticket.presale = presale_key;
+        ticket.user = user;
+        ticket.payment_mint = ctx.accounts.payment_mint.key();
+        ticket.tier = tier;
+        ticket.amount = received;
+        ticket.bump = *ctx.bumps.get("ticket").unwrap();
+        ticket.index = index;
+
+        emit!(LotteryTicketPurchased {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            mint: ticket.payment_mint,
+            amount: received,
+            timestamp: now as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Reveals the committed seed between the end of the sale window and
+    /// the draw deadline. Mixed with the latest slot hash, which nobody
+    /// knew when the seed was committed, it ranks the tickets; as many
+    /// tickets as fit under the hard cap win.
+    pub fn draw_lottery(ctx: Context<DrawLottery>, seed: [u8; 32]) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        presale.check_lottery_drawable(now)?;
+        require!(
+            hashv(&[&seed]).to_bytes() == presale.lottery_seed_hash,
+            PresaleError::InvalidLotterySeed
+        );
+        let entropy = lottery_entropy(&seed, &ctx.accounts.slot_hashes.try_borrow_data()?)?;
+
+        let entrants = presale.lottery_ticket_count;
+        let capacity = presale.hard_cap.saturating_sub(presale.total_contributions)
+            / presale.lottery_ticket_amount;
+        let winner_count = (entrants as u64).min(capacity) as u32;
+
+        // Tickets are ranked from the seed when they are settled; the first
+        // `winner_count` ranks win.
+        presale.lottery_seed = entropy;
+        presale.lottery_winner_count = winner_count;
+        presale.lottery_drawn = true.into();
+
+        emit!(LotteryDrawn {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            entrants,
+            winners: winner_count,
+            timestamp: now as u64,
+        });
+
+        presale.log_admin_action(AdminOp::DrawLottery, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Settles a ticket after the draw: winners are recorded as contributors,
+    /// losers get their ticket amount back. Permissionless so stragglers
+    /// cannot block withdrawals.
+    pub fn settle_lottery_ticket(ctx: Context<SettleLotteryTicket>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        let user = ctx.accounts.user.key();
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(presale.lottery_drawn.get(), PresaleError::LotteryNotDrawn);
+
+        let ticket = &ctx.accounts.ticket;
+        let amount = ticket.amount;
+        let won = presale.lottery_rank(ticket.index) < presale.lottery_winner_count;
+
+        presale.lottery_unsettled = presale.lottery_unsettled.saturating_sub(1);
+
+        if won {
+            let mint = ctx.accounts.payment_mint.key();
+            let mut contribution = load_contribution_account(
+                &ctx.accounts.contribution_account.to_account_info(),
+                &ctx.accounts.payer.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                &mut presale,
+                presale_key,
+                user,
+            )?;
+            let tokens_purchased =
+                record_contribution(&mut presale, &sale_registry, presale_key, &mut contribution, mint, amount, ticket.tier, u64::MAX)?;
+            save_contribution_account(&ctx.accounts.contribution_account, &contribution)?;
+
+            emit!(Contribution {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                contributor: user,
+                mint,
+                amount,
+                tokens_purchased,
+                timestamp: now as u64,
+            });
+        } else {
+            let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+            let signer = &[&seeds[..]];
+
+            let cpi_accounts = token_interface::TransferChecked {
+                from: ctx.accounts.presale_usdt.to_account_info(),
+                mint: ctx.accounts.payment_mint.to_account_info(),
+                to: ctx.accounts.user_usdt.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.payment_mint.decimals)?;
+        }
+
+        emit!(LotteryTicketSettled {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            won,
+            amount,
+            timestamp: now as u64,
+        });
+
+        if won {
+            // The presale signs the receipt mint, which it cannot do while
+            // borrowed.
+            drop(presale);
+            mint_receipts(
+                &ctx.accounts.presale,
+                ctx.accounts.receipt_mint.as_ref(),
+                ctx.accounts.user_receipt.as_ref(),
+                ctx.accounts.receipt_token_program.as_ref(),
+                user,
+                amount,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a ticket's amount when the lottery was not drawn by the draw
+    /// deadline. Permissionless, so an absent owner cannot lock the tickets
+    /// in the vault.
+    pub fn refund_lottery_ticket(ctx: Context<RefundLotteryTicket>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        presale.check_lottery_expired(now)?;
+
+        let amount = ctx.accounts.ticket.amount;
+        presale.lottery_unsettled = presale.lottery_unsettled.saturating_sub(1);
+
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.presale_usdt.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            to: ctx.accounts.user_usdt.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.payment_mint.decimals)?;
+
+        emit!(LotteryTicketSettled {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: ctx.accounts.user.key(),
+            won: false,
+            amount,
+            timestamp: now as u64,
+        });
+
+        Ok(())
+    }
+
+    pub fn contribute_sol(
+        ctx: Context<ContributeSol>,
+        lamports: u64,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let sale_registry = ctx.accounts.sale_registry.load()?;
+        let user = ctx.accounts.user.key();
+
+        require!(!presale.paused.get(), PresaleError::PresalePaused);
+        require!(!presale.emergency.get(), PresaleError::EmergencyMode);
+        require!(presale.is_active.get(), PresaleError::PresaleNotActive);
+        require!(!presale.is_closed.get(), PresaleError::PresaleClosed);
+        require!(presale.lottery_ticket_amount == 0, PresaleError::LotteryModeActive);
+        require!(
+            presale.in_sale_window(Clock::get()?.unix_timestamp),
+            PresaleError::OutsideSaleWindow
+        );
+        require!(
+            !presale.in_commit_reveal(Clock::get()?.unix_timestamp),
+            PresaleError::CommitRevealActive
+        );
+        // Oversubscription refunds are booked per payment mint, so SOL is
+        // not accepted in that mode.
+        require!(
+            (presale.sol_usdt_rate > 0 || presale.sol_price_feed().is_some()) && !presale.oversubscription.get(),
+            PresaleError::SolContributionsDisabled
+        );
+
+        let mut contribution = load_contribution_account(
+            &ctx.accounts.contribution_account.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &mut presale,
+            presale_key,
+            user,
+        )?;
+
+        let (tier, tier_max) = resolve_tier(
+            &presale,
+            &sale_registry,
+            user,
+            &ctx.accounts.whitelist_entry,
+            ctx.accounts.gating_token_account.as_ref(),
+            ctx.accounts.kyc_attestation.as_ref(),
+            contribution.user_limit,
+        )?;
+
+        let amount = match presale.sol_price_feed() {
+            Some(sol_price_feed) => {
+                let price_feed = ctx
+                    .accounts
+                    .sol_price_feed
+                    .as_ref()
+                    .ok_or(PresaleError::InvalidPriceFeed)?;
+                require_keys_eq!(price_feed.key(), sol_price_feed, PresaleError::InvalidPriceFeed);
+
+                oracle::pyth_usdt_value(
+                    price_feed,
+                    lamports,
+                    9,
+                    presale.max_price_staleness,
+                    presale.max_price_confidence_bps,
+                )?
+            }
+            None => presale.sol_to_usdt(lamports)?,
+        };
+        require!(amount > 0, PresaleError::ContributionTooSmall);
+
+        let tokens_purchased = record_contribution(
+            &mut presale,
+            &sale_registry,
+            presale_key,
+            &mut contribution,
+            token::spl_token::native_mint::ID,
+            amount,
+            tier,
+            tier_max,
+        )?;
+        upgrade_whitelist_tier(&mut presale, &sale_registry, &mut contribution, &ctx.accounts.whitelist_entry)?;
+        contribution.sol_contribution = contribution
+            .sol_contribution
+            .checked_add(lamports)
+            .ok_or(PresaleError::Overflow)?;
+        save_contribution_account(&ctx.accounts.contribution_account, &contribution)?;
+        presale.total_sol_contributions = presale
+            .total_sol_contributions
+            .checked_add(lamports)
+            .ok_or(PresaleError::Overflow)?;
+
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.user.to_account_info(),
+            to: ctx.accounts.sol_vault.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.system_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        system_program::transfer(cpi_ctx, lamports)?;
+
+        let timestamp = Clock::get()?.unix_timestamp as u64;
+
+        emit!(SolContribution {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            lamports,
+            amount,
+            timestamp,
+        });
+
+        emit!(Contribution {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            mint: token::spl_token::native_mint::ID,
+            amount,
+            tokens_purchased,
+            timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_presale(
+        ctx: Context<ClosePresale>,
+        refunds_allowed: bool,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        consume_council_approval(
+            &presale,
+            presale_key,
+            ctx.accounts.proposal.as_mut(),
+            AdminAction::ClosePresale { refunds_allowed },
+        )?;
+
+        require!(!presale.paused.get(), PresaleError::PresalePaused);
+        require!(presale.is_active.get(), PresaleError::PresaleNotActive);
+
+        close_sale(&mut presale, presale_key, refunds_allowed, Clock::get()?.unix_timestamp)?;
+
+        ctx.accounts.registry_entry.status = presale.status();
+        presale.log_admin_action(AdminOp::ClosePresale, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Updates the presale's display metadata; only allowed before the sale
+    /// starts.
+    pub fn set_presale_metadata(
+        ctx: Context<UpdatePresale>,
+        name: String,
+        symbol: String,
+        description: String,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(now < presale.start_time, PresaleError::SaleAlreadyStarted);
+        validate_presale_metadata(&name, &symbol, &description)?;
+
+        presale.set_metadata(&name, &symbol, &description);
+
+        emit!(PresaleMetadataUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            name,
+            symbol,
+            description,
+            timestamp: now as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetPresaleMetadata, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Overrides the maximum contribution of a single wallet, regardless of
+    /// its tier; `None` removes the override. The limit is kept in the
+    /// wallet's `ContributionAccount`, which the owner pays for if needed.
+    pub fn set_user_limit(
+        ctx: Context<SetUserLimit>,
+        user: Pubkey,
+        max_contribution: Option<u64>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+
+        if let Some(max_contribution) = max_contribution {
+            require!(max_contribution > 0, PresaleError::InvalidMaxContribution);
+        }
+
+        let mut contribution = load_contribution_account(
+            &ctx.accounts.contribution_account.to_account_info(),
+            &ctx.accounts.owner.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &mut presale,
+            presale_key,
+            user,
+        )?;
+        contribution.user_limit = max_contribution;
+        save_contribution_account(&ctx.accounts.contribution_account, &contribution)?;
+
+        emit!(UserLimitUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user,
+            max_contribution,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetUserLimit, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Calls off a sale that never took any funds, either because it has
+    /// not started or because nobody contributed. The sale is closed for
+    /// good and its accounts can be reclaimed with `close_presale_account`.
+    pub fn cancel_presale(ctx: Context<SetPresaleStatus>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!presale.is_closed.get(), PresaleError::PresaleAlreadyClosed);
+        require!(!presale.in_commit_reveal(now), PresaleError::CommitRevealActive);
+        require!(
+            now < presale.start_time
+                || (presale.total_contributions == 0
+                    && presale.total_sol_contributions == 0
+                    && presale.lottery_ticket_count == 0),
+            PresaleError::PresaleHasContributions
+        );
+
+        presale.is_cancelled = true.into();
+        presale.is_closed = true.into();
+        presale.is_active = false.into();
+        presale.refunds_allowed = false.into();
+
+        emit!(PresaleCancelled {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            timestamp: now as u64,
+        });
+
+        ctx.accounts.registry_entry.status = presale.status();
+        presale.log_admin_action(AdminOp::CancelPresale, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Undoes a mistaken close, as long as nothing has been refunded,
+    /// withdrawn or allocated since.
+    pub fn reopen_presale(ctx: Context<SetPresaleStatus>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+
+        presale.check_reopenable()?;
+
+        presale.is_closed = false.into();
+        presale.is_active = true.into();
+        presale.soft_cap_reached = false.into();
+        presale.refunds_allowed = false.into();
+        presale.refund_deadline = 0;
+
+        emit!(PresaleReopened {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        ctx.accounts.registry_entry.status = presale.status();
+        presale.log_admin_action(AdminOp::ReopenPresale, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Closes the sale once `end_time` has passed. Permissionless, so the
+    /// sale ends on schedule without the owner; refunds are enabled only
+    /// when the soft cap was missed.
+    pub fn finalize_presale(ctx: Context<FinalizePresale>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(now > presale.end_time, PresaleError::SaleNotEnded);
+
+        close_sale(&mut presale, presale_key, false, now)?;
+
+        ctx.accounts.registry_entry.status = presale.status();
+
+        Ok(())
+    }
+
+    /// Trust-minimized exit: once `end_time` has passed with the soft cap
+    /// missed, anyone can close the sale and open refunds.
+    pub fn trigger_failed_sale(ctx: Context<FinalizePresale>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!presale.is_closed.get(), PresaleError::PresaleAlreadyClosed);
+        require!(now > presale.end_time, PresaleError::SaleNotEnded);
+        require!(
+            presale.total_contributions < presale.soft_cap,
+            PresaleError::SoftCapReached
+        );
+
+        emit!(SaleFailed {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            total_contributions: presale.total_contributions,
+            soft_cap: presale.soft_cap,
+            timestamp: now as u64,
+        });
+
+        close_sale(&mut presale, presale_key, true, now)?;
+
+        ctx.accounts.registry_entry.status = presale.status();
+
+        Ok(())
+    }
+
+    /// Oversubscription mode: scales every contribution down pro-rata so the
+    /// accepted total fits the hard cap, and books the excess per payment
+    /// mint as refundable through `claim_excess`. The owner passes the
+    /// `ContributionAccount`s in `remaining_accounts` in creation order,
+    /// resuming each call at `allocation_cursor`; the allocation is final
+    /// once every account has been walked.
+    pub fn finalize_allocation<'info>(
+        ctx: Context<'_, '_, '_, 'info, FinalizeAllocation<'info>>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+
+        require!(presale.oversubscription.get(), PresaleError::OversubscriptionNotEnabled);
+        require!(presale.is_closed.get(), PresaleError::PresaleNotClosed);
+        require!(!presale.allocation_finalized.get(), PresaleError::AllocationAlreadyFinalized);
+
+        // The requested total is fixed by the first batch; refunds are
+        // blocked until the last one.
+        if presale.allocation_requested == 0 {
+            presale.allocation_requested = presale.total_contributions;
+        }
+        let total_requested = presale.allocation_requested;
+        let hard_cap = presale.hard_cap;
+        let scale = |amount: u64| -> u64 {
+            if total_requested <= hard_cap {
+                amount
+            } else {
+                ((amount as u128) * (hard_cap as u128) / (total_requested as u128)) as u64
+            }
+        };
+
+        for contribution_info in ctx.remaining_accounts.iter() {
+            let mut contribution = Account::<ContributionAccount>::try_from(contribution_info)?;
+            require_keys_eq!(
+                contribution.presale,
+                presale_key,
+                PresaleError::InvalidContributionAccount
+            );
+            require!(
+                contribution.index == presale.allocation_cursor,
+                PresaleError::InvalidAllocationCursor
+            );
+            presale.allocation_cursor = presale
+                .allocation_cursor
+                .checked_add(1)
+                .ok_or(PresaleError::Overflow)?;
+            if contribution.amount == 0 {
+                continue;
+            }
+
+            let mut accepted_total: u64 = 0;
+            for entry in contribution.mint_contributions.iter_mut() {
+                let accepted = scale(entry.amount);
+                let excess = entry.amount - accepted;
+                if excess > 0 {
+                    entry.amount = accepted;
+                    entry.excess = entry.excess.checked_add(excess).ok_or(PresaleError::Overflow)?;
+                    presale.release_payment_total(&entry.mint, excess)?;
+                    presale.unclaimed_excess = presale
+                        .unclaimed_excess
+                        .checked_add(excess)
+                        .ok_or(PresaleError::Overflow)?;
+                }
+                accepted_total = accepted_total.checked_add(accepted).ok_or(PresaleError::Overflow)?;
+            }
+
+            let requested = contribution.amount;
+            if accepted_total < requested {
+                contribution.purchased_tokens = ((contribution.purchased_tokens as u128)
+                    * (accepted_total as u128)
+                    / (requested as u128)) as u64;
+                contribution.bonus_tokens = ((contribution.bonus_tokens as u128)
+                    * (accepted_total as u128)
+                    / (requested as u128)) as u64;
+                contribution.amount = accepted_total;
+            }
+            contribution.exit(&crate::ID)?;
+
+            presale.allocation_accepted = presale
+                .allocation_accepted
+                .checked_add(accepted_total)
+                .ok_or(PresaleError::Overflow)?;
+        }
+
+        if presale.allocation_cursor < presale.contribution_account_count {
+            return Ok(());
+        }
+
+        let total_accepted = presale.allocation_accepted;
+        presale.total_contributions = total_accepted;
+        presale.allocation_finalized = true.into();
+
+        emit!(AllocationFinalized {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            total_requested,
+            total_accepted,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::FinalizeAllocation, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Returns the non-accepted part of an oversubscribed contribution in
+    /// `payment_mint`. Permissionless so withdrawals cannot be blocked.
+    pub fn claim_excess(ctx: Context<ClaimExcess>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let user = ctx.accounts.user.key();
+        let mint = ctx.accounts.payment_mint.key();
+
+        require!(presale.allocation_finalized.get(), PresaleError::AllocationNotFinalized);
+
+        let excess = ctx.accounts.contribution_account.take_excess(&mint);
+        require!(excess > 0, PresaleError::NoExcessToClaim);
+        presale.unclaimed_excess = presale
+            .unclaimed_excess
+            .checked_sub(excess)
+            .ok_or(PresaleError::Overflow)?;
+
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.presale_usdt.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            to: ctx.accounts.user_usdt.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token_interface::transfer_checked(cpi_ctx, excess, ctx.accounts.payment_mint.decimals)?;
+
+        emit!(ExcessRefunded {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            mint,
+            amount: excess,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraws the whole SOL vault. When payout recipients are configured,
+    /// their wallets must be passed in `remaining_accounts` in the configured
+    /// order, and the lamports are split between them by share instead of
+    /// going to `authority`.
+    pub fn withdraw_sol<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawSol<'info>>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        consume_council_approval(&presale, presale_key, ctx.accounts.proposal.as_mut(), AdminAction::WithdrawSol)?;
+
+        presale.check_withdrawals_open(Clock::get()?.unix_timestamp)?;
+
+        let sol_balance = ctx.accounts.sol_vault.lamports();
+        require!(sol_balance > 0, PresaleError::NoFundsToWithdraw);
+        presale.settlement_started = true.into();
+
+        let platform_fee = ((sol_balance as u128) * (presale.platform_fee_bps as u128) / 10_000) as u64;
+        let owner_lamports = sol_balance - platform_fee;
+
+        let seeds = &[b"sol_vault".as_ref(), presale_key.as_ref(), &[presale.sol_vault_bump]];
+        let signer = &[&seeds[..]];
+        let timestamp = Clock::get()?.unix_timestamp as u64;
+
+        if platform_fee > 0 {
+            let platform_fee_recipient = ctx
+                .accounts
+                .platform_fee_recipient
+                .as_ref()
+                .ok_or(PresaleError::InvalidPlatformFeeAccount)?;
+
+            let cpi_accounts = system_program::Transfer {
+                from: ctx.accounts.sol_vault.to_account_info(),
+                to: platform_fee_recipient.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.system_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            system_program::transfer(cpi_ctx, platform_fee)?;
+
+            emit!(PlatformFeePaid {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                mint: token::spl_token::native_mint::ID,
+                amount: platform_fee,
+                timestamp,
+            });
+        }
+
+        let recipients = presale.payout_recipients().to_vec();
+        if recipients.is_empty() {
+            let cpi_accounts = system_program::Transfer {
+                from: ctx.accounts.sol_vault.to_account_info(),
+                to: ctx.accounts.authority.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.system_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            system_program::transfer(cpi_ctx, owner_lamports)?;
+        } else {
+            require!(
+                ctx.remaining_accounts.len() == recipients.len(),
+                PresaleError::InvalidPayoutAccount
+            );
+
+            let mut distributed: u64 = 0;
+            for (i, (recipient, recipient_info)) in recipients.iter().zip(ctx.remaining_accounts.iter()).enumerate() {
+                require_keys_eq!(recipient_info.key(), recipient.wallet, PresaleError::InvalidPayoutAccount);
+
+                // The last recipient takes the rounding remainder.
+                let amount = if i == recipients.len() - 1 {
+                    owner_lamports - distributed
+                } else {
+                    ((owner_lamports as u128) * (recipient.share_bps as u128) / 10_000) as u64
+                };
+                distributed += amount;
+                if amount == 0 {
+                    continue;
+                }
+
+                let cpi_accounts = system_program::Transfer {
+                    from: ctx.accounts.sol_vault.to_account_info(),
+                    to: recipient_info.clone(),
+                };
+                let cpi_program = ctx.accounts.system_program.to_account_info();
+                let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+                system_program::transfer(cpi_ctx, amount)?;
+
+                emit!(PayoutDistributed {
+                    presale: presale_key,
+                    sequence: presale.next_sequence(),
+                    recipient: recipient.wallet,
+                    mint: token::spl_token::native_mint::ID,
+                    amount,
+                    timestamp,
+                });
+            }
+        }
+
+        emit!(SolWithdrawn {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            lamports: owner_lamports,
+            timestamp,
+        });
+
+        presale.log_admin_action(AdminOp::WithdrawSol, ctx.accounts.authority.key())?;
+
+        Ok(())
+    }
+
+    /// Schedules moving the vault to `destination` after
+    /// `EMERGENCY_WITHDRAW_DELAY`, at any point in the sale. The presale
+    /// enters emergency mode right away, so contributions stop and everyone
+    /// can refund during the delay.
+    pub fn initiate_emergency_withdraw(
+        ctx: Context<GovernedUpdate>,
+        destination: Pubkey,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        consume_council_approval(
+            &presale,
+            presale_key,
+            ctx.accounts.proposal.as_mut(),
+            AdminAction::EmergencyWithdraw { destination },
+        )?;
+
+        require!(presale.emergency_withdraw_at == 0, PresaleError::EmergencyWithdrawInitiated);
+
+        let now = Clock::get()?.unix_timestamp;
+        presale.emergency = true.into();
+        presale.emergency_withdraw_at = now
+            .checked_add(EMERGENCY_WITHDRAW_DELAY)
+            .ok_or(PresaleError::Overflow)?;
+        presale.emergency_withdraw_destination = destination;
+
+        emit!(EmergencyWithdrawInitiated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            destination,
+            executable_at: presale.emergency_withdraw_at,
+            timestamp: now as u64,
+        });
+
+        presale.log_admin_action(AdminOp::InitiateEmergencyWithdraw, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Moves the whole `payment_mint` vault balance to the emergency
+    /// withdraw destination once the delay has passed.
+    pub fn execute_emergency_withdraw(ctx: Context<ExecuteEmergencyWithdraw>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(presale.emergency_withdraw_at != 0, PresaleError::EmergencyWithdrawNotInitiated);
+        require!(now >= presale.emergency_withdraw_at, PresaleError::EmergencyWithdrawDelayActive);
+
+        let amount = ctx.accounts.presale_usdt.amount;
+        require!(amount > 0, PresaleError::NoFundsToWithdraw);
+
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.presale_usdt.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            to: ctx.accounts.destination_usdt.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.payment_mint.decimals)?;
+
+        emit!(EmergencyWithdrawExecuted {
+            presale: ctx.accounts.presale.key(),
+            sequence: presale.next_sequence(),
+            mint: ctx.accounts.payment_mint.key(),
+            amount,
+            destination: ctx.accounts.destination_usdt.key(),
+            timestamp: now as u64,
+        });
+
+        presale.log_admin_action(AdminOp::ExecuteEmergencyWithdraw, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Returns tokens of a mint the sale does not accept that were sent to
+    /// one of the presale's token accounts by mistake.
+    pub fn rescue_tokens(ctx: Context<RescueTokens>) -> Result<()> {
+        let amount = ctx.accounts.presale_token.amount;
+        require!(amount > 0, PresaleError::NoFundsToWithdraw);
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.presale_token.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.destination_token.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        emit!(TokensRescued {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            mint: ctx.accounts.mint.key(),
+            amount,
+            destination: ctx.accounts.destination_token.key(),
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::RescueTokens, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Moves refunds in `payment_mint` that were never claimed to the
+    /// treasury once the refund window has closed. The non-claimers'
+    /// `ContributionAccount`s are passed in `remaining_accounts`; only what
+    /// they still have booked in the mint is swept, released from the sale
+    /// totals like a refund, and it all goes to the treasury: the money is
+    /// the contributors', so no platform fee is taken. Anyone may call it.
+    pub fn sweep_unclaimed_refunds<'info>(
+        ctx: Context<'_, '_, '_, 'info, SweepUnclaimedRefunds<'info>>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!presale.emergency.get(), PresaleError::EmergencyMode);
+        require!(presale.is_closed.get(), PresaleError::PresaleNotClosed);
+        require!(presale.refunds_allowed.get(), PresaleError::RefundsNotAllowed);
+        require!(presale.refund_window_over(now), PresaleError::RefundWindowOpen);
+
+        let mint = ctx.accounts.payment_mint.key();
+        let mut amount: u64 = 0;
+        let mut non_claimers: u32 = 0;
+        for contribution_info in ctx.remaining_accounts.iter() {
+            let mut contribution = Account::<ContributionAccount>::try_from(contribution_info)?;
+            require_keys_eq!(
+                contribution.presale,
+                presale_key,
+                PresaleError::InvalidContributionAccount
+            );
+            let unclaimed = contribution.mint_contribution(&mint);
+            if unclaimed == 0 {
+                continue;
+            }
+
+            release_refund(&mut presale, &mut contribution, mint, unclaimed)?;
+            contribution.exit(&crate::ID)?;
+            amount = amount.checked_add(unclaimed).ok_or(PresaleError::Overflow)?;
+            non_claimers += 1;
+        }
+        require!(amount > 0, PresaleError::NoFundsToWithdraw);
+        presale.settlement_started = true.into();
+
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.presale_usdt.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            to: ctx.accounts.treasury_usdt.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.payment_mint.decimals)?;
+
+        emit!(UnclaimedRefundsSwept {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            mint,
+            amount,
+            non_claimers,
+            timestamp: now as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraws `amount` of `payment_mint` from the vault, or everything
+    /// available when `amount` is `None`, so proceeds can be taken in
+    /// tranches. When payout recipients are configured, their token accounts
+    /// must be passed in `remaining_accounts` in the configured order, and
+    /// the proceeds are split between them by share instead of going to
+    /// `owner_usdt`.
+    pub fn withdraw_funds<'info>(
+        ctx: Context<'_, '_, '_, 'info, WithdrawFunds<'info>>,
+        amount: Option<u64>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        consume_council_approval(
+            &presale,
+            presale_key,
+            ctx.accounts.proposal.as_mut(),
+            AdminAction::WithdrawFunds { payment_mint: ctx.accounts.payment_mint.key(), amount },
+        )?;
+
+        presale.check_withdrawals_open(Clock::get()?.unix_timestamp)?;
+
+        // When refunds were allowed, contributions still booked belong to
+        // contributors who never claimed them; only the sweep moves those.
+        let usdt_balance = ctx.accounts.presale_usdt.amount;
+        let refundable = if presale.refunds_allowed.get() {
+            presale.payment_total(&ctx.accounts.payment_mint.key())
+        } else {
+            0
+        };
+        let available = usdt_balance.saturating_sub(refundable);
+        let withdraw_amount = amount.unwrap_or(available);
+        require!(withdraw_amount > 0, PresaleError::NoFundsToWithdraw);
+        require!(withdraw_amount <= available, PresaleError::InsufficientVaultBalance);
+
+        let platform_fee = ((withdraw_amount as u128) * (presale.platform_fee_bps as u128) / 10_000) as u64;
+        let owner_amount = withdraw_amount - platform_fee;
+
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+        let signer = &[&seeds[..]];
+        let timestamp = Clock::get()?.unix_timestamp as u64;
+
+        if platform_fee > 0 {
+            let platform_fee_usdt = ctx
+                .accounts
+                .platform_fee_usdt
+                .as_ref()
+                .ok_or(PresaleError::InvalidPlatformFeeAccount)?;
+
+            let cpi_accounts = token_interface::TransferChecked {
+                from: ctx.accounts.presale_usdt.to_account_info(),
+                mint: ctx.accounts.payment_mint.to_account_info(),
+                to: platform_fee_usdt.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            token_interface::transfer_checked(cpi_ctx, platform_fee, ctx.accounts.payment_mint.decimals)?;
+
+            emit!(PlatformFeePaid {
+                presale: ctx.accounts.presale.key(),
+                sequence: presale.next_sequence(),
+                mint: ctx.accounts.payment_mint.key(),
+                amount: platform_fee,
+                timestamp,
+            });
+        }
+
+        let recipients = presale.payout_recipients().to_vec();
+        if recipients.is_empty() {
+            let cpi_accounts = token_interface::TransferChecked {
+                from: ctx.accounts.presale_usdt.to_account_info(),
+                mint: ctx.accounts.payment_mint.to_account_info(),
+                to: ctx.accounts.owner_usdt.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            token_interface::transfer_checked(cpi_ctx, owner_amount, ctx.accounts.payment_mint.decimals)?;
+        } else {
+            require!(
+                ctx.remaining_accounts.len() == recipients.len(),
+                PresaleError::InvalidPayoutAccount
+            );
+
+            let mint = ctx.accounts.payment_mint.key();
+            let mut distributed: u64 = 0;
+            for (i, (recipient, recipient_info)) in recipients.iter().zip(ctx.remaining_accounts.iter()).enumerate() {
+                let recipient_usdt = InterfaceAccount::<token_interface::TokenAccount>::try_from(recipient_info)?;
+                require_keys_eq!(recipient_usdt.mint, mint, PresaleError::InvalidPayoutAccount);
+                require_keys_eq!(recipient_usdt.owner, recipient.wallet, PresaleError::InvalidPayoutAccount);
+
+                // The last recipient takes the rounding remainder.
+                let amount = if i == recipients.len() - 1 {
+                    owner_amount - distributed
+                } else {
+                    ((owner_amount as u128) * (recipient.share_bps as u128) / 10_000) as u64
+                };
+                distributed += amount;
+                if amount == 0 {
+                    continue;
+                }
+
+                let cpi_accounts = token_interface::TransferChecked {
+                    from: ctx.accounts.presale_usdt.to_account_info(),
+                    mint: ctx.accounts.payment_mint.to_account_info(),
+                    to: recipient_info.clone(),
+                    authority: ctx.accounts.vault_authority.to_account_info(),
+                };
+                let cpi_program = ctx.accounts.token_program.to_account_info();
+                let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+                token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.payment_mint.decimals)?;
+
+                emit!(PayoutDistributed {
+                    presale: ctx.accounts.presale.key(),
+                    sequence: presale.next_sequence(),
+                    recipient: recipient.wallet,
+                    mint,
+                    amount,
+                    timestamp,
+                });
+            }
+        }
+
+        presale.settlement_started = true.into();
+
+        emit!(FundsWithdrawn {
+            presale: ctx.accounts.presale.key(),
+            sequence: presale.next_sequence(),
+            amount: owner_amount,
+            timestamp,
+        });
+
+        presale.log_admin_action(AdminOp::WithdrawFunds, ctx.accounts.authority.key())?;
+
+        Ok(())
+    }
+
+    /// Closes a fully settled presale and returns its rent to the owner.
+    /// Every payment vault is passed in `remaining_accounts`, in
+    /// `payment_vaults` order; each must be empty and is closed along with
+    /// the presale, as must the SOL vault be. The registry entry is marked
+    /// closed.
+    pub fn close_presale_account<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClosePresaleAccount<'info>>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!presale.emergency.get(), PresaleError::EmergencyMode);
+        require!(presale.is_closed.get(), PresaleError::PresaleNotClosed);
+        require!(
+            !presale.refunds_allowed.get() || presale.refund_window_over(now),
+            PresaleError::RefundWindowOpen
+        );
+        require!(presale.lottery_unsettled == 0, PresaleError::LotteryNotSettled);
+        require!(presale.unclaimed_excess == 0, PresaleError::ExcessNotClaimed);
+        require!(ctx.accounts.sol_vault.lamports() == 0, PresaleError::VaultNotEmpty);
+        require!(
+            ctx.remaining_accounts.len() == presale.payment_vaults().len(),
+            PresaleError::InvalidVaultAccount
+        );
+
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        for (vault_info, payment_vault) in ctx.remaining_accounts.iter().zip(presale.payment_vaults().iter()) {
+            require_keys_eq!(vault_info.key(), *payment_vault, PresaleError::InvalidVaultAccount);
+            let vault = InterfaceAccount::<token_interface::TokenAccount>::try_from(vault_info)?;
+            require_keys_eq!(vault.owner, ctx.accounts.vault_authority.key(), PresaleError::InvalidVaultAccount);
+            require!(vault.amount == 0, PresaleError::VaultNotEmpty);
+
+            let cpi_accounts = token_interface::CloseAccount {
+                account: vault_info.clone(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            token_interface::close_account(cpi_ctx)?;
+        }
+
+        emit!(PresaleAccountClosed {
+            presale: ctx.accounts.presale.key(),
+            sequence: presale.next_sequence(),
+            vaults_closed: ctx.remaining_accounts.len() as u32,
+            timestamp: now as u64,
+        });
+
+        ctx.accounts.registry_entry.status = PresaleStatus::AccountClosed;
+
+        Ok(())
+    }
+
+    pub fn refund(ctx: Context<Refund>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let user = ctx.accounts.user.key();
+
+        presale.check_refunds_open(Clock::get()?.unix_timestamp)?;
+
+        // Refunds are paid per payment mint, in the mint that was contributed.
+        let mint = ctx.accounts.payment_mint.key();
+        let contribution_account = &mut ctx.accounts.contribution_account;
+        let contribution = contribution_account.mint_contribution(&mint);
+        require!(contribution > 0, PresaleError::NoContributionsToRefund);
+
+        let remaining_contribution = release_refund(&mut presale, contribution_account, mint, contribution)?;
+        record_refund_ticket(
+            &ctx.accounts.refund_ticket.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            presale_key,
+            user,
+            contribution,
+            remaining_contribution == 0,
+        )?;
+
+        let vault_authority_bump = presale.vault_authority_bump;
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        pay_refund(ctx.accounts, presale.receipt_mint(), signer, contribution, 0)?;
+
+        emit!(events::Refund {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            mint,
+            amount: contribution,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Returns the lamports the user contributed in SOL, releasing the USDT
+    /// value they were credited at.
+    pub fn refund_sol(ctx: Context<RefundSol>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let user = ctx.accounts.user.key();
+        let now = Clock::get()?.unix_timestamp;
+
+        presale.check_refunds_open(now)?;
+
+        let native_mint = token::spl_token::native_mint::ID;
+        let contribution_account = &mut ctx.accounts.contribution_account;
+        let amount = contribution_account.mint_contribution(&native_mint);
+        let lamports = std::mem::take(&mut contribution_account.sol_contribution);
+        require!(lamports > 0, PresaleError::NoContributionsToRefund);
+
+        let remaining_contribution = release_refund(&mut presale, contribution_account, native_mint, amount)?;
+        record_refund_ticket(
+            &ctx.accounts.refund_ticket.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            presale_key,
+            user,
+            amount,
+            remaining_contribution == 0,
+        )?;
+        presale.total_sol_contributions = presale
+            .total_sol_contributions
+            .checked_sub(lamports)
+            .ok_or(PresaleError::Overflow)?;
+
+        let seeds = &[b"sol_vault".as_ref(), presale_key.as_ref(), &[presale.sol_vault_bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.sol_vault.to_account_info(),
+            to: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.system_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        system_program::transfer(cpi_ctx, lamports)?;
+
+        emit!(SolRefunded {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            lamports,
+            amount,
+            timestamp: now as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Pushes refunds in `payment_mint` to the token accounts passed in
+    /// `remaining_accounts`, each followed by its owner's `RefundTicket` and
+    /// `ContributionAccount` PDAs; the token account's owner is the
+    /// refunded user. Users already
+    /// refunded, or with nothing in this mint, are skipped, so a batch can
+    /// safely be resubmitted.
+    pub fn bulk_refund<'info>(
+        ctx: Context<'_, '_, '_, 'info, BulkRefund<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.remaining_accounts.len() <= MAX_BULK_REFUND * 3,
+            PresaleError::ExceedsBulkRefundLimit
+        );
+        require!(
+            ctx.remaining_accounts.len().is_multiple_of(3),
+            PresaleError::InvalidRefundTicket
+        );
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        presale.check_refunds_open(now)?;
+        // Receipts can only be burned by their holder.
+        require!(presale.receipt_mint().is_none(), PresaleError::ReceiptsRequireUserRefund);
+
+        let mint = ctx.accounts.payment_mint.key();
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        for accounts in ctx.remaining_accounts.chunks(3) {
+            let (user_usdt_info, ticket_info, contribution_info) = (&accounts[0], &accounts[1], &accounts[2]);
+            let user_usdt = InterfaceAccount::<token_interface::TokenAccount>::try_from(user_usdt_info)?;
+            require_keys_eq!(user_usdt.mint, mint, PresaleError::InvalidUserUsdtAccount);
+            let user = user_usdt.owner;
+
+            if !ticket_info.data_is_empty() {
+                let ticket = {
+                    let data = ticket_info.try_borrow_data()?;
+                    RefundTicket::try_deserialize(&mut &data[..])?
+                };
+                if ticket.fully_refunded {
+                    continue;
+                }
+            }
+
+            let mut contribution_account = Account::<ContributionAccount>::try_from(contribution_info)?;
+            require!(
+                contribution_account.presale == presale_key && contribution_account.user == user,
+                PresaleError::InvalidContributionAccount
+            );
+            let contribution = contribution_account.mint_contribution(&mint);
+            if contribution == 0 {
+                continue;
+            }
+
+            let remaining_contribution = release_refund(&mut presale, &mut contribution_account, mint, contribution)?;
+            contribution_account.exit(&crate::ID)?;
+            record_refund_ticket(
+                ticket_info,
+                &ctx.accounts.owner.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                presale_key,
+                user,
+                contribution,
+                remaining_contribution == 0,
+            )?;
+
+            let cpi_accounts = token_interface::TransferChecked {
+                from: ctx.accounts.presale_usdt.to_account_info(),
+                mint: ctx.accounts.payment_mint.to_account_info(),
+                to: user_usdt_info.clone(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            token_interface::transfer_checked(cpi_ctx, contribution, ctx.accounts.payment_mint.decimals)?;
+
+            emit!(events::Refund {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                contributor: user,
+                mint,
+                amount: contribution,
+                timestamp: now as u64,
+            });
+        }
+
+        presale.log_admin_action(AdminOp::BulkRefund, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Force-refunds a flagged wallet's contribution in `payment_mint`,
+    /// whatever the sale state. Once nothing is left in any mint, the
+    /// user's token allocation is cleared. Receipts, if any, stay with the
+    /// user since only the holder can burn them.
+    pub fn compliance_refund(ctx: Context<ComplianceClawback>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let user = ctx.accounts.user.key();
+        let mint = ctx.accounts.payment_mint.key();
+
+        let contribution_account = &mut ctx.accounts.contribution_account;
+        let contribution = contribution_account.mint_contribution(&mint);
+        require!(contribution > 0, PresaleError::NoContributionsToRefund);
+
+        let remaining_contribution = release_refund(&mut presale, contribution_account, mint, contribution)?;
+        if remaining_contribution == 0 {
+            contribution_account.purchased_tokens = 0;
+            contribution_account.bonus_tokens = 0;
+        }
+        record_refund_ticket(
+            &ctx.accounts.refund_ticket.to_account_info(),
+            &ctx.accounts.owner.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            presale_key,
+            user,
+            contribution,
+            remaining_contribution == 0,
+        )?;
+
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[presale.vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.presale_usdt.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            to: ctx.accounts.user_usdt.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token_interface::transfer_checked(cpi_ctx, contribution, ctx.accounts.payment_mint.decimals)?;
+
+        emit!(ComplianceRefund {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            mint,
+            amount: contribution,
+            remaining_contribution,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::ComplianceRefund, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Closes the user's refund ticket, returning its rent. The refund
+    /// history it holds is lost.
+    pub fn close_refund_ticket(ctx: Context<CloseRefundTicket>) -> Result<()> {
+        emit!(RefundTicketClosed {
+            presale: ctx.accounts.presale.key(),
+            sequence: ctx.accounts.presale.load_mut()?.next_sequence(),
+            user: ctx.accounts.user.key(),
+            refunded_amount: ctx.accounts.refund_ticket.refunded_amount,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Closes a contributor's `ContributionAccount`, and their whitelist
+    /// entry if passed, returning the rent to them. See
+    /// `SaleConfig::check_user_account_closable` for who may close it when.
+    /// Closing an account settles the sale, so it can no longer be reopened.
+    pub fn close_user_account(ctx: Context<CloseUserAccount>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let user = ctx.accounts.user.key();
+        let authority = ctx.accounts.authority.key();
+        let now = Clock::get()?.unix_timestamp;
+
+        let drained = !ctx.accounts.contribution_account.has_balance();
+        presale.check_user_account_closable(&authority, &user, drained, now)?;
+        presale.settlement_started = true.into();
+
+        if let Some(entry) = &ctx.accounts.whitelist_entry {
+            presale.remove_tier_member(entry.tier);
+        }
+
+        emit!(UserAccountClosed {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            user,
+            closed_by: authority,
+            timestamp: now as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Returns `amount` of the user's contribution in `payment_mint`. Allowed
+    /// while the sale is open when the owner enables partial refunds, and
+    /// after close whenever refunds are allowed.
+    pub fn refund_partial(ctx: Context<Refund>, amount: u64) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let user = ctx.accounts.user.key();
+
+        if presale.is_closed.get() || presale.emergency.get() {
+            presale.check_refunds_open(Clock::get()?.unix_timestamp)?;
+        } else {
+            require!(!presale.paused.get(), PresaleError::PresalePaused);
+            require!(presale.partial_refunds_enabled.get(), PresaleError::RefundsNotAllowed);
+        }
+
+        let mint = ctx.accounts.payment_mint.key();
+        let contribution_account = &mut ctx.accounts.contribution_account;
+        let contribution = contribution_account.mint_contribution(&mint);
+        require!(amount > 0 && amount <= contribution, PresaleError::NoContributionsToRefund);
+
+        let remaining_contribution = release_refund(&mut presale, contribution_account, mint, amount)?;
+        require!(
+            remaining_contribution == 0 || remaining_contribution >= presale.min_contribution,
+            PresaleError::BelowMinContribution
+        );
+
+        record_refund_ticket(
+            &ctx.accounts.refund_ticket.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            presale_key,
+            user,
+            amount,
+            presale.is_closed.get() && remaining_contribution == 0,
+        )?;
+
+        let fee = if presale.is_closed.get() || presale.emergency.get() {
+            0
+        } else {
+            ((amount as u128) * (presale.refund_fee_bps as u128) / 10_000) as u64
+        };
+
+        let vault_authority_bump = presale.vault_authority_bump;
+        let seeds = &[b"vault".as_ref(), presale_key.as_ref(), &[vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        pay_refund(ctx.accounts, presale.receipt_mint(), signer, amount, fee)?;
+
+        let timestamp = Clock::get()?.unix_timestamp as u64;
+
+        if fee > 0 {
+            emit!(RefundFeeCharged {
+                presale: presale_key,
+                sequence: presale.next_sequence(),
+                contributor: user,
+                mint,
+                fee,
+                timestamp,
+            });
+        }
+
+        emit!(events::Refund {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: user,
+            mint,
+            amount: amount - fee,
+            timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_min_contribution(
+        ctx: Context<TimelockedUpdate>,
+        new_min: u64,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        consume_timelock(
+            &presale,
+            presale_key,
+            ctx.accounts.queued_change.as_ref(),
+            ParamChange::MinContribution { new_min },
+        )?;
+        require!(new_min > 0, PresaleError::InvalidMinContribution);
+
+        let old_min_contribution = presale.min_contribution;
+        presale.min_contribution = new_min;
+
+        emit!(MinContributionUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.owner.key(),
+            old_min_contribution,
+            new_min_contribution: new_min,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetMinContribution, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_contribution_rules(
+        ctx: Context<UpdatePresale>,
+        contribution_increment: u64,
+        dust_threshold: u64,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.contribution_increment = contribution_increment;
+        presale.dust_threshold = dust_threshold;
+
+        emit!(ContributionRulesUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contribution_increment,
+            dust_threshold,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetContributionRules, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Moves the sale window; only allowed before the sale has started.
+    pub fn set_sale_window(
+        ctx: Context<UpdatePresale>,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(now < presale.start_time, PresaleError::SaleAlreadyStarted);
+        validate_sale_window(start_time, end_time)?;
+
+        let (old_start_time, old_end_time) = (presale.start_time, presale.end_time);
+        presale.start_time = start_time;
+        presale.end_time = end_time;
+
+        emit!(SaleWindowUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.owner.key(),
+            old_start_time,
+            old_end_time,
+            start_time,
+            end_time,
+            timestamp: now as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetSaleWindow, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Replaces the phase schedule. Phases must be ordered and must not
+    /// overlap; an empty schedule disables phases.
+    pub fn set_sale_phases(
+        ctx: Context<UpdatePresale>,
+        phases: Vec<SalePhase>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+
+        require!(
+            phases.len() <= MAX_SALE_PHASES,
+            PresaleError::ExceedsMaxSalePhases
+        );
+
+        for (i, phase) in phases.iter().enumerate() {
+            require!(phase.start_time < phase.end_time, PresaleError::InvalidSalePhases);
+            if i > 0 {
+                require!(
+                    phases[i - 1].end_time < phase.start_time,
+                    PresaleError::InvalidSalePhases
+                );
+            }
+        }
+
+        presale.set_phases(&phases);
+
+        emit!(SalePhasesUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            phase_count: presale.phases().len() as u8,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetSalePhases, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Enables lottery mode with a fixed ticket price and a commitment to
+    /// the draw seed. Must be configured before anyone contributes.
+    pub fn configure_lottery(
+        ctx: Context<UpdatePresale>,
+        ticket_amount: u64,
+        seed_hash: [u8; 32],
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+
+        require!(
+            presale.total_contributions == 0 && presale.lottery_ticket_count == 0,
+            PresaleError::PresaleHasContributions
+        );
+
+        presale.lottery_ticket_amount = ticket_amount;
+        presale.lottery_seed_hash = seed_hash;
+
+        emit!(LotteryConfigured {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            ticket_amount,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::ConfigureLottery, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_hard_cap(
+        ctx: Context<GovernedUpdate>,
+        new_hard_cap: u64,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        consume_council_approval(
+            &presale,
+            presale_key,
+            ctx.accounts.proposal.as_mut(),
+            AdminAction::SetHardCap { new_hard_cap },
+        )?;
+        consume_timelock(
+            &presale,
+            presale_key,
+            ctx.accounts.queued_change.as_ref(),
+            ParamChange::HardCap { new_hard_cap },
+        )?;
+        require!(new_hard_cap > 0, PresaleError::InvalidHardCap);
+        require!(
+            new_hard_cap >= presale.total_contributions,
+            PresaleError::HardCapLessThanTotal
+        );
+        require!(new_hard_cap >= presale.soft_cap, PresaleError::InvalidSoftCap);
+
+        let old_hard_cap = presale.hard_cap;
+        presale.hard_cap = new_hard_cap;
+
+        emit!(HardCapUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.owner.key(),
+            old_hard_cap,
+            new_hard_cap,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetHardCap, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_public_sale(
+        ctx: Context<UpdateRegistry>,
+        public_sale: bool,
+        default_tier: String,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+
+        // The default tier only has to exist while the public sale is on.
+        let default_tier = match sale_registry.resolve_tier_name(&default_tier) {
+            Ok(tier) => Some(tier),
+            Err(_) if !public_sale => None,
+            Err(err) => return Err(err),
+        };
+
+        sale_registry.public_sale = public_sale.into();
+        sale_registry.set_default_tier(default_tier);
+
+        emit!(PublicSaleUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            public_sale,
+            default_tier,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetPublicSale, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_partial_fill(
+        ctx: Context<UpdatePresale>,
+        partial_fill: bool,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.partial_fill = partial_fill.into();
+
+        emit!(PartialFillUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            partial_fill,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetPartialFill, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Sets how long refunds stay open after a close that allows them. Fixed
+    /// once the sale starts, so contributors know the window they get.
+    pub fn set_refund_period(
+        ctx: Context<UpdatePresale>,
+        refund_period: i64,
+    ) -> Result<()> {
+        require!(refund_period >= 0, PresaleError::InvalidRefundPeriod);
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        require!(Clock::get()?.unix_timestamp < presale.start_time, PresaleError::SaleAlreadyStarted);
+        let old_refund_period = presale.refund_period;
+        presale.refund_period = refund_period;
+
+        emit!(RefundPeriodUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.owner.key(),
+            old_refund_period,
+            refund_period,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetRefundPeriod, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_refund_fee(
+        ctx: Context<UpdatePresale>,
+        refund_fee_bps: u16,
+    ) -> Result<()> {
+        require!(refund_fee_bps <= 10_000, PresaleError::InvalidRefundFee);
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let old_refund_fee_bps = presale.refund_fee_bps;
+        presale.refund_fee_bps = refund_fee_bps;
+
+        emit!(RefundFeeUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.owner.key(),
+            old_refund_fee_bps,
+            refund_fee_bps,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetRefundFee, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_refunds_restore_capacity(
+        ctx: Context<UpdatePresale>,
+        refunds_restore_capacity: bool,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.refunds_restore_capacity = refunds_restore_capacity.into();
+
+        emit!(RefundsRestoreCapacityUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            refunds_restore_capacity,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetRefundsRestoreCapacity, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_partial_refunds(
+        ctx: Context<UpdatePresale>,
+        enabled: bool,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.partial_refunds_enabled = enabled.into();
+
+        emit!(PartialRefundsUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            enabled,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetPartialRefunds, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_oversubscription(
+        ctx: Context<UpdatePresale>,
+        oversubscription: bool,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+
+        require!(presale.total_contributions == 0, PresaleError::PresaleHasContributions);
+
+        presale.oversubscription = oversubscription.into();
+
+        emit!(OversubscriptionUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            oversubscription,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetOversubscription, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_early_bird_bonus(
+        ctx: Context<UpdatePresale>,
+        bonus_bps: u16,
+        cutoff: i64,
+        fill_bps: u16,
+    ) -> Result<()> {
+        require!(
+            bonus_bps <= 10_000 && fill_bps <= 10_000,
+            PresaleError::InvalidEarlyBirdBonus
+        );
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.early_bird_bonus_bps = bonus_bps;
+        presale.early_bird_cutoff = cutoff;
+        presale.early_bird_fill_bps = fill_bps;
+
+        emit!(EarlyBirdBonusUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            bonus_bps,
+            cutoff,
+            fill_bps,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetEarlyBirdBonus, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Sets or clears the bonding curve. Only allowed before any
+    /// contribution so every buyer is priced by the same schedule.
+    pub fn set_pricing_curve(
+        ctx: Context<UpdatePresale>,
+        pricing_curve: Option<PricingCurve>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+
+        require!(presale.total_contributions == 0, PresaleError::PresaleHasContributions);
+        if let Some(curve) = &pricing_curve {
+            curve.validate()?;
+        }
+
+        presale.pricing_curve.set(pricing_curve.as_ref());
+
+        emit!(PricingCurveUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            enabled: pricing_curve.is_some(),
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetPricingCurve, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_balance_tiers(
+        ctx: Context<TimelockedTierUpdate>,
+        gating_mint: Option<Pubkey>,
+        tier_names: Vec<String>,
+        min_balances: Vec<u64>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+        consume_timelock(
+            &presale,
+            presale_key,
+            ctx.accounts.queued_change.as_ref(),
+            ParamChange::BalanceTiers {
+                gating_mint,
+                tier_names: tier_names.clone(),
+                min_balances: min_balances.clone(),
+            },
+        )?;
+
+        require!(
+            tier_names.len() == min_balances.len(),
+            PresaleError::TierDataMismatch
+        );
+
+        require!(
+            tier_names.len() <= MAX_TIERS,
+            PresaleError::ExceedsMaxTiers
+        );
+
+        let mut balance_tiers = Vec::with_capacity(tier_names.len());
+        for (tier_name, min_balance) in tier_names.iter().zip(min_balances.iter()) {
+            balance_tiers.push(BalanceTier::new(*min_balance, sale_registry.resolve_tier_name(tier_name)?));
+        }
+
+        sale_registry.gating_mint = gating_mint.unwrap_or_default();
+        sale_registry.set_balance_tiers(&balance_tiers);
+
+        emit!(BalanceTiersUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            gating_mint,
+            tier_count: sale_registry.balance_tiers().len() as u8,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetBalanceTiers, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_upgrade_thresholds(
+        ctx: Context<TimelockedTierUpdate>,
+        tier_names: Vec<String>,
+        min_contributions: Vec<u64>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+        consume_timelock(
+            &presale,
+            presale_key,
+            ctx.accounts.queued_change.as_ref(),
+            ParamChange::UpgradeThresholds {
+                tier_names: tier_names.clone(),
+                min_contributions: min_contributions.clone(),
+            },
+        )?;
+
+        require!(
+            tier_names.len() == min_contributions.len(),
+            PresaleError::TierDataMismatch
+        );
+
+        require!(
+            tier_names.len() <= MAX_TIERS,
+            PresaleError::ExceedsMaxTiers
+        );
+
+        let mut upgrade_thresholds = Vec::with_capacity(tier_names.len());
+        for (tier_name, min_contribution) in tier_names.iter().zip(min_contributions.iter()) {
+            upgrade_thresholds.push(UpgradeThreshold::new(*min_contribution, sale_registry.resolve_tier_name(tier_name)?));
+        }
+
+        sale_registry.set_upgrade_thresholds(&upgrade_thresholds);
+
+        emit!(UpgradeThresholdsUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            threshold_count: sale_registry.upgrade_thresholds().len() as u8,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetUpgradeThresholds, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_tier_max_members(
+        ctx: Context<TimelockedTierUpdate>,
+        tier_name: String,
+        max_members: Option<u32>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+        consume_timelock(
+            &presale,
+            presale_key,
+            ctx.accounts.queued_change.as_ref(),
+            ParamChange::TierMaxMembers { tier_name: tier_name.clone(), max_members },
+        )?;
+
+        let tier = sale_registry.resolve_tier_name(&tier_name)?;
+        let member_count = presale.tier_totals(tier)?.member_count;
+        let tier_info = sale_registry.tier_at_mut(tier)?;
+        let old_max_members = tier_info.max_members();
+        if let Some(max_members) = max_members {
+            require!(
+                max_members >= member_count,
+                PresaleError::InvalidMaxMembers
+            );
+        }
+        tier_info.set_max_members(max_members);
+
+        emit!(TierMaxMembersUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.owner.key(),
+            tier,
+            old_max_members,
+            max_members,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetTierMaxMembers, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_receipt_nft_uri(
+        ctx: Context<UpdatePresale>,
+        uri: Option<String>,
+    ) -> Result<()> {
+        if let Some(uri) = &uri {
+            require!(uri.len() <= MAX_RECEIPT_URI_LENGTH, PresaleError::ReceiptNftUriTooLong);
+        }
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.set_receipt_nft_uri(uri.as_deref());
+
+        emit!(ReceiptNftUriUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            uri,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetReceiptNftUri, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_kyc_issuer(
+        ctx: Context<UpdateRegistry>,
+        kyc_issuer: Option<Pubkey>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+        sale_registry.kyc_issuer = kyc_issuer.unwrap_or_default();
+
+        emit!(KycIssuerUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            kyc_issuer,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetKycIssuer, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Queues a timelocked parameter change. It is applied by calling the
+    /// matching setter with the queued account once `eta` has passed.
+    pub fn queue_change(ctx: Context<QueueChange>, change: ParamChange) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let queued_change = &mut ctx.accounts.queued_change;
+        let now = Clock::get()?.unix_timestamp;
+
+        queued_change.presale = presale_key;
+        queued_change.index = presale.change_count;
+        queued_change.change = change.clone();
+        queued_change.eta = now.checked_add(presale.timelock_delay).ok_or(PresaleError::Overflow)?;
+        queued_change.bump = *ctx.bumps.get("queued_change").unwrap();
+
+        presale.change_count = presale.change_count.checked_add(1).ok_or(PresaleError::Overflow)?;
+
+        emit!(ChangeQueued {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            index: queued_change.index,
+            change,
+            eta: queued_change.eta,
+            timestamp: now as u64,
+        });
+
+        presale.log_admin_action(AdminOp::QueueChange, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn cancel_change(ctx: Context<CancelChange>) -> Result<()> {
+        let mut presale = ctx.accounts.presale.load_mut()?;
+
+        emit!(ChangeCancelled {
+            presale: ctx.accounts.presale.key(),
+            sequence: presale.next_sequence(),
+            index: ctx.accounts.queued_change.index,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::CancelChange, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Sets the timelock delay. While a timelock is active, changing it is
+    /// itself a queued change.
+    pub fn set_timelock_delay(ctx: Context<TimelockedUpdate>, delay: i64) -> Result<()> {
+        require!(delay >= 0, PresaleError::InvalidTimelockDelay);
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        consume_timelock(
+            &presale,
+            presale_key,
+            ctx.accounts.queued_change.as_ref(),
+            ParamChange::TimelockDelay { delay },
+        )?;
+
+        let old_delay = presale.timelock_delay;
+        presale.timelock_delay = delay;
+
+        emit!(TimelockDelayUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.owner.key(),
+            old_delay,
+            delay,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetTimelockDelay, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Replaces the admin council. Once a council exists, changing it needs
+    /// the current council's approval; an empty list removes it.
+    pub fn set_council(
+        ctx: Context<GovernedUpdate>,
+        members: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        validate_council(&members, threshold)?;
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        consume_council_approval(
+            &presale,
+            presale_key,
+            ctx.accounts.proposal.as_mut(),
+            AdminAction::SetCouncil { members: members.clone(), threshold },
+        )?;
+
+        presale.set_council(&members);
+        presale.council_threshold = threshold;
+
+        emit!(CouncilUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            members,
+            threshold,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetCouncil, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Opens a proposal for `action`, approved by the proposing member.
+    pub fn create_admin_proposal(
+        ctx: Context<CreateAdminProposal>,
+        action: AdminAction,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let proposal = &mut ctx.accounts.proposal;
+        let member = ctx.accounts.member.key();
+
+        proposal.presale = presale_key;
+        proposal.index = presale.proposal_count;
+        proposal.action = action;
+        proposal.approvals = vec![member];
+        proposal.executed = false;
+        proposal.bump = *ctx.bumps.get("proposal").unwrap();
+
+        presale.proposal_count = presale.proposal_count.checked_add(1).ok_or(PresaleError::Overflow)?;
+
+        emit!(AdminProposalCreated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            proposal: proposal.key(),
+            index: proposal.index,
+            proposer: member,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_admin_proposal(ctx: Context<ApproveAdminProposal>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        let member = ctx.accounts.member.key();
+
+        require!(!proposal.executed, PresaleError::ProposalAlreadyExecuted);
+        require!(!proposal.approvals.contains(&member), PresaleError::ProposalAlreadyApproved);
+
+        proposal.approvals.push(member);
+
+        emit!(AdminProposalApproved {
+            presale: ctx.accounts.presale.key(),
+            sequence: ctx.accounts.presale.load_mut()?.next_sequence(),
+            proposal: proposal.key(),
+            member,
+            approvals: proposal.approvals.len() as u32,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    /// First step of an ownership transfer; `None` cancels a pending one.
+    pub fn propose_owner(
+        ctx: Context<UpdatePresale>,
+        new_owner: Option<Pubkey>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.pending_owner = new_owner.unwrap_or_default();
+
+        emit!(OwnershipProposed {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            owner: presale.owner,
+            pending_owner: new_owner,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::ProposeOwner, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Completes an ownership transfer; must be signed by the pending owner.
+    pub fn accept_ownership(ctx: Context<AcceptOwnership>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let previous_owner = presale.owner;
+
+        presale.owner = ctx.accounts.new_owner.key();
+        presale.pending_owner = Pubkey::default();
+
+        emit!(OwnershipTransferred {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            previous_owner,
+            new_owner: presale.owner,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::AcceptOwnership, ctx.accounts.new_owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_treasury(
+        ctx: Context<UpdatePresale>,
+        treasury: Option<Pubkey>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.treasury = treasury.unwrap_or_default();
+
+        emit!(TreasuryUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            treasury,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetTreasury, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Sets the wallets `withdraw_funds` splits proceeds between. Shares must
+    /// add up to 10_000 basis points; an empty list pays the withdrawer.
+    pub fn set_payout_recipients(
+        ctx: Context<GovernedUpdate>,
+        recipients: Vec<PayoutRecipient>,
+    ) -> Result<()> {
+        validate_payout_recipients(&recipients)?;
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        consume_council_approval(
+            &presale,
+            presale_key,
+            ctx.accounts.proposal.as_mut(),
+            AdminAction::SetPayoutRecipients { recipients: recipients.clone() },
+        )?;
+
+        presale.set_payout_recipients(&recipients);
+
+        emit!(PayoutRecipientsUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            recipients,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetPayoutRecipients, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_operator(
+        ctx: Context<UpdatePresale>,
+        operator: Option<Pubkey>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.operator = operator.unwrap_or_default();
+
+        emit!(OperatorUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            operator,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetOperator, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_treasurer(
+        ctx: Context<UpdatePresale>,
+        treasurer: Option<Pubkey>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.treasurer = treasurer.unwrap_or_default();
+
+        emit!(TreasurerUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            treasurer,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetTreasurer, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_guardian(
+        ctx: Context<UpdatePresale>,
+        guardian: Option<Pubkey>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.guardian = guardian.unwrap_or_default();
+
+        emit!(GuardianUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            guardian,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetGuardian, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Puts the presale into emergency mode: contributions and withdrawals
+    /// stop and every contributor can refund, even while paused or open.
+    /// There is no way back out.
+    pub fn trigger_emergency(ctx: Context<TriggerEmergency>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        require!(!presale.emergency.get(), PresaleError::EmergencyMode);
+
+        presale.emergency = true.into();
+
+        emit!(EmergencyTriggered {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            guardian: ctx.accounts.guardian.key(),
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::TriggerEmergency, ctx.accounts.guardian.key())?;
+
+        Ok(())
+    }
+
+    pub fn issue_kyc_attestation(
+        ctx: Context<IssueKycAttestation>,
+        user: Pubkey,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+
+        if let Some(expires_at) = expires_at {
+            require!(expires_at > now, PresaleError::InvalidKycAttestation);
+        }
+
+        let kyc_attestation = &mut ctx.accounts.kyc_attestation;
+        kyc_attestation.issuer = ctx.accounts.issuer.key();
+        kyc_attestation.user = user;
+        kyc_attestation.issued_at = now;
+        kyc_attestation.expires_at = expires_at;
+        kyc_attestation.bump = *ctx.bumps.get("kyc_attestation").unwrap();
+
+        emit!(KycAttested {
+            issuer: kyc_attestation.issuer,
+            user,
+            expires_at,
+            timestamp: now as u64,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_kyc_attestation(
+        ctx: Context<RevokeKycAttestation>,
+        user: Pubkey,
+    ) -> Result<()> {
+        emit!(KycRevoked {
+            issuer: ctx.accounts.issuer.key(),
+            user,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_sol_usdt_rate(
+        ctx: Context<UpdatePresale>,
+        sol_usdt_rate: u64,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.sol_usdt_rate = sol_usdt_rate;
+
+        emit!(SolUsdtRateUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            sol_usdt_rate,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetSolUsdtRate, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn add_payment_mint(ctx: Context<AddPaymentMint>) -> Result<()> {
+        let vault = create_payment_vault(
+            &ctx.accounts.presale_vault.to_account_info(),
+            &ctx.accounts.owner.to_account_info(),
+            &ctx.accounts.vault_authority.to_account_info(),
+            &ctx.accounts.payment_mint.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.associated_token_program.to_account_info(),
+        )?;
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let mint = ctx.accounts.payment_mint.key();
+
+        require!(
+            ctx.accounts.payment_mint.decimals == 6,
+            PresaleError::InvalidPaymentMintDecimals
+        );
+        require!(
+            ctx.accounts.config.allows_payment_mint(&mint),
+            PresaleError::PaymentMintNotAllowed
+        );
+        require!(
+            !presale.payment_mints().contains(&mint),
+            PresaleError::PaymentMintAlreadyAccepted
+        );
+        require!(
+            presale.payment_mints().len() < MAX_PAYMENT_MINTS,
+            PresaleError::ExceedsMaxPaymentMints
+        );
+
+        presale.add_payment_mint(mint, vault)?;
+
+        emit!(PaymentMintAdded {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            mint,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::AddPaymentMint, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_sol_price_feed(
+        ctx: Context<UpdatePresale>,
+        sol_price_feed: Option<Pubkey>,
+        max_price_staleness: u64,
+        max_price_confidence_bps: u16,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+
+        if sol_price_feed.is_some() {
+            require!(
+                max_price_staleness > 0 && max_price_confidence_bps > 0,
+                PresaleError::InvalidPriceFeed
+            );
+        }
+
+        presale.sol_price_feed = sol_price_feed.unwrap_or_default();
+        presale.max_price_staleness = max_price_staleness;
+        presale.max_price_confidence_bps = max_price_confidence_bps;
+
+        emit!(SolPriceFeedUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            sol_price_feed,
+            max_price_staleness,
+            max_price_confidence_bps,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetSolPriceFeed, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Changes a tier's max contribution. Contributions already above the
+    /// new limit stand but cannot grow. Contributions are checked against
+    /// the tier itself, so every whitelisted member picks the change up.
+    pub fn update_tier_max_contribution(
+        ctx: Context<TimelockedTierUpdate>,
+        tier_name: String,
+        max_contribution: u64,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+        consume_timelock(
+            &presale,
+            presale_key,
+            ctx.accounts.queued_change.as_ref(),
+            ParamChange::TierMaxContribution { tier_name: tier_name.clone(), max_contribution },
+        )?;
+
+        require!(max_contribution > 0, PresaleError::InvalidMaxContribution);
+
+        let tier = sale_registry.resolve_tier_name(&tier_name)?;
+
+        let tier_info = sale_registry.tier_at_mut(tier)?;
+        let old_max_contribution = tier_info.max_contribution;
+        tier_info.max_contribution = max_contribution;
+
+        let sum_tier_max = sale_registry
+            .tiers
+            .iter()
+            .filter(|tier_info| !tier_info.is_empty())
+            .try_fold(0u64, |sum, tier_info| sum.checked_add(tier_info.max_contribution))
+            .ok_or(PresaleError::Overflow)?;
+        require!(
+            presale.hard_cap >= sum_tier_max,
+            PresaleError::HardCapLessThanTierMax
+        );
+
+        emit!(TierUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.owner.key(),
+            tier,
+            old_max_contribution,
+            max_contribution,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::UpdateTierMaxContribution, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_tier_total_cap(
+        ctx: Context<TimelockedTierUpdate>,
+        tier_name: String,
+        total_cap: Option<u64>,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+        consume_timelock(
+            &presale,
+            presale_key,
+            ctx.accounts.queued_change.as_ref(),
+            ParamChange::TierTotalCap { tier_name: tier_name.clone(), total_cap },
+        )?;
+
+        let tier = sale_registry.resolve_tier_name(&tier_name)?;
+        let total_contributions = presale.tier_totals(tier)?.total_contributions;
+        let tier_info = sale_registry.tier_at_mut(tier)?;
+        if let Some(total_cap) = total_cap {
+            require!(total_cap >= total_contributions, PresaleError::ExceedsTierCap);
+        }
+        let old_total_cap = tier_info.total_cap();
+        tier_info.set_total_cap(total_cap);
+
+        emit!(TierTotalCapUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.owner.key(),
+            tier,
+            old_total_cap,
+            total_cap,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetTierTotalCap, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn set_contribution_cooldown(
+        ctx: Context<UpdatePresale>,
+        contribution_cooldown: i64,
+    ) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        require!(contribution_cooldown >= 0, PresaleError::InvalidCooldown);
+
+        let old_contribution_cooldown = presale.contribution_cooldown;
+        presale.contribution_cooldown = contribution_cooldown;
+
+        emit!(ContributionCooldownUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.owner.key(),
+            old_contribution_cooldown,
+            contribution_cooldown,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetContributionCooldown, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Configures a commit-reveal round; pass zeros to disable it.
+    pub fn set_commit_reveal_window(
+        ctx: Context<UpdatePresale>,
+        commit_end: i64,
+        reveal_end: i64,
+    ) -> Result<()> {
+        require!(
+            (commit_end == 0 && reveal_end == 0) || commit_end < reveal_end,
+            PresaleError::InvalidCommitRevealWindow
+        );
+
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        presale.commit_end = commit_end;
+        presale.reveal_end = reveal_end;
+
+        emit!(CommitRevealWindowUpdated {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            commit_end,
+            reveal_end,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::SetCommitRevealWindow, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Permanently stops whitelist additions and tier reassignments, so
+    /// contributors know the allocation list can't change mid-sale.
+    pub fn freeze_whitelist(ctx: Context<UpdateRegistry>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let mut sale_registry = ctx.accounts.sale_registry.load_mut()?;
+        require!(!sale_registry.whitelist_frozen.get(), PresaleError::WhitelistFrozen);
+
+        sale_registry.whitelist_frozen = true.into();
+
+        emit!(WhitelistFrozen {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::FreezeWhitelist, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    pub fn pause_presale(ctx: Context<PausePresale>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        require!(!presale.paused.get(), PresaleError::PresaleAlreadyPaused);
+
+        presale.paused = true.into();
+
+        emit!(PresalePaused {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.authority.key(),
+            was_paused: false,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::Pause, ctx.accounts.authority.key())?;
+
+        Ok(())
+    }
+
+    /// Resumes the sale. Owner-only, so a guardian can halt the sale but
+    /// never restart it.
+    pub fn unpause_presale(ctx: Context<UnpausePresale>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        require!(presale.paused.get(), PresaleError::PresaleNotPaused);
+
+        presale.paused = false.into();
+
+        emit!(PresaleUnpaused {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            authority: ctx.accounts.owner.key(),
+            was_paused: true,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+
+        presale.log_admin_action(AdminOp::Unpause, ctx.accounts.owner.key())?;
+
+        Ok(())
+    }
+
+    /// Records the presale's current totals in its rolling snapshot PDA.
+    /// Permissionless, at most once per `MIN_SNAPSHOT_INTERVAL`; the caller
+    /// pays for the account on the first snapshot.
+    pub fn snapshot(ctx: Context<TakeSnapshot>) -> Result<()> {
+        let presale_key = ctx.accounts.presale.key();
+        let mut presale = ctx.accounts.presale.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
+        let entry = SnapshotEntry::of(&presale, now);
+
+        let index = record_snapshot(
+            &ctx.accounts.snapshots.to_account_info(),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            presale_key,
+            entry,
+        )?;
+
+        emit!(SnapshotTaken {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            index,
+            total_contributions: entry.total_contributions,
+            total_sol_contributions: entry.total_sol_contributions,
+            contributor_count: entry.contributor_count,
+            timestamp: now as u64,
+        });
+
+        Ok(())
+    }
+}
+
+/// Creates a `WhitelistEntry` PDA passed in through `remaining_accounts`,
+/// used by batch instructions that can't declare a fixed number of accounts.
+/// The canonical bump is filled in here.
+pub fn init_whitelist_entry<'info>(
+    entry_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    mut entry: WhitelistEntry,
+) -> Result<()> {
+    let presale = entry.presale;
+    let user = entry.user;
+    let (expected_entry, bump) = Pubkey::find_program_address(
+        &[b"wl", presale.as_ref(), user.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(
+        entry_info.key(),
+        expected_entry,
+        PresaleError::InvalidWhitelistEntry
+    );
+    require!(
+        entry_info.data_is_empty(),
+        PresaleError::UserAlreadyWhitelisted
+    );
+
+    create_pda_account(
+        entry_info,
+        payer,
+        system_program,
+        &[b"wl", presale.as_ref(), user.as_ref(), &[bump]],
+        WhitelistEntry::LEN,
+    )?;
+
+    entry.bump = bump;
+    let mut data = entry_info.try_borrow_mut_data()?;
+    entry.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+/// Closes a `WhitelistEntry` PDA passed in through `remaining_accounts`,
+/// returning its rent to `destination`. Returns the tier the user was in.
+pub fn close_whitelist_entry<'info>(
+    entry_info: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    presale: Pubkey,
+    user: Pubkey,
+) -> Result<u8> {
+    require!(
+        !entry_info.data_is_empty(),
+        PresaleError::UserNotWhitelisted
+    );
+
+    let entry = Account::<WhitelistEntry>::try_from(entry_info)?;
+    require!(
+        entry.presale == presale && entry.user == user,
+        PresaleError::InvalidWhitelistEntry
+    );
+
+    entry.close(destination.clone())?;
+
+    Ok(entry.tier)
+}
+
+/// Appends a record to the user's `WhitelistAuditTrail` PDA, creating the
+/// account on first use.
+pub fn record_whitelist_audit<'info>(
+    audit_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    presale: Pubkey,
+    user: Pubkey,
+    record: AuditRecord,
+) -> Result<()> {
+    let (expected_audit_trail, bump) = Pubkey::find_program_address(
+        &[b"audit", presale.as_ref(), user.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(
+        audit_info.key(),
+        expected_audit_trail,
+        PresaleError::InvalidAuditTrail
+    );
+
+    let mut audit_trail = if audit_info.data_is_empty() {
+        create_pda_account(
+            audit_info,
+            payer,
+            system_program,
+            &[b"audit", presale.as_ref(), user.as_ref(), &[bump]],
+            WhitelistAuditTrail::LEN,
+        )?;
+
+        WhitelistAuditTrail {
+            presale,
+            user,
+            records: Vec::new(),
+            bump,
+        }
+    } else {
+        let data = audit_info.try_borrow_data()?;
+        WhitelistAuditTrail::try_deserialize(&mut &data[..])?
+    };
+
+    require!(
+        audit_trail.records.len() < MAX_AUDIT_RECORDS,
+        PresaleError::AuditTrailFull
+    );
+    audit_trail.records.push(record);
+
+    let mut data = audit_info.try_borrow_mut_data()?;
+    audit_trail.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+/// Allocates a program-owned PDA of `space` bytes, funded by `payer`.
+///
+/// Like Anchor's `init`, an address that already holds lamports is topped up
+/// and then allocated and assigned, so pre-funding it cannot block creation.
+pub fn create_pda_account<'info>(
+    account_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    seeds: &[&[u8]],
+    space: usize,
+) -> Result<()> {
+    let signer = &[seeds];
+    let required_lamports = Rent::get()?.minimum_balance(space);
+    let current_lamports = account_info.lamports();
+
+    if current_lamports == 0 {
+        let cpi_accounts = system_program::CreateAccount {
+            from: payer.clone(),
+            to: account_info.clone(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer);
+        return system_program::create_account(
+            cpi_ctx,
+            required_lamports,
+            space as u64,
+            &crate::ID,
+        );
+    }
+
+    let shortfall = required_lamports.saturating_sub(current_lamports);
+    if shortfall > 0 {
+        let cpi_accounts = system_program::Transfer {
+            from: payer.clone(),
+            to: account_info.clone(),
+        };
+        let cpi_ctx = CpiContext::new(system_program.clone(), cpi_accounts);
+        system_program::transfer(cpi_ctx, shortfall)?;
+    }
+
+    let cpi_accounts = system_program::Allocate {
+        account_to_allocate: account_info.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer);
+    system_program::allocate(cpi_ctx, space as u64)?;
+
+    let cpi_accounts = system_program::Assign {
+        account_to_assign: account_info.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer);
+    system_program::assign(cpi_ctx, &crate::ID)
+}
+
+/// Checks the user's eligibility (KYC, whitelist entry, balance tiers, public
+/// sale, current phase) and returns the tier they contribute under along with
+/// the maximum total contribution allowed for them.
+pub fn resolve_tier(
+    presale: &SaleConfig,
+    registry: &Registry,
+    user: Pubkey,
+    whitelist_entry_info: &AccountInfo,
+    gating_token_account: Option<&Account<token::TokenAccount>>,
+    kyc_attestation: Option<&Account<KycAttestation>>,
+    user_limit: Option<u64>,
+) -> Result<(u8, u64)> {
+    if let Some(kyc_issuer) = registry.kyc_issuer() {
+        let kyc_attestation = kyc_attestation.ok_or(PresaleError::KycRequired)?;
+        require!(
+            kyc_attestation.issuer == kyc_issuer && kyc_attestation.user == user,
+            PresaleError::InvalidKycAttestation
+        );
+        if let Some(expires_at) = kyc_attestation.expires_at {
+            require!(
+                Clock::get()?.unix_timestamp <= expires_at,
+                PresaleError::KycAttestationExpired
+            );
+        }
+    }
+
+    let whitelist_entry = if whitelist_entry_info.data_is_empty() {
+        None
+    } else {
+        let data = whitelist_entry_info.try_borrow_data()?;
+        Some(WhitelistEntry::try_deserialize(&mut &data[..])?)
+    };
+
+    let now = Clock::get()?.unix_timestamp;
+
+    let phase = presale.current_phase(now);
+    if !presale.phases().is_empty() {
+        require!(phase.is_some(), PresaleError::NoActivePhase);
+    }
+    if phase == Some(PhaseKind::Guaranteed) {
+        require!(whitelist_entry.is_some(), PresaleError::UserNotWhitelisted);
+    }
+
+    let mut resolved = match &whitelist_entry {
+        Some(entry) => {
+            if let Some(valid_from) = entry.valid_from {
+                require!(now >= valid_from, PresaleError::WhitelistEntryNotYetValid);
+            }
+            if let Some(valid_until) = entry.valid_until {
+                require!(now <= valid_until, PresaleError::WhitelistEntryExpired);
+            }
+            Some((entry.tier, registry.tier_at(entry.tier)?.max_contribution))
+        }
+        None => None,
+    };
+
+    if let (Some(gating_mint), Some(gating_token_account)) =
+        (registry.gating_mint(), gating_token_account)
+    {
+        require!(
+            gating_token_account.mint == gating_mint && gating_token_account.owner == user,
+            PresaleError::InvalidGatingTokenAccount
+        );
+
+        if let Some(balance_tier) = registry.best_balance_tier(gating_token_account.amount) {
+            let is_better = resolved
+                .as_ref()
+                .is_none_or(|(_, tier_max)| balance_tier.1 > *tier_max);
+            if is_better {
+                resolved = Some(balance_tier);
+            }
+        }
+    }
+
+    let public_fallback = match phase {
+        Some(PhaseKind::Public) => true,
+        Some(PhaseKind::Guaranteed) => false,
+        _ => registry.public_sale.get(),
+    };
+    if resolved.is_none() && public_fallback {
+        let default_tier = registry.default_tier().ok_or(PresaleError::TierDoesNotExist)?;
+        let default_tier_max = registry.tier_at(default_tier)?.max_contribution;
+        resolved = Some((default_tier, default_tier_max));
+    }
+
+    let (tier, tier_max) = resolved.ok_or(error!(PresaleError::UserNotWhitelisted))?;
+
+    // FCFS lifts per-user limits; only the hard cap bounds contributions.
+    if phase == Some(PhaseKind::Fcfs) {
+        return Ok((tier, u64::MAX));
+    }
+
+    if let Some(user_limit) = user_limit {
+        return Ok((tier, user_limit));
+    }
+
+    Ok((tier, tier_max))
+}
+
+/// Promotes a whitelisted user to the best tier unlocked by their cumulative
+/// contribution, moving their totals to the new tier's bucket. The upgrade is
+/// skipped (not an error) when the target tier is full or over its total cap.
+pub fn upgrade_whitelist_tier(
+    presale: &mut SaleConfig,
+    registry: &Registry,
+    contribution: &mut ContributionAccount,
+    whitelist_entry_info: &AccountInfo,
+) -> Result<()> {
+    if whitelist_entry_info.data_is_empty() {
+        return Ok(());
+    }
+    let mut entry = {
+        let data = whitelist_entry_info.try_borrow_data()?;
+        WhitelistEntry::try_deserialize(&mut &data[..])?
+    };
+
+    let user = contribution.user;
+    let user_contribution = contribution.amount;
+    let current_tier_max = registry.tier_at(entry.tier).map_or(0, |tier_info| tier_info.max_contribution);
+    let (new_tier, new_tier_max) = match registry.best_upgrade_tier(user_contribution) {
+        Some(upgrade) if upgrade.1 > current_tier_max && upgrade.0 != entry.tier => upgrade,
+        _ => return Ok(()),
+    };
+
+    let new_tier_info = registry.tier_at(new_tier)?;
+    let new_tier_totals = *presale.tier_totals(new_tier)?;
+    if let Some(max_members) = new_tier_info.max_members() {
+        if new_tier_totals.member_count >= max_members {
+            return Ok(());
+        }
+    }
+    let new_tier_total = new_tier_totals
+        .total_contributions
+        .checked_add(user_contribution)
+        .ok_or(PresaleError::Overflow)?;
+    if let Some(total_cap) = new_tier_info.total_cap() {
+        if new_tier_total > total_cap {
+            return Ok(());
+        }
+    }
+
+    presale.release_tier_contribution(entry.tier, user_contribution);
+    presale.add_tier_contribution(registry, new_tier, user_contribution)?;
+    contribution.tier = new_tier;
+    presale.remove_tier_member(entry.tier);
+    let tier_full = presale.add_tier_member(registry, new_tier)?;
+
+    let from_tier = std::mem::replace(&mut entry.tier, new_tier);
+    entry.max_contribution = new_tier_max;
+    let mut data = whitelist_entry_info.try_borrow_mut_data()?;
+    entry.try_serialize(&mut &mut data[..])?;
+
+    let timestamp = Clock::get()?.unix_timestamp as u64;
+
+    emit!(TierUpgraded {
+        presale: entry.presale,
+        sequence: presale.next_sequence(),
+        user,
+        from_tier,
+        to_tier: new_tier,
+        timestamp,
+    });
+
+    if tier_full {
+        emit!(TierFull {
+            presale: entry.presale,
+            sequence: presale.next_sequence(),
+            tier: new_tier,
+            timestamp,
+        });
+    }
+
+    Ok(())
+}
+
+/// Removes `amount` of the user's `mint` contribution from their
+/// `ContributionAccount` and the sale, payment-mint, tier and contributor
+/// totals, and releases the purchased and bonus tokens bought with it.
+/// Returns the user's remaining total contribution.
+pub fn release_refund(
+    presale: &mut SaleConfig,
+    contribution: &mut ContributionAccount,
+    mint: Pubkey,
+    amount: u64,
+) -> Result<u64> {
+    require!(
+        presale.allocation_requested == 0 || presale.allocation_finalized.get(),
+        PresaleError::AllocationInProgress
+    );
+
+    let entry = contribution.mint_entry_mut(&mint)?;
+    entry.amount = entry.amount.checked_sub(amount).ok_or(PresaleError::Overflow)?;
+
+    let previous_contribution = contribution.amount;
+    let remaining_contribution = previous_contribution
+        .checked_sub(amount)
+        .ok_or(PresaleError::Overflow)?;
+    contribution.amount = remaining_contribution;
+
+    presale.total_contributions = presale
+        .total_contributions
+        .checked_sub(amount)
+        .ok_or(PresaleError::Overflow)?;
+    presale.release_payment_total(&mint, amount)?;
+    presale.release_tier_contribution(contribution.tier, amount);
+    if remaining_contribution == 0 && previous_contribution > 0 {
+        presale.contributor_count = presale.contributor_count.saturating_sub(1);
+        presale.refunded_count = presale
+            .refunded_count
+            .checked_add(1)
+            .ok_or(PresaleError::Overflow)?;
+        contribution.refunded = true;
+    }
+    if presale.is_closed.get() {
+        presale.settlement_started = true.into();
+    }
+    if !presale.refunds_restore_capacity.get() {
+        contribution.refunded_amount = contribution
+            .refunded_amount
+            .checked_add(amount)
+            .ok_or(PresaleError::Overflow)?;
+    }
+
+    // Release the sale tokens bought with the refunded portion.
+    if previous_contribution > 0 {
+        let refunded_tokens = ((contribution.purchased_tokens as u128) * (amount as u128)
+            / (previous_contribution as u128)) as u64;
+        contribution.purchased_tokens -= refunded_tokens;
+        let refunded_bonus = ((contribution.bonus_tokens as u128) * (amount as u128)
+            / (previous_contribution as u128)) as u64;
+        contribution.bonus_tokens -= refunded_bonus;
+    }
+
+    Ok(remaining_contribution)
+}
+
+/// Closes the sale at `now` and opens refunds when `refunds_allowed`. A
+/// sale that missed its soft cap has failed, so contributors can get their
+/// funds back regardless of `refunds_allowed`. Unrevealed escrows sit in
+/// the vault until the reveal phase ends, so nothing closes before then.
+pub fn close_sale(presale: &mut SaleConfig, presale_key: Pubkey, refunds_allowed: bool, now: i64) -> Result<()> {
+    require!(!presale.is_closed.get(), PresaleError::PresaleAlreadyClosed);
+    require!(!presale.in_commit_reveal(now), PresaleError::CommitRevealActive);
+
+    let soft_cap_reached = presale.total_contributions >= presale.soft_cap;
+    let refunds_allowed = refunds_allowed || !soft_cap_reached;
+
+    presale.is_closed = true.into();
+    presale.is_active = false.into();
+    presale.soft_cap_reached = soft_cap_reached.into();
+    presale.open_refunds(refunds_allowed, now)?;
+
+    emit!(PresaleClosed {
+        presale: presale_key,
+        sequence: presale.next_sequence(),
+        timestamp: now as u64,
+        refunds_allowed,
+        soft_cap_reached,
+        refund_deadline: presale.refund_deadline,
+    });
+
+    Ok(())
+}
+
+/// When the presale has a council, checks that `proposal` approves
+/// `action` with enough votes from current members and marks it executed.
+pub fn consume_council_approval(
+    presale: &SaleConfig,
+    presale_key: Pubkey,
+    proposal: Option<&mut Account<AdminProposal>>,
+    action: AdminAction,
+) -> Result<()> {
+    if presale.council().is_empty() {
+        return Ok(());
+    }
+
+    let proposal = proposal.ok_or(PresaleError::CouncilApprovalRequired)?;
+    require_keys_eq!(proposal.presale, presale_key, PresaleError::InvalidProposal);
+    require!(!proposal.executed, PresaleError::ProposalAlreadyExecuted);
+    require!(proposal.action == action, PresaleError::InvalidProposal);
+
+    let approvals = proposal
+        .approvals
+        .iter()
+        .filter(|member| presale.council().contains(member))
+        .count();
+    require!(
+        approvals >= presale.council_threshold as usize,
+        PresaleError::InsufficientApprovals
+    );
+
+    proposal.executed = true;
+
+    Ok(())
+}
+
+/// While a timelock is configured, checks that `queued_change` holds
+/// `change` for this presale and that its delay has elapsed.
+pub fn consume_timelock(
+    presale: &SaleConfig,
+    presale_key: Pubkey,
+    queued_change: Option<&Account<QueuedChange>>,
+    change: ParamChange,
+) -> Result<()> {
+    if presale.timelock_delay == 0 {
+        return Ok(());
+    }
+
+    let queued_change = queued_change.ok_or(PresaleError::ChangeNotQueued)?;
+    require_keys_eq!(queued_change.presale, presale_key, PresaleError::InvalidQueuedChange);
+    require!(queued_change.change == change, PresaleError::InvalidQueuedChange);
+    require!(
+        Clock::get()?.unix_timestamp >= queued_change.eta,
+        PresaleError::TimelockNotElapsed
+    );
+
+    Ok(())
+}
+
+/// Records a refund of `amount` in the user's `RefundTicket` PDA, creating
+/// the account on first use. Fails once the user has been fully refunded.
+pub fn record_refund_ticket<'info>(
+    ticket_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    presale: Pubkey,
+    user: Pubkey,
+    amount: u64,
+    fully_refunded: bool,
+) -> Result<()> {
+    let (expected_ticket, bump) = Pubkey::find_program_address(
+        &[b"refund", presale.as_ref(), user.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(
+        ticket_info.key(),
+        expected_ticket,
+        PresaleError::InvalidRefundTicket
+    );
+
+    let mut ticket = if ticket_info.data_is_empty() {
+        create_pda_account(
+            ticket_info,
+            payer,
+            system_program,
+            &[b"refund", presale.as_ref(), user.as_ref(), &[bump]],
+            RefundTicket::LEN,
+        )?;
+
+        RefundTicket {
+            presale,
+            user,
+            bump,
+            ..Default::default()
+        }
+    } else {
+        let data = ticket_info.try_borrow_data()?;
+        RefundTicket::try_deserialize(&mut &data[..])?
+    };
+
+    require!(!ticket.fully_refunded, PresaleError::AlreadyRefunded);
+    ticket.refunded_amount = ticket.refunded_amount.checked_add(amount).ok_or(PresaleError::Overflow)?;
+    ticket.refund_count = ticket.refund_count.checked_add(1).ok_or(PresaleError::Overflow)?;
+    ticket.fully_refunded = fully_refunded;
+    ticket.last_refund_at = Clock::get()?.unix_timestamp;
+
+    let mut data = ticket_info.try_borrow_mut_data()?;
+    ticket.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+/// Loads the user's `ContributionAccount`, creating the PDA with `payer`
+/// covering rent when this is the wallet's first contribution. New
+/// accounts take the presale's next contribution account index.
+pub fn load_contribution_account<'info>(
+    contribution_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    presale: &mut SaleConfig,
+    presale_key: Pubkey,
+    user: Pubkey,
+) -> Result<ContributionAccount> {
+    let (expected_contribution_account, bump) = Pubkey::find_program_address(
+        &[b"contribution", presale_key.as_ref(), user.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(
+        contribution_info.key(),
+        expected_contribution_account,
+        PresaleError::InvalidContributionAccount
+    );
+
+    if contribution_info.data_is_empty() {
+        create_pda_account(
+            contribution_info,
+            payer,
+            system_program,
+            &[b"contribution", presale_key.as_ref(), user.as_ref(), &[bump]],
+            ContributionAccount::LEN,
+        )?;
+
+        let index = presale.contribution_account_count;
+        presale.contribution_account_count = index.checked_add(1).ok_or(PresaleError::Overflow)?;
+        return Ok(ContributionAccount {
+            presale: presale_key,
+            user,
+            bump,
+            index,
+            ..Default::default()
+        });
+    }
+
+    let data = contribution_info.try_borrow_data()?;
+    let contribution = ContributionAccount::try_deserialize(&mut &data[..])?;
+    require!(
+        contribution.presale == presale_key && contribution.user == user,
+        PresaleError::InvalidContributionAccount
+    );
+    Ok(contribution)
+}
+
+/// Writes `contribution` back to its account.
+pub fn save_contribution_account(
+    contribution_info: &AccountInfo,
+    contribution: &ContributionAccount,
+) -> Result<()> {
+    let mut data = contribution_info.try_borrow_mut_data()?;
+    contribution.try_serialize(&mut &mut data[..])
+}
+
+/// Burns the user's `receipt_mint` receipts for `amount` (when receipts are
+/// enabled) and transfers `amount` less `fee` back from the vault; the fee
+/// stays there.
+pub fn pay_refund(
+    accounts: &Refund,
+    receipt_mint: Option<Pubkey>,
+    signer: &[&[&[u8]]],
+    amount: u64,
+    fee: u64,
+) -> Result<()> {
+    let destination_owner = accounts.user_usdt.owner;
+    require!(
+        destination_owner == accounts.user.key()
+            || accounts
+                .destination_authority
+                .as_ref()
+                .is_some_and(|authority| authority.key() == destination_owner),
+        PresaleError::InvalidRefundDestination
+    );
+
+    if let Some(receipt_mint_key) = receipt_mint {
+        let (receipt_mint, user_receipt, receipt_token_program) = match (
+            accounts.receipt_mint.as_ref(),
+            accounts.user_receipt.as_ref(),
+            accounts.receipt_token_program.as_ref(),
+        ) {
+            (Some(mint), Some(account), Some(program)) => (mint, account, program),
+            _ => return err!(PresaleError::InvalidReceiptAccount),
+        };
+        require!(
+            receipt_mint.key() == receipt_mint_key && user_receipt.mint == receipt_mint_key,
+            PresaleError::InvalidReceiptAccount
+        );
+
+        let cpi_accounts = token::Burn {
+            mint: receipt_mint.to_account_info(),
+            from: user_receipt.to_account_info(),
+            authority: accounts.user.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(receipt_token_program.to_account_info(), cpi_accounts);
+        token::burn(cpi_ctx, amount)?;
+    }
+
+    let cpi_accounts = token_interface::TransferChecked {
+        from: accounts.presale_usdt.to_account_info(),
+        mint: accounts.payment_mint.to_account_info(),
+        to: accounts.user_usdt.to_account_info(),
+        authority: accounts.vault_authority.to_account_info(),
+    };
+    let cpi_program = accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+    token_interface::transfer_checked(cpi_ctx, amount - fee, accounts.payment_mint.decimals)
+}
+
+/// Enforces the hard cap and per-user limits for a contribution of `amount`
+/// (in USDT units) paid in `mint` and records it in the user's
+/// `ContributionAccount` and the sale totals. Returns the sale tokens
+/// purchased at `tier`'s price.
+#[allow(clippy::too_many_arguments)]
+pub fn record_contribution(
+    presale: &mut SaleConfig,
+    registry: &Registry,
+    presale_key: Pubkey,
+    contribution: &mut ContributionAccount,
+    mint: Pubkey,
+    amount: u64,
+    tier: u8,
+    tier_max: u64,
+) -> Result<u64> {
+    let now = Clock::get()?.unix_timestamp;
+    if contribution.last_contribution_at != 0 {
+        require!(
+            now >= contribution.last_contribution_at.saturating_add(presale.contribution_cooldown),
+            PresaleError::CooldownActive
+        );
+    }
+    contribution.last_contribution_at = now;
+    if contribution.first_contribution_at == 0 {
+        contribution.first_contribution_at = now;
+    }
+
+    // Oversubscribed sales accept past the hard cap and scale down at close.
+    if !presale.oversubscription.get() {
+        require!(
+            presale.total_contributions.checked_add(amount).ok_or(PresaleError::Overflow)? <= presale.hard_cap,
+            PresaleError::ExceedsHardCap
+        );
+    }
+
+    let previous_contribution = contribution.amount;
+    let user_contribution = previous_contribution.checked_add(amount).ok_or(PresaleError::Overflow)?;
+
+    require!(
+        user_contribution >= presale.min_contribution,
+        PresaleError::BelowMinContribution
+    );
+    require!(
+        user_contribution.checked_add(contribution.refunded_amount).ok_or(PresaleError::Overflow)? <= tier_max,
+        PresaleError::AboveMaxContribution
+    );
+
+    // Price at the marginal rate before this contribution moves the curve.
+    let tokens_purchased = presale.tokens_for(registry.tier_at(tier)?, amount)?;
+    let bonus = presale.early_bird_bonus(tokens_purchased, now);
+
+    if previous_contribution == 0 {
+        presale.contributor_count = presale
+            .contributor_count
+            .checked_add(1)
+            .ok_or(PresaleError::Overflow)?;
+    }
+    contribution.amount = user_contribution;
+    contribution.tier = tier;
+    contribution.refunded = false;
+    let entry = contribution.mint_entry_mut(&mint)?;
+    entry.amount = entry.amount.checked_add(amount).ok_or(PresaleError::Overflow)?;
+    presale.total_contributions = presale
+        .total_contributions
+        .checked_add(amount)
+        .ok_or(PresaleError::Overflow)?;
+    presale.add_payment_total(&mint, amount)?;
+
+    presale.add_tier_contribution(registry, tier, amount)?;
+
+    contribution.purchased_tokens = contribution
+        .purchased_tokens
+        .checked_add(tokens_purchased)
+        .ok_or(PresaleError::Overflow)?;
+
+    if bonus > 0 {
+        contribution.bonus_tokens = contribution
+            .bonus_tokens
+            .checked_add(bonus)
+            .ok_or(PresaleError::Overflow)?;
+
+        emit!(EarlyBirdBonus {
+            presale: presale_key,
+            sequence: presale.next_sequence(),
+            contributor: contribution.user,
+            bonus_tokens: bonus,
+            timestamp: now as u64,
+        });
+    }
+
+    Ok(tokens_purchased)
+}
+
+/// The message a user signs to authorize `contribute_with_permit`:
+/// `presale || payment_mint || amount || nonce || expiry`, integers little-endian.
+pub fn permit_message(
+    presale: &Pubkey,
+    payment_mint: &Pubkey,
+    amount: u64,
+    nonce: u64,
+    expiry: i64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(32 + 32 + 8 + 8 + 8);
+    message.extend_from_slice(presale.as_ref());
+    message.extend_from_slice(payment_mint.as_ref());
+    message.extend_from_slice(&amount.to_le_bytes());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message.extend_from_slice(&expiry.to_le_bytes());
+    message
+}
+
+/// Mixes the revealed lottery `seed` with the newest entry of the
+/// SlotHashes sysvar data `slot_hashes`.
+pub fn lottery_entropy(seed: &[u8; 32], slot_hashes: &[u8]) -> Result<[u8; 32]> {
+    // Layout: entry count as a u64, then (slot: u64, hash: [u8; 32])
+    // entries, newest first.
+    let count = slot_hashes.get(..8).ok_or(PresaleError::InvalidSlotHashes)?;
+    require!(count != [0; 8], PresaleError::InvalidSlotHashes);
+    let recent_hash = slot_hashes.get(16..48).ok_or(PresaleError::InvalidSlotHashes)?;
+    Ok(hashv(&[seed, recent_hash]).to_bytes())
+}
+
+/// Checks that the instruction preceding the current one is an Ed25519
+/// program signature check of `message` by `signer`, with the public key and
+/// message embedded in that instruction's own data.
+pub fn verify_permit_signature(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current_index = ix_sysvar::load_current_index_checked(instructions)?;
+    require!(current_index > 0, PresaleError::InvalidPermitSignature);
+    let ed25519_ix = ix_sysvar::load_instruction_at_checked(current_index as usize - 1, instructions)?;
+
+    require!(
+        ed25519_ix.program_id == ed25519_program::ID && ed25519_ix.accounts.is_empty(),
+        PresaleError::InvalidPermitSignature
+    );
+
+    // Layout: [num_signatures: u8, padding: u8] followed by one 14-byte
+    // offsets struct per signature.
+    let data = &ed25519_ix.data;
+    require!(data.len() >= 16 && data[0] == 1, PresaleError::InvalidPermitSignature);
+
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let signature_ix_index = read_u16(4);
+    let public_key_offset = read_u16(6) as usize;
+    let public_key_ix_index = read_u16(8);
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    let message_ix_index = read_u16(14);
+
+    // u16::MAX refers to the Ed25519 instruction itself.
+    require!(
+        signature_ix_index == u16::MAX
+            && public_key_ix_index == u16::MAX
+            && message_ix_index == u16::MAX,
+        PresaleError::InvalidPermitSignature
+    );
+
+    let signed_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(PresaleError::InvalidPermitSignature)?;
+    let signed_message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(PresaleError::InvalidPermitSignature)?;
+
+    require!(
+        signed_key == signer.as_ref() && signed_message == message,
+        PresaleError::InvalidPermitSignature
+    );
+
+    Ok(())
+}
+
+/// Mints receipt tokens 1:1 with `amount` to the user's receipt account when
+/// the presale has receipts enabled.
+pub fn mint_receipts<'info>(
+    presale: &AccountLoader<'info, SaleConfig>,
+    receipt_mint: Option<&Account<'info, token::Mint>>,
+    user_receipt: Option<&Account<'info, token::TokenAccount>>,
+    receipt_token_program: Option<&Program<'info, token::Token>>,
+    user: Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let (receipt_mint_key, creator, sale_id, bump) = {
+        let presale = presale.load()?;
+        match presale.receipt_mint() {
+            Some(receipt_mint_key) => (receipt_mint_key, presale.creator, presale.sale_id, presale.bump),
+            None => return Ok(()),
+        }
+    };
+    let (receipt_mint, user_receipt, receipt_token_program) =
+        match (receipt_mint, user_receipt, receipt_token_program) {
+            (Some(mint), Some(account), Some(program)) => (mint, account, program),
+            _ => return err!(PresaleError::InvalidReceiptAccount),
+        };
+    require!(
+        receipt_mint.key() == receipt_mint_key
+            && user_receipt.mint == receipt_mint_key
+            && user_receipt.owner == user,
+        PresaleError::InvalidReceiptAccount
+    );
+
+    let sale_id = sale_id.to_le_bytes();
+    let seeds = &[b"presale".as_ref(), creator.as_ref(), sale_id.as_ref(), &[bump]];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = token::MintTo {
+        mint: receipt_mint.to_account_info(),
+        to: user_receipt.to_account_info(),
+        authority: presale.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(receipt_token_program.to_account_info(), cpi_accounts, signer);
+    token::mint_to(cpi_ctx, amount)
+}
+
+/// Creates the vault authority's associated token account for `mint`, which
+/// holds every contribution paid in that mint, and returns its address. Uses the
+/// idempotent instruction so an ATA created ahead of time by someone else
+/// cannot block the sale.
+pub fn create_payment_vault<'info>(
+    vault: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    vault_authority: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    associated_token_program: &AccountInfo<'info>,
+) -> Result<Pubkey> {
+    let cpi_accounts = associated_token::Create {
+        payer: payer.clone(),
+        associated_token: vault.clone(),
+        authority: vault_authority.clone(),
+        mint: mint.clone(),
+        system_program: system_program.clone(),
+        token_program: token_program.clone(),
+    };
+    associated_token::create_idempotent(CpiContext::new(associated_token_program.clone(), cpi_accounts))?;
+
+    Ok(associated_token::get_associated_token_address_with_program_id(
+        vault_authority.key,
+        mint.key,
+        token_program.key,
+    ))
+}
+
+/// Returns the creator's next sale id and advances their `SaleCounter`,
+/// creating the counter on their first presale.
+pub fn take_sale_id<'info>(
+    counter_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    creator: Pubkey,
+) -> Result<u64> {
+    let (expected_counter, bump) = Pubkey::find_program_address(
+        &[b"sale_counter", creator.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(
+        counter_info.key(),
+        expected_counter,
+        PresaleError::InvalidSaleCounter
+    );
+
+    let mut counter = if counter_info.data_is_empty() {
+        create_pda_account(
+            counter_info,
+            payer,
+            system_program,
+            &[b"sale_counter", creator.as_ref(), &[bump]],
+            SaleCounter::LEN,
+        )?;
+
+        SaleCounter {
+            creator,
+            sale_count: 0,
+            bump,
+        }
+    } else {
+        let data = counter_info.try_borrow_data()?;
+        SaleCounter::try_deserialize(&mut &data[..])?
+    };
+
+    let sale_id = counter.sale_count;
+    counter.sale_count = sale_id.checked_add(1).ok_or(PresaleError::Overflow)?;
+
+    let mut data = counter_info.try_borrow_mut_data()?;
+    counter.try_serialize(&mut &mut data[..])?;
+
+    Ok(sale_id)
+}
+
+/// Appends `entry` to the presale's `PresaleSnapshots` PDA, creating it on
+/// first use, and returns the snapshot number.
+pub fn record_snapshot<'info>(
+    snapshots_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    presale: Pubkey,
+    entry: SnapshotEntry,
+) -> Result<u64> {
+    let (expected_snapshots, bump) = Pubkey::find_program_address(
+        &[b"snapshots", presale.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(
+        snapshots_info.key(),
+        expected_snapshots,
+        PresaleError::InvalidSnapshotAccount
+    );
+
+    let mut snapshots = if snapshots_info.data_is_empty() {
+        create_pda_account(
+            snapshots_info,
+            payer,
+            system_program,
+            &[b"snapshots", presale.as_ref(), &[bump]],
+            PresaleSnapshots::LEN,
+        )?;
+
+        PresaleSnapshots {
+            presale,
+            bump,
+            ..Default::default()
+        }
+    } else {
+        let data = snapshots_info.try_borrow_data()?;
+        PresaleSnapshots::try_deserialize(&mut &data[..])?
+    };
+
+    if let Some(last) = snapshots.last_timestamp() {
+        require!(
+            entry.timestamp >= last + MIN_SNAPSHOT_INTERVAL,
+            PresaleError::SnapshotTooSoon
+        );
+    }
+    let index = snapshots.push(entry)?;
+
+    let mut data = snapshots_info.try_borrow_mut_data()?;
+    snapshots.try_serialize(&mut &mut data[..])?;
+
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A one-mint sale holding `amount` from a single contributor in tier 0.
+    fn sale(mint: Pubkey, amount: u64) -> (Box<SaleConfig>, ContributionAccount) {
+        let mut presale: Box<SaleConfig> = bytemuck::zeroed_box();
+        presale.add_payment_mint(mint, Pubkey::new_unique()).unwrap();
+        presale.add_payment_total(&mint, amount).unwrap();
+        presale.total_contributions = amount;
+        presale.tier_totals_mut(0).unwrap().total_contributions = amount;
+        presale.contributor_count = 1;
+        let mut contribution = ContributionAccount {
+            amount,
+            purchased_tokens: 40 * amount,
+            bonus_tokens: 4 * amount,
+            ..ContributionAccount::default()
+        };
+        contribution.mint_contributions[0] = MintContribution { mint, amount, excess: 0 };
+        (presale, contribution)
+    }
+
+    #[test]
+    fn partial_refund_releases_a_proportional_share() {
+        let mint = Pubkey::new_unique();
+        let (mut presale, mut contribution) = sale(mint, 1_000);
+
+        let remaining = release_refund(&mut presale, &mut contribution, mint, 250).unwrap();
+
+        assert_eq!(remaining, 750);
+        assert_eq!(contribution.amount, 750);
+        assert_eq!(contribution.mint_contribution(&mint), 750);
+        assert_eq!(contribution.purchased_tokens, 30_000);
+        assert_eq!(contribution.bonus_tokens, 3_000);
+        assert_eq!(contribution.refunded_amount, 250);
+        assert!(!contribution.refunded);
+        assert_eq!(presale.total_contributions, 750);
+        assert_eq!(presale.payment_totals[0], 750);
+        assert_eq!(presale.tier_totals(0).unwrap().total_contributions, 750);
+        assert_eq!(presale.contributor_count, 1);
+        assert_eq!(presale.refunded_count, 0);
+        assert!(!presale.settlement_started.get());
+    }
+
+    #[test]
+    fn partial_refund_rounds_released_tokens_down() {
+        let mint = Pubkey::new_unique();
+        let (mut presale, mut contribution) = sale(mint, 3);
+        contribution.purchased_tokens = 100;
+        contribution.bonus_tokens = 10;
+
+        release_refund(&mut presale, &mut contribution, mint, 1).unwrap();
+
+        assert_eq!(contribution.purchased_tokens, 67);
+        assert_eq!(contribution.bonus_tokens, 7);
+    }
+
+    #[test]
+    fn full_refund_after_close_counts_the_refunded_contributor() {
+        let mint = Pubkey::new_unique();
+        let (mut presale, mut contribution) = sale(mint, 1_000);
+        presale.is_closed = true.into();
+        presale.refunds_restore_capacity = true.into();
+
+        let remaining = release_refund(&mut presale, &mut contribution, mint, 1_000).unwrap();
+
+        assert_eq!(remaining, 0);
+        assert_eq!(contribution.purchased_tokens, 0);
+        assert_eq!(contribution.bonus_tokens, 0);
+        assert_eq!(contribution.refunded_amount, 0);
+        assert!(contribution.refunded);
+        assert_eq!(presale.total_contributions, 0);
+        assert_eq!(presale.payment_totals[0], 0);
+        assert_eq!(presale.contributor_count, 0);
+        assert_eq!(presale.refunded_count, 1);
+        assert!(presale.settlement_started.get());
+    }
+
+    #[test]
+    fn refund_above_the_paid_amount_fails() {
+        let mint = Pubkey::new_unique();
+        let (mut presale, mut contribution) = sale(mint, 1_000);
+
+        let err = release_refund(&mut presale, &mut contribution, mint, 1_001).unwrap_err();
+
+        assert_eq!(err, PresaleError::Overflow.into());
+    }
+
+    #[test]
+    fn refund_waits_for_allocation_to_finish() {
+        let mint = Pubkey::new_unique();
+        let (mut presale, mut contribution) = sale(mint, 1_000);
+        presale.allocation_requested = 1_000;
+
+        let err = release_refund(&mut presale, &mut contribution, mint, 100).unwrap_err();
+
+        assert_eq!(err, PresaleError::AllocationInProgress.into());
+    }
+
+    #[test]
+    fn closing_below_the_soft_cap_forces_refunds() {
+        let (mut presale, _) = sale(Pubkey::new_unique(), 1_000);
+        presale.soft_cap = 2_000;
+        presale.refund_period = 100;
+
+        close_sale(&mut presale, Pubkey::new_unique(), false, 50).unwrap();
+
+        assert!(presale.is_closed.get());
+        assert!(!presale.soft_cap_reached.get());
+        assert!(presale.refunds_allowed.get());
+        assert_eq!(presale.refund_deadline, 150);
+    }
+
+    #[test]
+    fn sales_do_not_close_during_commit_reveal() {
+        let (mut presale, _) = sale(Pubkey::new_unique(), 1_000);
+        presale.commit_end = 100;
+        presale.reveal_end = 200;
+
+        let err = close_sale(&mut presale, Pubkey::new_unique(), true, 200).unwrap_err();
+        assert_eq!(err, PresaleError::CommitRevealActive.into());
+
+        close_sale(&mut presale, Pubkey::new_unique(), true, 201).unwrap();
+        assert!(presale.soft_cap_reached.get());
+        let err = close_sale(&mut presale, Pubkey::new_unique(), true, 202).unwrap_err();
+        assert_eq!(err, PresaleError::PresaleAlreadyClosed.into());
+    }
+
+    /// Ed25519 program data for one signature by `signer` over `message`,
+    /// with every offset pointing into the instruction itself.
+    fn ed25519_data(signer: &Pubkey, message: &[u8]) -> Vec<u8> {
+        let (public_key_offset, signature_offset, message_offset) = (16u16, 48u16, 112u16);
+        let mut data = vec![1, 0];
+        for field in [
+            signature_offset,
+            u16::MAX,
+            public_key_offset,
+            u16::MAX,
+            message_offset,
+            message.len() as u16,
+            u16::MAX,
+        ] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(signer.as_ref());
+        data.extend_from_slice(&[0; 64]);
+        data.extend_from_slice(message);
+        data
+    }
+
+    /// Runs `verify_permit_signature` as the last of `instructions`.
+    fn verify_after(instructions: &[(Pubkey, Vec<u8>)], signer: &Pubkey, message: &[u8]) -> Result<()> {
+        let borrowed: Vec<ix_sysvar::BorrowedInstruction> = instructions
+            .iter()
+            .map(|(program_id, data)| ix_sysvar::BorrowedInstruction {
+                program_id,
+                accounts: vec![],
+                data,
+            })
+            .collect();
+        let mut data = ix_sysvar::construct_instructions_data(&borrowed);
+        ix_sysvar::store_current_index(&mut data, instructions.len() as u16 - 1);
+
+        let key = ix_sysvar::ID;
+        let owner = Pubkey::default();
+        let mut lamports = 0;
+        let info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        verify_permit_signature(&info, signer, message)
+    }
+
+    #[test]
+    fn permits_need_the_users_ed25519_check_right_before() {
+        let (presale, mint, user) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let message = permit_message(&presale, &mint, 1_000, 0, 100);
+        let contribute = (crate::ID, vec![]);
+
+        verify_after(&[(ed25519_program::ID, ed25519_data(&user, &message)), contribute.clone()], &user, &message)
+            .unwrap();
+
+        let err = verify_after(std::slice::from_ref(&contribute), &user, &message).unwrap_err();
+        assert_eq!(err, PresaleError::InvalidPermitSignature.into());
+        let err = verify_after(&[(crate::ID, ed25519_data(&user, &message)), contribute], &user, &message)
+            .unwrap_err();
+        assert_eq!(err, PresaleError::InvalidPermitSignature.into());
+    }
+
+    #[test]
+    fn permits_reject_other_signers_and_terms() {
+        let (presale, mint, user) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let message = permit_message(&presale, &mint, 1_000, 0, 100);
+        let contribute = (crate::ID, vec![]);
+        let signed_by = |signer: &Pubkey, message: &[u8]| {
+            [(ed25519_program::ID, ed25519_data(signer, message)), contribute.clone()]
+        };
+
+        let err = verify_after(&signed_by(&Pubkey::new_unique(), &message), &user, &message).unwrap_err();
+        assert_eq!(err, PresaleError::InvalidPermitSignature.into());
+        for other in [
+            permit_message(&presale, &mint, 2_000, 0, 100),
+            permit_message(&presale, &mint, 1_000, 1, 100),
+            permit_message(&presale, &mint, 1_000, 0, 200),
+            permit_message(&Pubkey::new_unique(), &mint, 1_000, 0, 100),
+        ] {
+            let err = verify_after(&signed_by(&user, &other), &user, &message).unwrap_err();
+            assert_eq!(err, PresaleError::InvalidPermitSignature.into());
+        }
+
+        // Offsets into another instruction would let it supply the key.
+        let mut data = ed25519_data(&user, &message);
+        data[8..10].copy_from_slice(&1u16.to_le_bytes());
+        let err = verify_after(&[(ed25519_program::ID, data), contribute], &user, &message).unwrap_err();
+        assert_eq!(err, PresaleError::InvalidPermitSignature.into());
+    }
+
+    /// SlotHashes sysvar data holding `hashes`, newest first.
+    fn slot_hashes(hashes: &[[u8; 32]]) -> Vec<u8> {
+        let mut data = (hashes.len() as u64).to_le_bytes().to_vec();
+        for (age, hash) in hashes.iter().enumerate() {
+            data.extend_from_slice(&(1_000 - age as u64).to_le_bytes());
+            data.extend_from_slice(hash);
+        }
+        data
+    }
+
+    #[test]
+    fn lottery_entropy_mixes_in_the_latest_slot_hash() {
+        let seed = [7; 32];
+        let drawn = lottery_entropy(&seed, &slot_hashes(&[[1; 32], [2; 32]])).unwrap();
+
+        assert_ne!(drawn, seed);
+        assert_eq!(drawn, lottery_entropy(&seed, &slot_hashes(&[[1; 32], [3; 32]])).unwrap());
+        assert_ne!(drawn, lottery_entropy(&seed, &slot_hashes(&[[2; 32], [1; 32]])).unwrap());
+        assert_ne!(drawn, lottery_entropy(&[8; 32], &slot_hashes(&[[1; 32], [2; 32]])).unwrap());
+
+        for data in [vec![], slot_hashes(&[]), slot_hashes(&[[1; 32]])[..40].to_vec()] {
+            let err = lottery_entropy(&seed, &data).unwrap_err();
+            assert_eq!(err, PresaleError::InvalidSlotHashes.into());
+        }
+    }
+}
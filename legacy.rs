@@ -0,0 +1,1188 @@
+//! Layouts the sale state had while it was a single `Presale` account.
+//! `migrate` decodes an old account from its raw bytes with these and
+//! rewrites it as a `SaleConfig` plus a `Registry`.
+//!
+//! Versions 0 and 1 were seeded by `[b"presale", creator]`, an address no
+//! instruction has accepted since version 2 added the sale id, so they have
+//! no layout here and `migrate` rejects them.
+
+use anchor_lang::prelude::*;
+use std::collections::BTreeMap;
+use crate::error::PresaleError;
+use crate::pricing::{PodPricingCurve, PricingCurve};
+use crate::state::*;
+use crate::{
+    MAX_ADMIN_LOG_ENTRIES, MAX_COUNCIL_MEMBERS, MAX_PAYMENT_MINTS, MAX_PAYOUT_RECIPIENTS,
+    MAX_PRESALE_DESCRIPTION_LENGTH, MAX_PRESALE_NAME_LENGTH, MAX_PRESALE_SYMBOL_LENGTH,
+    MAX_RECEIPT_URI_LENGTH, MAX_SALE_PHASES, MAX_TIERS, MAX_TIER_NAME_LENGTH,
+};
+
+/// Discriminator of the `Presale` account, which every layout up to
+/// version 4 was stored under.
+pub const PRESALE_DISCRIMINATOR: [u8; 8] = [38, 215, 222, 14, 115, 220, 52, 168];
+
+/// A presale account in one of the layouts before version 5.
+pub enum LegacyPresale {
+    V2(Box<PresaleV2>),
+    V3(Box<PresaleV3>),
+    V4(Box<PresaleV4>),
+}
+
+impl LegacyPresale {
+    /// Decodes the account data after the discriminator. Version 4 accounts
+    /// have exactly its size; anything else is Borsh, whose version sits
+    /// after every variable-length field. Each Borsh layout is tried from
+    /// the newest and only taken if it reads back its own version.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() == PresaleV4::LEN - 8 {
+            let mut presale: Box<PresaleV4> = bytemuck::zeroed_box();
+            bytemuck::bytes_of_mut(presale.as_mut()).copy_from_slice(data);
+            return Ok(Self::V4(presale));
+        }
+        if let Ok(presale) = PresaleV3::deserialize(&mut &data[..]) {
+            if presale.version == 3 {
+                return Ok(Self::V3(Box::new(presale)));
+            }
+        }
+        if let Ok(presale) = PresaleV2::deserialize(&mut &data[..]) {
+            if presale.version == 2 {
+                return Ok(Self::V2(Box::new(presale)));
+            }
+        }
+        err!(PresaleError::UnsupportedLayout)
+    }
+
+    pub fn version(&self) -> u8 {
+        match self {
+            Self::V2(presale) => presale.version,
+            Self::V3(presale) => presale.version,
+            Self::V4(presale) => presale.version,
+        }
+    }
+
+    /// Writes the account's contents into `presale` and `registry`.
+    pub fn migrate(&self, presale: &mut SaleConfig, registry: &mut Registry) -> Result<()> {
+        match self {
+            Self::V2(legacy) => legacy.migrate(presale, registry)?,
+            Self::V3(legacy) => legacy.migrate(presale, registry),
+            Self::V4(legacy) => legacy.migrate(presale, registry),
+        }
+        Ok(())
+    }
+}
+
+/// Version 2: the Borsh layout that still kept every per-user ledger in
+/// the presale itself.
+#[derive(AnchorSerialize, AnchorDeserialize, Default)]
+pub struct PresaleV2 {
+    pub is_initialized: bool,
+    pub owner: Pubkey,
+    pub config: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub description: String,
+    pub creator: Pubkey,
+    pub sale_id: u64,
+    pub bump: u8,
+    pub sol_vault_bump: u8,
+    pub vault_authority_bump: u8,
+    pub pending_owner: Option<Pubkey>,
+    pub operator: Option<Pubkey>,
+    pub treasurer: Option<Pubkey>,
+    pub whitelist_frozen: bool,
+    pub council: Vec<Pubkey>,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub timelock_delay: i64,
+    pub change_count: u64,
+    pub payment_mints: Vec<Pubkey>,
+    pub payment_vaults: Vec<Pubkey>,
+    pub min_contribution: u64,
+    pub contribution_increment: u64,
+    pub dust_threshold: u64,
+    pub hard_cap: u64,
+    pub soft_cap: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub phases: Vec<SalePhaseV3>,
+    pub total_contributions: u64,
+    pub is_active: bool,
+    pub is_closed: bool,
+    pub is_cancelled: bool,
+    pub refunds_allowed: bool,
+    pub refund_period: i64,
+    pub refund_deadline: i64,
+    pub paused: bool,
+    pub partial_fill: bool,
+    pub oversubscription: bool,
+    pub allocation_finalized: bool,
+    pub partial_refunds_enabled: bool,
+    pub settlement_started: bool,
+    pub refund_fee_bps: u16,
+    pub platform_fee_bps: u16,
+    pub platform_fee_recipient: Pubkey,
+    pub guardian: Option<Pubkey>,
+    pub emergency: bool,
+    pub emergency_withdraw_at: i64,
+    pub emergency_withdraw_destination: Pubkey,
+    pub refunds_restore_capacity: bool,
+    pub treasury: Option<Pubkey>,
+    pub payout_recipients: Vec<PayoutRecipientV3>,
+    pub public_sale: bool,
+    pub default_tier: Option<u8>,
+    pub gating_mint: Option<Pubkey>,
+    pub balance_tiers: Vec<BalanceTierV3>,
+    pub upgrade_thresholds: Vec<UpgradeThresholdV3>,
+    pub pass_merkle_tree: Option<Pubkey>,
+    pub kyc_issuer: Option<Pubkey>,
+    pub receipt_mint: Option<Pubkey>,
+    pub receipt_nft_uri: Option<String>,
+    pub commit_end: i64,
+    pub reveal_end: i64,
+    pub lottery_ticket_amount: u64,
+    pub lottery_seed_hash: [u8; 32],
+    pub lottery_entrants: Vec<Pubkey>,
+    pub lottery_winner_count: u32,
+    pub lottery_drawn: bool,
+    pub lottery_unsettled: u32,
+    pub sol_usdt_rate: u64,
+    pub sol_price_feed: Option<Pubkey>,
+    pub max_price_staleness: u64,
+    pub max_price_confidence_bps: u16,
+    pub total_sol_contributions: u64,
+    pub sol_contributions: BTreeMap<Pubkey, u64>,
+    pub mint_contributions: BTreeMap<(Pubkey, Pubkey), u64>,
+    pub contribution_cooldown: i64,
+    pub last_contribution_at: BTreeMap<Pubkey, i64>,
+    pub permit_nonces: BTreeMap<Pubkey, u64>,
+    pub tiers: [TierInfoV3; MAX_TIERS],
+    pub early_bird_bonus_bps: u16,
+    pub early_bird_cutoff: i64,
+    pub early_bird_fill_bps: u16,
+    pub pricing_curve: Option<PricingCurve>,
+    pub contributions: BTreeMap<Pubkey, u64>,
+    pub purchased_tokens: BTreeMap<Pubkey, u64>,
+    pub bonus_tokens: BTreeMap<Pubkey, u64>,
+    pub accepted_contributions: BTreeMap<Pubkey, u64>,
+    pub excess_refunds: BTreeMap<(Pubkey, Pubkey), u64>,
+    pub contributor_count: u32,
+    pub refunded_count: u32,
+    pub contribution_tiers: BTreeMap<Pubkey, u8>,
+    pub refunded_amounts: BTreeMap<Pubkey, u64>,
+    pub user_limits: BTreeMap<Pubkey, u64>,
+    pub admin_log: Vec<AdminLogEntryV3>,
+    pub admin_log_count: u64,
+    pub sequence: u64,
+    pub version: u8,
+}
+
+impl PresaleV2 {
+    /// Whether any wallet has an entry in the presale's own ledgers, which
+    /// version 3 moved into `ContributionAccount` PDAs.
+    pub fn has_user_entries(&self) -> bool {
+        !self.lottery_entrants.is_empty()
+            || !self.sol_contributions.is_empty()
+            || !self.mint_contributions.is_empty()
+            || !self.last_contribution_at.is_empty()
+            || !self.permit_nonces.is_empty()
+            || !self.contributions.is_empty()
+            || !self.purchased_tokens.is_empty()
+            || !self.bonus_tokens.is_empty()
+            || !self.accepted_contributions.is_empty()
+            || !self.excess_refunds.is_empty()
+            || !self.contribution_tiers.is_empty()
+            || !self.refunded_amounts.is_empty()
+            || !self.user_limits.is_empty()
+    }
+
+    /// Writes this account's contents into `presale` and `registry` by way
+    /// of the version 3 layout. Only a presale no wallet has an entry in
+    /// can migrate: the entries have no `ContributionAccount` to go to.
+    pub fn migrate(&self, presale: &mut SaleConfig, registry: &mut Registry) -> Result<()> {
+        require!(!self.has_user_entries(), PresaleError::ContributorsNotMigratable);
+        PresaleV3 {
+            is_initialized: self.is_initialized,
+            owner: self.owner,
+            config: self.config,
+            name: self.name.clone(),
+            symbol: self.symbol.clone(),
+            description: self.description.clone(),
+            creator: self.creator,
+            sale_id: self.sale_id,
+            bump: self.bump,
+            sol_vault_bump: self.sol_vault_bump,
+            vault_authority_bump: self.vault_authority_bump,
+            pending_owner: self.pending_owner,
+            operator: self.operator,
+            treasurer: self.treasurer,
+            whitelist_frozen: self.whitelist_frozen,
+            council: self.council.clone(),
+            council_threshold: self.council_threshold,
+            proposal_count: self.proposal_count,
+            timelock_delay: self.timelock_delay,
+            change_count: self.change_count,
+            payment_mints: self.payment_mints.clone(),
+            payment_vaults: self.payment_vaults.clone(),
+            min_contribution: self.min_contribution,
+            contribution_increment: self.contribution_increment,
+            dust_threshold: self.dust_threshold,
+            hard_cap: self.hard_cap,
+            soft_cap: self.soft_cap,
+            start_time: self.start_time,
+            end_time: self.end_time,
+            phases: self.phases.clone(),
+            total_contributions: self.total_contributions,
+            is_active: self.is_active,
+            is_closed: self.is_closed,
+            is_cancelled: self.is_cancelled,
+            refunds_allowed: self.refunds_allowed,
+            refund_period: self.refund_period,
+            refund_deadline: self.refund_deadline,
+            paused: self.paused,
+            partial_fill: self.partial_fill,
+            oversubscription: self.oversubscription,
+            allocation_finalized: self.allocation_finalized,
+            partial_refunds_enabled: self.partial_refunds_enabled,
+            settlement_started: self.settlement_started,
+            refund_fee_bps: self.refund_fee_bps,
+            platform_fee_bps: self.platform_fee_bps,
+            platform_fee_recipient: self.platform_fee_recipient,
+            guardian: self.guardian,
+            emergency: self.emergency,
+            emergency_withdraw_at: self.emergency_withdraw_at,
+            emergency_withdraw_destination: self.emergency_withdraw_destination,
+            refunds_restore_capacity: self.refunds_restore_capacity,
+            treasury: self.treasury,
+            payout_recipients: self.payout_recipients.clone(),
+            public_sale: self.public_sale,
+            default_tier: self.default_tier,
+            gating_mint: self.gating_mint,
+            balance_tiers: self.balance_tiers.clone(),
+            upgrade_thresholds: self.upgrade_thresholds.clone(),
+            pass_merkle_tree: self.pass_merkle_tree,
+            kyc_issuer: self.kyc_issuer,
+            receipt_mint: self.receipt_mint,
+            receipt_nft_uri: self.receipt_nft_uri.clone(),
+            commit_end: self.commit_end,
+            reveal_end: self.reveal_end,
+            lottery_ticket_amount: self.lottery_ticket_amount,
+            lottery_seed_hash: self.lottery_seed_hash,
+            lottery_winner_count: self.lottery_winner_count,
+            lottery_drawn: self.lottery_drawn,
+            lottery_unsettled: self.lottery_unsettled,
+            sol_usdt_rate: self.sol_usdt_rate,
+            sol_price_feed: self.sol_price_feed,
+            max_price_staleness: self.max_price_staleness,
+            max_price_confidence_bps: self.max_price_confidence_bps,
+            total_sol_contributions: self.total_sol_contributions,
+            contribution_cooldown: self.contribution_cooldown,
+            tiers: self.tiers,
+            early_bird_bonus_bps: self.early_bird_bonus_bps,
+            early_bird_cutoff: self.early_bird_cutoff,
+            early_bird_fill_bps: self.early_bird_fill_bps,
+            pricing_curve: self.pricing_curve.clone(),
+            contributor_count: self.contributor_count,
+            refunded_count: self.refunded_count,
+            admin_log: self.admin_log.clone(),
+            admin_log_count: self.admin_log_count,
+            sequence: self.sequence,
+            version: self.version,
+            ..PresaleV3::default()
+        }
+        .migrate(presale, registry);
+        Ok(())
+    }
+}
+
+/// Version 3: the Borsh layout, with every list as a `Vec` sized up to its
+/// `MAX_*` bound.
+#[derive(AnchorSerialize, AnchorDeserialize, Default)]
+pub struct PresaleV3 {
+    pub is_initialized: bool,
+    pub owner: Pubkey,
+    pub config: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub description: String,
+    pub creator: Pubkey,
+    pub sale_id: u64,
+    pub bump: u8,
+    pub sol_vault_bump: u8,
+    pub vault_authority_bump: u8,
+    pub pending_owner: Option<Pubkey>,
+    pub operator: Option<Pubkey>,
+    pub treasurer: Option<Pubkey>,
+    pub whitelist_frozen: bool,
+    pub council: Vec<Pubkey>,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub timelock_delay: i64,
+    pub change_count: u64,
+    pub payment_mints: Vec<Pubkey>,
+    pub payment_vaults: Vec<Pubkey>,
+    pub payment_totals: Vec<u64>,
+    pub min_contribution: u64,
+    pub contribution_increment: u64,
+    pub dust_threshold: u64,
+    pub hard_cap: u64,
+    pub soft_cap: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub phases: Vec<SalePhaseV3>,
+    pub total_contributions: u64,
+    pub is_active: bool,
+    pub is_closed: bool,
+    pub is_cancelled: bool,
+    pub refunds_allowed: bool,
+    pub refund_period: i64,
+    pub refund_deadline: i64,
+    pub paused: bool,
+    pub partial_fill: bool,
+    pub oversubscription: bool,
+    pub allocation_finalized: bool,
+    pub allocation_requested: u64,
+    pub allocation_accepted: u64,
+    pub allocation_cursor: u32,
+    pub contribution_account_count: u32,
+    pub unclaimed_excess: u64,
+    pub partial_refunds_enabled: bool,
+    pub settlement_started: bool,
+    pub refund_fee_bps: u16,
+    pub platform_fee_bps: u16,
+    pub platform_fee_recipient: Pubkey,
+    pub guardian: Option<Pubkey>,
+    pub emergency: bool,
+    pub emergency_withdraw_at: i64,
+    pub emergency_withdraw_destination: Pubkey,
+    pub refunds_restore_capacity: bool,
+    pub treasury: Option<Pubkey>,
+    pub payout_recipients: Vec<PayoutRecipientV3>,
+    pub public_sale: bool,
+    pub default_tier: Option<u8>,
+    pub gating_mint: Option<Pubkey>,
+    pub balance_tiers: Vec<BalanceTierV3>,
+    pub upgrade_thresholds: Vec<UpgradeThresholdV3>,
+    pub pass_merkle_tree: Option<Pubkey>,
+    pub kyc_issuer: Option<Pubkey>,
+    pub receipt_mint: Option<Pubkey>,
+    pub receipt_nft_uri: Option<String>,
+    pub commit_end: i64,
+    pub reveal_end: i64,
+    pub lottery_ticket_amount: u64,
+    pub lottery_seed_hash: [u8; 32],
+    pub lottery_ticket_count: u32,
+    pub lottery_seed: [u8; 32],
+    pub lottery_winner_count: u32,
+    pub lottery_drawn: bool,
+    pub lottery_unsettled: u32,
+    pub sol_usdt_rate: u64,
+    pub sol_price_feed: Option<Pubkey>,
+    pub max_price_staleness: u64,
+    pub max_price_confidence_bps: u16,
+    pub total_sol_contributions: u64,
+    pub contribution_cooldown: i64,
+    pub tiers: [TierInfoV3; MAX_TIERS],
+    pub early_bird_bonus_bps: u16,
+    pub early_bird_cutoff: i64,
+    pub early_bird_fill_bps: u16,
+    pub pricing_curve: Option<PricingCurve>,
+    pub contributor_count: u32,
+    pub refunded_count: u32,
+    pub admin_log: Vec<AdminLogEntryV3>,
+    pub admin_log_count: u64,
+    pub sequence: u64,
+    pub version: u8,
+}
+
+// `LegacyPresale::decode` tells a Borsh account from a zero-copy one by
+// its size.
+const _: () = assert!(PresaleV3::LEN < PresaleV4::LEN);
+
+impl PresaleV3 {
+    pub const LEN: usize =
+        8 +  // Discriminator
+        1 + // is_initialized
+        32 + // owner
+        32 + // config
+        4 + MAX_PRESALE_NAME_LENGTH + // name
+        4 + MAX_PRESALE_SYMBOL_LENGTH + // symbol
+        4 + MAX_PRESALE_DESCRIPTION_LENGTH + // description
+        32 + // creator
+        8 +  // sale_id
+        1 +  // bump
+        1 +  // sol_vault_bump
+        1 +  // vault_authority_bump
+        1 + 32 + // pending_owner
+        1 + 32 + // operator
+        1 + 32 + // treasurer
+        1 +  // whitelist_frozen
+        4 + (MAX_COUNCIL_MEMBERS * 32) + // council
+        1 +  // council_threshold
+        8 +  // proposal_count
+        8 +  // timelock_delay
+        8 +  // change_count
+        4 + (MAX_PAYMENT_MINTS * 32) + // payment_mints
+        4 + (MAX_PAYMENT_MINTS * 32) + // payment_vaults
+        4 + (MAX_PAYMENT_MINTS * 8) + // payment_totals
+        8 +  // min_contribution
+        8 +  // contribution_increment
+        8 +  // dust_threshold
+        8 +  // hard_cap
+        8 +  // soft_cap
+        8 +  // start_time
+        8 +  // end_time
+        4 + (MAX_SALE_PHASES * SalePhaseV3::LEN) + // phases
+        8 +  // total_contributions
+        1 +  // is_active
+        1 +  // is_closed
+        1 +  // is_cancelled
+        1 +  // refunds_allowed
+        8 +  // refund_period
+        8 +  // refund_deadline
+        1 +  // paused
+        1 +  // partial_fill
+        1 +  // oversubscription
+        1 +  // allocation_finalized
+        8 +  // allocation_requested
+        8 +  // allocation_accepted
+        4 +  // allocation_cursor
+        4 +  // contribution_account_count
+        8 +  // unclaimed_excess
+        1 +  // partial_refunds_enabled
+        1 +  // settlement_started
+        2 +  // refund_fee_bps
+        2 +  // platform_fee_bps
+        32 + // platform_fee_recipient
+        1 + 32 + // guardian
+        1 +  // emergency
+        8 +  // emergency_withdraw_at
+        32 + // emergency_withdraw_destination
+        1 +  // refunds_restore_capacity
+        1 + 32 + // treasury
+        4 + (MAX_PAYOUT_RECIPIENTS * PayoutRecipientV3::LEN) + // payout_recipients
+        1 +  // public_sale
+        1 + 1 + // default_tier
+        1 + 32 + // gating_mint
+        4 + (MAX_TIERS * BalanceTierV3::LEN) + // balance_tiers
+        4 + (MAX_TIERS * UpgradeThresholdV3::LEN) + // upgrade_thresholds
+        1 + 32 + // pass_merkle_tree
+        1 + 32 + // kyc_issuer
+        1 + 32 + // receipt_mint
+        1 + 4 + MAX_RECEIPT_URI_LENGTH + // receipt_nft_uri
+        8 +  // commit_end
+        8 +  // reveal_end
+        8 +  // lottery_ticket_amount
+        32 + // lottery_seed_hash
+        4 +  // lottery_ticket_count
+        32 + // lottery_seed
+        4 +  // lottery_winner_count
+        1 +  // lottery_drawn
+        4 +  // lottery_unsettled
+        8 +  // sol_usdt_rate
+        1 + 32 + // sol_price_feed
+        8 +  // max_price_staleness
+        2 +  // max_price_confidence_bps
+        8 +  // total_sol_contributions
+        8 +  // contribution_cooldown
+        (MAX_TIERS * TierInfoV3::LEN) + // tiers
+        2 +  // early_bird_bonus_bps
+        8 +  // early_bird_cutoff
+        2 +  // early_bird_fill_bps
+        1 + PricingCurve::LEN + // pricing_curve
+        4 +  // contributor_count
+        4 +  // refunded_count
+        4 + (MAX_ADMIN_LOG_ENTRIES * AdminLogEntryV3::LEN) + // admin_log
+        8 +  // admin_log_count
+        8 +  // sequence
+        1;   // version
+
+    /// Writes this account's contents into `presale` and `registry`.
+    /// Strings and lists fit, as they were already bounded by the same
+    /// limits.
+    pub fn migrate(&self, presale: &mut SaleConfig, registry: &mut Registry) {
+        presale.is_initialized = self.is_initialized.into();
+        presale.owner = self.owner;
+        presale.config = self.config;
+        presale.set_metadata(&self.name, &self.symbol, &self.description);
+        presale.creator = self.creator;
+        presale.sale_id = self.sale_id;
+        presale.bump = self.bump;
+        presale.sol_vault_bump = self.sol_vault_bump;
+        presale.vault_authority_bump = self.vault_authority_bump;
+        presale.pending_owner = self.pending_owner.unwrap_or_default();
+        presale.operator = self.operator.unwrap_or_default();
+        presale.treasurer = self.treasurer.unwrap_or_default();
+        registry.whitelist_frozen = self.whitelist_frozen.into();
+        presale.set_council(&self.council);
+        presale.council_threshold = self.council_threshold;
+        presale.proposal_count = self.proposal_count;
+        presale.timelock_delay = self.timelock_delay;
+        presale.change_count = self.change_count;
+        for (index, mint) in self.payment_mints.iter().enumerate() {
+            presale.payment_mints[index] = *mint;
+            presale.payment_vaults[index] = self.payment_vaults[index];
+            presale.payment_totals[index] = self.payment_totals.get(index).copied().unwrap_or_default();
+        }
+        presale.payment_mint_count = self.payment_mints.len() as u8;
+        presale.min_contribution = self.min_contribution;
+        presale.contribution_increment = self.contribution_increment;
+        presale.dust_threshold = self.dust_threshold;
+        presale.hard_cap = self.hard_cap;
+        presale.soft_cap = self.soft_cap;
+        presale.start_time = self.start_time;
+        presale.end_time = self.end_time;
+        let phases: Vec<SalePhase> = self
+            .phases
+            .iter()
+            .map(|phase| SalePhase {
+                start_time: phase.start_time,
+                end_time: phase.end_time,
+                kind: phase.kind,
+            })
+            .collect();
+        presale.set_phases(&phases);
+        presale.total_contributions = self.total_contributions;
+        presale.is_active = self.is_active.into();
+        presale.is_closed = self.is_closed.into();
+        presale.is_cancelled = self.is_cancelled.into();
+        presale.refunds_allowed = self.refunds_allowed.into();
+        presale.refund_period = self.refund_period;
+        presale.refund_deadline = self.refund_deadline;
+        presale.paused = self.paused.into();
+        presale.partial_fill = self.partial_fill.into();
+        presale.oversubscription = self.oversubscription.into();
+        presale.allocation_finalized = self.allocation_finalized.into();
+        presale.allocation_requested = self.allocation_requested;
+        presale.allocation_accepted = self.allocation_accepted;
+        presale.allocation_cursor = self.allocation_cursor;
+        presale.contribution_account_count = self.contribution_account_count;
+        presale.unclaimed_excess = self.unclaimed_excess;
+        presale.partial_refunds_enabled = self.partial_refunds_enabled.into();
+        presale.settlement_started = self.settlement_started.into();
+        presale.refund_fee_bps = self.refund_fee_bps;
+        presale.platform_fee_bps = self.platform_fee_bps;
+        presale.platform_fee_recipient = self.platform_fee_recipient;
+        presale.guardian = self.guardian.unwrap_or_default();
+        presale.emergency = self.emergency.into();
+        presale.emergency_withdraw_at = self.emergency_withdraw_at;
+        presale.emergency_withdraw_destination = self.emergency_withdraw_destination;
+        presale.refunds_restore_capacity = self.refunds_restore_capacity.into();
+        presale.treasury = self.treasury.unwrap_or_default();
+        let recipients: Vec<PayoutRecipient> = self
+            .payout_recipients
+            .iter()
+            .map(|recipient| PayoutRecipient {
+                wallet: recipient.wallet,
+                share_bps: recipient.share_bps,
+            })
+            .collect();
+        presale.set_payout_recipients(&recipients);
+        registry.public_sale = self.public_sale.into();
+        registry.set_default_tier(self.default_tier);
+        registry.gating_mint = self.gating_mint.unwrap_or_default();
+        let balance_tiers: Vec<BalanceTier> = self
+            .balance_tiers
+            .iter()
+            .map(|balance_tier| BalanceTier::new(balance_tier.min_balance, balance_tier.tier))
+            .collect();
+        registry.set_balance_tiers(&balance_tiers);
+        let upgrade_thresholds: Vec<UpgradeThreshold> = self
+            .upgrade_thresholds
+            .iter()
+            .map(|threshold| UpgradeThreshold::new(threshold.min_contribution, threshold.tier))
+            .collect();
+        registry.set_upgrade_thresholds(&upgrade_thresholds);
+        registry.pass_merkle_tree = self.pass_merkle_tree.unwrap_or_default();
+        registry.kyc_issuer = self.kyc_issuer.unwrap_or_default();
+        presale.receipt_mint = self.receipt_mint.unwrap_or_default();
+        presale.set_receipt_nft_uri(self.receipt_nft_uri.as_deref());
+        presale.commit_end = self.commit_end;
+        presale.reveal_end = self.reveal_end;
+        presale.lottery_ticket_amount = self.lottery_ticket_amount;
+        presale.lottery_seed_hash = self.lottery_seed_hash;
+        presale.lottery_ticket_count = self.lottery_ticket_count;
+        presale.lottery_seed = self.lottery_seed;
+        presale.lottery_winner_count = self.lottery_winner_count;
+        presale.lottery_drawn = self.lottery_drawn.into();
+        presale.lottery_unsettled = self.lottery_unsettled;
+        presale.sol_usdt_rate = self.sol_usdt_rate;
+        presale.sol_price_feed = self.sol_price_feed.unwrap_or_default();
+        presale.max_price_staleness = self.max_price_staleness;
+        presale.max_price_confidence_bps = self.max_price_confidence_bps;
+        presale.total_sol_contributions = self.total_sol_contributions;
+        presale.contribution_cooldown = self.contribution_cooldown;
+        for (index, tier) in self.tiers.iter().enumerate() {
+            let slot = &mut registry.tiers[index];
+            *slot = TierInfo {
+                max_contribution: tier.max_contribution,
+                tokens_per_usdt: tier.tokens_per_usdt,
+                name: tier.name,
+                ..TierInfo::default()
+            };
+            slot.set_total_cap(tier.total_cap);
+            slot.set_max_members(tier.max_members);
+            presale.tier_totals[index] = TierTotals {
+                total_contributions: tier.total_contributions,
+                member_count: tier.member_count,
+                ..TierTotals::default()
+            };
+        }
+        presale.early_bird_bonus_bps = self.early_bird_bonus_bps;
+        presale.early_bird_cutoff = self.early_bird_cutoff;
+        presale.early_bird_fill_bps = self.early_bird_fill_bps;
+        presale.pricing_curve.set(self.pricing_curve.as_ref());
+        presale.contributor_count = self.contributor_count;
+        presale.refunded_count = self.refunded_count;
+        for (slot, entry) in presale.admin_log.iter_mut().zip(self.admin_log.iter()) {
+            *slot = AdminLogEntry {
+                sequence: entry.sequence,
+                timestamp: entry.timestamp,
+                actor: entry.actor,
+                op: entry.op as u8,
+                _padding: [0; 7],
+            };
+        }
+        presale.admin_log_count = self.admin_log_count;
+        presale.sequence = self.sequence;
+        presale.version = self.version;
+    }
+}
+
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct TierInfoV3 {
+    pub name: [u8; MAX_TIER_NAME_LENGTH],
+    pub max_contribution: u64,
+    pub tokens_per_usdt: u64,
+    pub total_cap: Option<u64>,
+    pub max_members: Option<u32>,
+    pub member_count: u32,
+    pub total_contributions: u64,
+}
+
+impl TierInfoV3 {
+    pub const LEN: usize = Self::INIT_SPACE;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace)]
+pub struct BalanceTierV3 {
+    pub min_balance: u64,
+    pub tier: u8,
+}
+
+impl BalanceTierV3 {
+    pub const LEN: usize = Self::INIT_SPACE;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace)]
+pub struct UpgradeThresholdV3 {
+    pub min_contribution: u64,
+    pub tier: u8,
+}
+
+impl UpgradeThresholdV3 {
+    pub const LEN: usize = Self::INIT_SPACE;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace)]
+pub struct PayoutRecipientV3 {
+    pub wallet: Pubkey,
+    pub share_bps: u16,
+}
+
+impl PayoutRecipientV3 {
+    pub const LEN: usize = Self::INIT_SPACE;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace)]
+pub struct SalePhaseV3 {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub kind: PhaseKind,
+}
+
+impl SalePhaseV3 {
+    pub const LEN: usize = Self::INIT_SPACE;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace)]
+pub struct AdminLogEntryV3 {
+    pub sequence: u64,
+    pub op: AdminOp,
+    pub actor: Pubkey,
+    pub timestamp: i64,
+}
+
+impl AdminLogEntryV3 {
+    pub const LEN: usize = Self::INIT_SPACE;
+}
+
+/// Version 4: the zero-copy `Presale`, holding the tiers and whitelist
+/// settings that version 5 moved to the `Registry`.
+#[zero_copy]
+pub struct PresaleV4 {
+    pub sale_id: u64,
+    pub proposal_count: u64,
+    pub timelock_delay: i64,
+    pub change_count: u64,
+    pub payment_totals: [u64; MAX_PAYMENT_MINTS],
+    pub min_contribution: u64,
+    pub contribution_increment: u64,
+    pub dust_threshold: u64,
+    pub hard_cap: u64,
+    pub soft_cap: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub phases: [PodSalePhase; MAX_SALE_PHASES],
+    pub total_contributions: u64,
+    pub refund_period: i64,
+    pub refund_deadline: i64,
+    pub allocation_requested: u64,
+    pub allocation_accepted: u64,
+    pub unclaimed_excess: u64,
+    pub emergency_withdraw_at: i64,
+    pub commit_end: i64,
+    pub reveal_end: i64,
+    pub lottery_ticket_amount: u64,
+    pub sol_usdt_rate: u64,
+    pub max_price_staleness: u64,
+    pub total_sol_contributions: u64,
+    pub contribution_cooldown: i64,
+    pub tiers: [TierInfoV4; MAX_TIERS],
+    pub early_bird_cutoff: i64,
+    pub pricing_curve: PodPricingCurve,
+    pub balance_tiers: [BalanceTier; MAX_TIERS],
+    pub upgrade_thresholds: [UpgradeThreshold; MAX_TIERS],
+    pub admin_log: [AdminLogEntry; MAX_ADMIN_LOG_ENTRIES],
+    pub admin_log_count: u64,
+    pub sequence: u64,
+    pub allocation_cursor: u32,
+    pub contribution_account_count: u32,
+    pub lottery_ticket_count: u32,
+    pub lottery_winner_count: u32,
+    pub lottery_unsettled: u32,
+    pub contributor_count: u32,
+    pub refunded_count: u32,
+    pub refund_fee_bps: u16,
+    pub platform_fee_bps: u16,
+    pub max_price_confidence_bps: u16,
+    pub early_bird_bonus_bps: u16,
+    pub early_bird_fill_bps: u16,
+    pub payout_recipients: [PayoutRecipient; MAX_PAYOUT_RECIPIENTS],
+    pub owner: Pubkey,
+    pub config: Pubkey,
+    pub creator: Pubkey,
+    pub name: [u8; MAX_PRESALE_NAME_LENGTH],
+    pub symbol: [u8; MAX_PRESALE_SYMBOL_LENGTH],
+    pub description: [u8; MAX_PRESALE_DESCRIPTION_LENGTH],
+    pub pending_owner: Pubkey,
+    pub operator: Pubkey,
+    pub treasurer: Pubkey,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub payment_mints: [Pubkey; MAX_PAYMENT_MINTS],
+    pub payment_vaults: [Pubkey; MAX_PAYMENT_MINTS],
+    pub platform_fee_recipient: Pubkey,
+    pub guardian: Pubkey,
+    pub emergency_withdraw_destination: Pubkey,
+    pub treasury: Pubkey,
+    pub gating_mint: Pubkey,
+    pub pass_merkle_tree: Pubkey,
+    pub kyc_issuer: Pubkey,
+    pub receipt_mint: Pubkey,
+    pub receipt_nft_uri: [u8; MAX_RECEIPT_URI_LENGTH],
+    pub lottery_seed_hash: [u8; 32],
+    pub lottery_seed: [u8; 32],
+    pub sol_price_feed: Pubkey,
+    pub bump: u8,
+    pub sol_vault_bump: u8,
+    pub vault_authority_bump: u8,
+    pub council_len: u8,
+    pub council_threshold: u8,
+    pub payment_mint_count: u8,
+    pub phase_count: u8,
+    pub payout_recipient_count: u8,
+    pub default_tier: u8,
+    pub balance_tier_count: u8,
+    pub upgrade_threshold_count: u8,
+    pub is_initialized: PodBool,
+    pub whitelist_frozen: PodBool,
+    pub is_active: PodBool,
+    pub is_closed: PodBool,
+    pub is_cancelled: PodBool,
+    pub refunds_allowed: PodBool,
+    pub paused: PodBool,
+    pub partial_fill: PodBool,
+    pub oversubscription: PodBool,
+    pub allocation_finalized: PodBool,
+    pub partial_refunds_enabled: PodBool,
+    pub settlement_started: PodBool,
+    pub emergency: PodBool,
+    pub refunds_restore_capacity: PodBool,
+    pub public_sale: PodBool,
+    pub has_default_tier: PodBool,
+    pub lottery_drawn: PodBool,
+    pub _padding: [u8; 1],
+    pub version: u8,
+}
+
+// Nested types are shared with the current layout; a change to any of them
+// must copy the old one here.
+const _: () = assert!(PresaleV4::LEN == 5104);
+
+impl PresaleV4 {
+    pub const LEN: usize = 8 + std::mem::size_of::<PresaleV4>();
+
+    /// Writes this account's contents into `presale` and `registry`.
+    pub fn migrate(&self, presale: &mut SaleConfig, registry: &mut Registry) {
+        presale.sale_id = self.sale_id;
+        presale.proposal_count = self.proposal_count;
+        presale.timelock_delay = self.timelock_delay;
+        presale.change_count = self.change_count;
+        presale.payment_totals = self.payment_totals;
+        presale.min_contribution = self.min_contribution;
+        presale.contribution_increment = self.contribution_increment;
+        presale.dust_threshold = self.dust_threshold;
+        presale.hard_cap = self.hard_cap;
+        presale.soft_cap = self.soft_cap;
+        presale.start_time = self.start_time;
+        presale.end_time = self.end_time;
+        presale.phases = self.phases;
+        presale.total_contributions = self.total_contributions;
+        presale.refund_period = self.refund_period;
+        presale.refund_deadline = self.refund_deadline;
+        presale.allocation_requested = self.allocation_requested;
+        presale.allocation_accepted = self.allocation_accepted;
+        presale.unclaimed_excess = self.unclaimed_excess;
+        presale.emergency_withdraw_at = self.emergency_withdraw_at;
+        presale.commit_end = self.commit_end;
+        presale.reveal_end = self.reveal_end;
+        presale.lottery_ticket_amount = self.lottery_ticket_amount;
+        presale.sol_usdt_rate = self.sol_usdt_rate;
+        presale.max_price_staleness = self.max_price_staleness;
+        presale.total_sol_contributions = self.total_sol_contributions;
+        presale.contribution_cooldown = self.contribution_cooldown;
+        presale.early_bird_cutoff = self.early_bird_cutoff;
+        presale.pricing_curve = self.pricing_curve;
+        presale.admin_log = self.admin_log;
+        presale.admin_log_count = self.admin_log_count;
+        presale.sequence = self.sequence;
+        presale.allocation_cursor = self.allocation_cursor;
+        presale.contribution_account_count = self.contribution_account_count;
+        presale.lottery_ticket_count = self.lottery_ticket_count;
+        presale.lottery_winner_count = self.lottery_winner_count;
+        presale.lottery_unsettled = self.lottery_unsettled;
+        presale.contributor_count = self.contributor_count;
+        presale.refunded_count = self.refunded_count;
+        presale.refund_fee_bps = self.refund_fee_bps;
+        presale.platform_fee_bps = self.platform_fee_bps;
+        presale.max_price_confidence_bps = self.max_price_confidence_bps;
+        presale.early_bird_bonus_bps = self.early_bird_bonus_bps;
+        presale.early_bird_fill_bps = self.early_bird_fill_bps;
+        presale.payout_recipients = self.payout_recipients;
+        presale.owner = self.owner;
+        presale.config = self.config;
+        presale.creator = self.creator;
+        presale.name = self.name;
+        presale.symbol = self.symbol;
+        presale.description = self.description;
+        presale.pending_owner = self.pending_owner;
+        presale.operator = self.operator;
+        presale.treasurer = self.treasurer;
+        presale.council = self.council;
+        presale.payment_mints = self.payment_mints;
+        presale.payment_vaults = self.payment_vaults;
+        presale.platform_fee_recipient = self.platform_fee_recipient;
+        presale.guardian = self.guardian;
+        presale.emergency_withdraw_destination = self.emergency_withdraw_destination;
+        presale.treasury = self.treasury;
+        presale.receipt_mint = self.receipt_mint;
+        presale.receipt_nft_uri = self.receipt_nft_uri;
+        presale.lottery_seed_hash = self.lottery_seed_hash;
+        presale.lottery_seed = self.lottery_seed;
+        presale.sol_price_feed = self.sol_price_feed;
+        presale.bump = self.bump;
+        presale.sol_vault_bump = self.sol_vault_bump;
+        presale.vault_authority_bump = self.vault_authority_bump;
+        presale.council_len = self.council_len;
+        presale.council_threshold = self.council_threshold;
+        presale.payment_mint_count = self.payment_mint_count;
+        presale.phase_count = self.phase_count;
+        presale.payout_recipient_count = self.payout_recipient_count;
+        presale.is_initialized = self.is_initialized;
+        presale.is_active = self.is_active;
+        presale.is_closed = self.is_closed;
+        presale.is_cancelled = self.is_cancelled;
+        presale.refunds_allowed = self.refunds_allowed;
+        presale.paused = self.paused;
+        presale.partial_fill = self.partial_fill;
+        presale.oversubscription = self.oversubscription;
+        presale.allocation_finalized = self.allocation_finalized;
+        presale.partial_refunds_enabled = self.partial_refunds_enabled;
+        presale.settlement_started = self.settlement_started;
+        presale.emergency = self.emergency;
+        presale.refunds_restore_capacity = self.refunds_restore_capacity;
+        presale.lottery_drawn = self.lottery_drawn;
+        presale.version = self.version;
+        registry.balance_tiers = self.balance_tiers;
+        registry.upgrade_thresholds = self.upgrade_thresholds;
+        registry.gating_mint = self.gating_mint;
+        registry.pass_merkle_tree = self.pass_merkle_tree;
+        registry.kyc_issuer = self.kyc_issuer;
+        registry.default_tier = self.default_tier;
+        registry.balance_tier_count = self.balance_tier_count;
+        registry.upgrade_threshold_count = self.upgrade_threshold_count;
+        registry.whitelist_frozen = self.whitelist_frozen;
+        registry.public_sale = self.public_sale;
+        registry.has_default_tier = self.has_default_tier;
+        for (index, tier) in self.tiers.iter().enumerate() {
+            registry.tiers[index] = TierInfo {
+                max_contribution: tier.max_contribution,
+                tokens_per_usdt: tier.tokens_per_usdt,
+                total_cap: tier.total_cap,
+                max_members: tier.max_members,
+                name: tier.name,
+                _padding: [0; 4],
+            };
+            presale.tier_totals[index] = TierTotals {
+                total_contributions: tier.total_contributions,
+                member_count: tier.member_count,
+                _padding: [0; 4],
+            };
+        }
+    }
+}
+
+#[zero_copy]
+pub struct TierInfoV4 {
+    pub max_contribution: u64,
+    pub tokens_per_usdt: u64,
+    pub total_cap: u64,
+    pub total_contributions: u64,
+    pub max_members: u32,
+    pub member_count: u32,
+    pub name: [u8; MAX_TIER_NAME_LENGTH],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tier_name(name: &str) -> [u8; MAX_TIER_NAME_LENGTH] {
+        let mut bytes = [0; MAX_TIER_NAME_LENGTH];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        bytes
+    }
+
+    /// Account data after the discriminator, with the slack a Borsh
+    /// account keeps after its contents.
+    fn borsh_account(presale: &impl AnchorSerialize) -> Vec<u8> {
+        let mut data = presale.try_to_vec().unwrap();
+        data.resize(data.len() + 512, 0);
+        data
+    }
+
+    fn migrate(data: &[u8]) -> Result<(u8, Box<SaleConfig>, Box<Registry>)> {
+        let legacy = LegacyPresale::decode(data)?;
+        let mut presale: Box<SaleConfig> = bytemuck::zeroed_box();
+        let mut registry: Box<Registry> = bytemuck::zeroed_box();
+        legacy.migrate(&mut presale, &mut registry)?;
+        Ok((legacy.version(), presale, registry))
+    }
+
+    fn v2() -> PresaleV2 {
+        let mut tiers = [TierInfoV3::default(); MAX_TIERS];
+        tiers[0] = TierInfoV3 {
+            name: tier_name("gold"),
+            max_contribution: 5_000_000_000,
+            tokens_per_usdt: 40,
+            total_cap: Some(100_000_000_000),
+            max_members: None,
+            member_count: 0,
+            total_contributions: 0,
+        };
+        PresaleV2 {
+            is_initialized: true,
+            owner: Pubkey::new_unique(),
+            creator: Pubkey::new_unique(),
+            name: "Moosty".to_string(),
+            symbol: "MST".to_string(),
+            sale_id: 3,
+            bump: 254,
+            payment_mints: vec![Pubkey::new_unique()],
+            payment_vaults: vec![Pubkey::new_unique()],
+            min_contribution: 10_000_000,
+            hard_cap: 1_000_000_000_000,
+            phases: vec![SalePhaseV3 { start_time: 100, end_time: 200, kind: PhaseKind::Fcfs }],
+            is_active: true,
+            default_tier: Some(0),
+            gating_mint: Some(Pubkey::new_unique()),
+            balance_tiers: vec![BalanceTierV3 { min_balance: 1_000, tier: 0 }],
+            tiers,
+            admin_log: vec![AdminLogEntryV3 {
+                sequence: 6,
+                op: AdminOp::Pause,
+                actor: Pubkey::new_unique(),
+                timestamp: 150,
+            }],
+            admin_log_count: 1,
+            sequence: 7,
+            version: 2,
+            ..PresaleV2::default()
+        }
+    }
+
+    #[test]
+    fn migrates_version_2() {
+        let legacy = v2();
+        let (version, presale, registry) = migrate(&borsh_account(&legacy)).unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(presale.owner, legacy.owner);
+        assert_eq!(presale.creator, legacy.creator);
+        assert_eq!(presale.name(), "Moosty");
+        assert_eq!(presale.sale_id, 3);
+        assert_eq!(presale.bump, 254);
+        assert_eq!(presale.payment_mints(), &legacy.payment_mints[..]);
+        assert_eq!(presale.payment_totals[0], 0);
+        assert_eq!(presale.min_contribution, 10_000_000);
+        assert_eq!(presale.phases()[0].end_time, 200);
+        assert!(presale.is_active.get());
+        assert_eq!(presale.admin_log()[0].actor, legacy.admin_log[0].actor);
+        assert_eq!(presale.sequence, 7);
+        assert_eq!(presale.lottery_ticket_count, 0);
+        assert_eq!(presale.version, 2);
+        let tier = registry.tier_at(0).unwrap();
+        assert_eq!(tier.name(), "gold");
+        assert_eq!(tier.total_cap(), Some(100_000_000_000));
+        assert_eq!(tier.max_members(), None);
+        assert_eq!(registry.default_tier(), Some(0));
+        assert_eq!(registry.gating_mint(), legacy.gating_mint);
+        assert_eq!(registry.balance_tiers()[0].min_balance, 1_000);
+    }
+
+    #[test]
+    fn rejects_version_2_with_user_entries() {
+        let mut legacy = v2();
+        legacy.contributions.insert(Pubkey::new_unique(), 10_000_000);
+        legacy.contributor_count = 1;
+        let err = migrate(&borsh_account(&legacy)).err().unwrap();
+        assert_eq!(err, PresaleError::ContributorsNotMigratable.into());
+    }
+
+    #[test]
+    fn migrates_version_3() {
+        let mut tiers = [TierInfoV3::default(); MAX_TIERS];
+        tiers[1] = TierInfoV3 {
+            name: tier_name("silver"),
+            max_contribution: 1_000_000_000,
+            tokens_per_usdt: 50,
+            total_cap: None,
+            max_members: Some(200),
+            member_count: 12,
+            total_contributions: 7_500_000_000,
+        };
+        let legacy = PresaleV3 {
+            is_initialized: true,
+            owner: Pubkey::new_unique(),
+            creator: Pubkey::new_unique(),
+            description: "Community round".to_string(),
+            sale_id: 1,
+            payment_mints: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            payment_vaults: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            payment_totals: vec![5_000_000_000, 2_500_000_000],
+            min_contribution: 10_000_000,
+            total_contributions: 7_500_000_000,
+            allocation_cursor: 4,
+            contribution_account_count: 12,
+            public_sale: true,
+            kyc_issuer: Some(Pubkey::new_unique()),
+            receipt_nft_uri: Some("https://example.com/receipt.json".to_string()),
+            lottery_ticket_count: 9,
+            tiers,
+            pricing_curve: Some(PricingCurve::Linear {
+                start_tokens_per_usdt: 60,
+                end_tokens_per_usdt: 40,
+            }),
+            contributor_count: 12,
+            sequence: 31,
+            version: 3,
+            ..PresaleV3::default()
+        };
+        let (version, presale, registry) = migrate(&borsh_account(&legacy)).unwrap();
+        assert_eq!(version, 3);
+        assert_eq!(presale.owner, legacy.owner);
+        assert_eq!(presale.description(), "Community round");
+        assert_eq!(presale.payment_mints(), &legacy.payment_mints[..]);
+        assert_eq!(presale.payment_totals[..2], [5_000_000_000, 2_500_000_000]);
+        assert_eq!(presale.total_contributions, 7_500_000_000);
+        assert_eq!(presale.allocation_cursor, 4);
+        assert_eq!(presale.contribution_account_count, 12);
+        assert_eq!(presale.receipt_nft_uri(), Some("https://example.com/receipt.json"));
+        assert_eq!(presale.lottery_ticket_count, 9);
+        assert!(presale.pricing_curve() == legacy.pricing_curve);
+        assert_eq!(presale.contributor_count, 12);
+        assert_eq!(presale.version, 3);
+        let totals = presale.tier_totals(1).unwrap();
+        assert_eq!(totals.total_contributions, 7_500_000_000);
+        assert_eq!(totals.member_count, 12);
+        let tier = registry.tier_at(1).unwrap();
+        assert_eq!(tier.name(), "silver");
+        assert_eq!(tier.total_cap(), None);
+        assert_eq!(tier.max_members(), Some(200));
+        assert!(registry.public_sale.get());
+        assert_eq!(registry.kyc_issuer(), legacy.kyc_issuer);
+    }
+
+    #[test]
+    fn migrates_version_4() {
+        let mut legacy: Box<PresaleV4> = bytemuck::zeroed_box();
+        legacy.owner = Pubkey::new_unique();
+        legacy.creator = Pubkey::new_unique();
+        legacy.sale_id = 2;
+        legacy.hard_cap = 1_000_000_000_000;
+        legacy.total_contributions = 3_000_000_000;
+        legacy.tiers[0] = TierInfoV4 {
+            max_contribution: 5_000_000_000,
+            tokens_per_usdt: 40,
+            total_cap: u64::MAX,
+            total_contributions: 3_000_000_000,
+            max_members: 50,
+            member_count: 2,
+            name: tier_name("gold"),
+        };
+        legacy.pass_merkle_tree = Pubkey::new_unique();
+        legacy.default_tier = 0;
+        legacy.has_default_tier = true.into();
+        legacy.whitelist_frozen = true.into();
+        legacy.contributor_count = 2;
+        legacy.is_active = true.into();
+        legacy.sequence = 12;
+        legacy.version = 4;
+        let (version, presale, registry) = migrate(bytemuck::bytes_of(legacy.as_ref())).unwrap();
+        assert_eq!(version, 4);
+        assert_eq!(presale.owner, legacy.owner);
+        assert_eq!(presale.creator, legacy.creator);
+        assert_eq!(presale.sale_id, 2);
+        assert_eq!(presale.hard_cap, 1_000_000_000_000);
+        assert_eq!(presale.total_contributions, 3_000_000_000);
+        assert_eq!(presale.contributor_count, 2);
+        assert!(presale.is_active.get());
+        assert_eq!(presale.sequence, 12);
+        assert_eq!(presale.version, 4);
+        let totals = presale.tier_totals(0).unwrap();
+        assert_eq!(totals.total_contributions, 3_000_000_000);
+        assert_eq!(totals.member_count, 2);
+        let tier = registry.tier_at(0).unwrap();
+        assert_eq!(tier.name(), "gold");
+        assert_eq!(tier.total_cap(), None);
+        assert_eq!(tier.max_members(), Some(50));
+        assert_eq!(registry.pass_merkle_tree(), Some(legacy.pass_merkle_tree));
+        assert_eq!(registry.default_tier(), Some(0));
+        assert!(registry.whitelist_frozen.get());
+    }
+
+    #[test]
+    fn rejects_unknown_layouts() {
+        let err = migrate(&[0xff; 256]).err().unwrap();
+        assert_eq!(err, PresaleError::UnsupportedLayout.into());
+    }
+}
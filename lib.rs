@@ -1,16 +0,0 @@
-pub mod state;
-pub mod instructions;
-pub mod error;
-pub mod events;
-pub mod context;
-
-pub use state::*;
-pub use instructions::*;
-pub use error::*;
-pub use events::*;
-pub use context::*;
-
-use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer, Mint};
-
-declare_id!("YourProgramIDHere1234567890ABCDEFGH");
@@ -1,23 +1,51 @@
-pub mod state;
-pub mod instructions;
-pub mod error;
-pub mod events;
-pub mod context;
-
-pub use state::*;
-pub use instructions::*;
-pub use error::*;
-pub use events::*;
-pub use context::*;
-
-use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer, Mint};
-
-declare_id!("YourProgramIDHere1234567890ABCDEFGH");
-
-// Constants
-pub const USDT_DECIMALS: u64 = 1_000_000;
-pub const MAX_TIERS: usize = 10;
-pub const MAX_USERS: usize = 1000;
-pub const MAX_TIER_NAME_LENGTH: usize = 32;
-pub const MAX_BULK_ASSIGN: usize = 50; 
\ No newline at end of file
+// Anchor's `Error` is large by design; every handler returns it.
+#![allow(clippy::result_large_err)]
+
+pub mod state;
+pub mod instructions;
+pub mod error;
+pub mod events;
+pub mod context;
+pub mod oracle;
+pub mod pricing;
+pub mod legacy;
+
+pub use state::*;
+pub use instructions::*;
+pub use error::*;
+pub use events::*;
+pub use context::*;
+// `Refund` is both an instruction context and an event; the context wins.
+pub use context::Refund;
+
+use anchor_lang::prelude::*;
+
+declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
+
+// Constants
+pub const USDT_DECIMALS: u64 = 1_000_000;
+pub const MAX_TIERS: usize = 10;
+pub const MAX_TIER_NAME_LENGTH: usize = 32;
+pub const MAX_BULK_ASSIGN: usize = 50;
+pub const MAX_AUDIT_RECORDS: usize = 16;
+pub const MAX_PAYMENT_MINTS: usize = 4;
+/// Mints a single wallet can hold a balance in: each payment mint plus the
+/// native mint SOL contributions are booked under.
+pub const MAX_MINTS_PER_USER: usize = MAX_PAYMENT_MINTS + 1;
+pub const MAX_RECEIPT_URI_LENGTH: usize = 128;
+pub const MAX_SALE_PHASES: usize = 8;
+pub const MAX_PRICE_STEPS: usize = 10;
+pub const MAX_BULK_REFUND: usize = 20;
+pub const MAX_COUNCIL_MEMBERS: usize = 7;
+pub const EMERGENCY_WITHDRAW_DELAY: i64 = 3 * 24 * 60 * 60;
+pub const MAX_PAYOUT_RECIPIENTS: usize = 5;
+pub const MAX_ALLOWED_PAYMENT_MINTS: usize = 16;
+pub const PRESALE_VERSION: u8 = 6;
+pub const MAX_ADMIN_LOG_ENTRIES: usize = 32;
+pub const MAX_PRESALE_NAME_LENGTH: usize = 32;
+pub const MAX_PRESALE_SYMBOL_LENGTH: usize = 10;
+pub const MAX_PRESALE_DESCRIPTION_LENGTH: usize = 200;
+pub const MAX_SNAPSHOTS: usize = 64;
+pub const MIN_SNAPSHOT_INTERVAL: i64 = 3600;
+pub const CLAIM_PERIOD: i64 = 180 * 24 * 60 * 60;
+pub const LOTTERY_DRAW_PERIOD: i64 = 7 * 24 * 60 * 60;
\ No newline at end of file
@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+use pyth_sdk_solana::load_price_feed_from_account_info;
+use crate::error::*;
+
+/// Values `amount` base units of an asset with `asset_decimals` in USDT base
+/// units using a Pyth price account. Prices older than `max_staleness`
+/// seconds, or whose confidence interval exceeds `max_confidence_bps` of the
+/// price, are rejected.
+pub fn pyth_usdt_value(
+    price_account: &AccountInfo,
+    amount: u64,
+    asset_decimals: u32,
+    max_staleness: u64,
+    max_confidence_bps: u16,
+) -> Result<u64> {
+    let price_feed = load_price_feed_from_account_info(price_account)
+        .map_err(|_| error!(PresaleError::InvalidPriceFeed))?;
+
+    let price = price_feed
+        .get_price_no_older_than(Clock::get()?.unix_timestamp, max_staleness)
+        .ok_or(PresaleError::StalePrice)?;
+    require!(price.price > 0, PresaleError::InvalidPriceFeed);
+
+    let price_value = price.price as u128;
+    require!(
+        (price.conf as u128) * 10_000 <= price_value * max_confidence_bps as u128,
+        PresaleError::PriceConfidenceTooLow
+    );
+
+    // value = amount * price * 10^expo * 10^6 / 10^asset_decimals
+    let value = (amount as u128)
+        .checked_mul(price_value)
+        .ok_or(PresaleError::Overflow)?;
+    let exponent = price.expo + 6 - asset_decimals as i32;
+    let value = if exponent >= 0 {
+        value
+            .checked_mul(10u128.pow(exponent as u32))
+            .ok_or(PresaleError::Overflow)?
+    } else {
+        value / 10u128.pow((-exponent) as u32)
+    };
+
+    u64::try_from(value).map_err(|_| error!(PresaleError::Overflow))
+}
@@ -0,0 +1,164 @@
+use anchor_lang::prelude::*;
+use crate::error::*;
+use crate::MAX_PRICE_STEPS;
+
+/// A price schedule keyed on `total_contributions`. Rates are sale token base
+/// units per whole USDT, so a falling rate means a rising token price.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum PricingCurve {
+    /// Interpolates from `start_tokens_per_usdt` at zero raised to
+    /// `end_tokens_per_usdt` at the hard cap.
+    Linear {
+        start_tokens_per_usdt: u64,
+        end_tokens_per_usdt: u64,
+    },
+    /// Uses the rate of the last step whose `min_total` has been reached.
+    Stepwise {
+        #[max_len(10)]
+        steps: Vec<PriceStep>,
+    },
+}
+
+#[zero_copy]
+#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Default, InitSpace)]
+pub struct PriceStep {
+    pub min_total: u64,
+    pub tokens_per_usdt: u64,
+}
+
+impl PricingCurve {
+    pub const LEN: usize = Self::INIT_SPACE;
+
+    /// Checks that the curve never lowers the token price as the sale fills.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            PricingCurve::Linear {
+                start_tokens_per_usdt,
+                end_tokens_per_usdt,
+            } => {
+                require!(
+                    *end_tokens_per_usdt > 0 && end_tokens_per_usdt <= start_tokens_per_usdt,
+                    PresaleError::InvalidPricingCurve
+                );
+            }
+            PricingCurve::Stepwise { steps } => {
+                require!(
+                    !steps.is_empty() && steps.len() <= MAX_PRICE_STEPS,
+                    PresaleError::InvalidPricingCurve
+                );
+                require!(steps[0].min_total == 0, PresaleError::InvalidPricingCurve);
+                for pair in steps.windows(2) {
+                    require!(
+                        pair[0].min_total < pair[1].min_total
+                            && pair[1].tokens_per_usdt <= pair[0].tokens_per_usdt,
+                        PresaleError::InvalidPricingCurve
+                    );
+                }
+                require!(
+                    steps.iter().all(|step| step.tokens_per_usdt > 0),
+                    PresaleError::InvalidPricingCurve
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Marginal rate once `total_contributions` out of `hard_cap` is raised.
+    pub fn tokens_per_usdt(&self, total_contributions: u64, hard_cap: u64) -> u64 {
+        match self {
+            PricingCurve::Linear {
+                start_tokens_per_usdt,
+                end_tokens_per_usdt,
+            } => linear_rate(
+                *start_tokens_per_usdt,
+                *end_tokens_per_usdt,
+                total_contributions,
+                hard_cap,
+            ),
+            PricingCurve::Stepwise { steps } => stepwise_rate(steps, total_contributions),
+        }
+    }
+}
+
+/// `Option<PricingCurve>` as stored in the presale.
+#[zero_copy]
+#[derive(Default)]
+pub struct PodPricingCurve {
+    pub start_tokens_per_usdt: u64,
+    pub end_tokens_per_usdt: u64,
+    pub steps: [PriceStep; MAX_PRICE_STEPS],
+    /// 0 when unset, 1 for `Linear`, 2 for `Stepwise`.
+    pub kind: u8,
+    pub step_count: u8,
+    pub _padding: [u8; 6],
+}
+
+impl PodPricingCurve {
+    const LINEAR: u8 = 1;
+    const STEPWISE: u8 = 2;
+
+    pub fn get(&self) -> Option<PricingCurve> {
+        match self.kind {
+            Self::LINEAR => Some(PricingCurve::Linear {
+                start_tokens_per_usdt: self.start_tokens_per_usdt,
+                end_tokens_per_usdt: self.end_tokens_per_usdt,
+            }),
+            Self::STEPWISE => Some(PricingCurve::Stepwise {
+                steps: self.steps[..self.step_count as usize].to_vec(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Stores `curve`, which callers have already validated.
+    pub fn set(&mut self, curve: Option<&PricingCurve>) {
+        *self = Self::default();
+        match curve {
+            Some(PricingCurve::Linear {
+                start_tokens_per_usdt,
+                end_tokens_per_usdt,
+            }) => {
+                self.kind = Self::LINEAR;
+                self.start_tokens_per_usdt = *start_tokens_per_usdt;
+                self.end_tokens_per_usdt = *end_tokens_per_usdt;
+            }
+            Some(PricingCurve::Stepwise { steps }) => {
+                self.kind = Self::STEPWISE;
+                self.steps[..steps.len()].copy_from_slice(steps);
+                self.step_count = steps.len() as u8;
+            }
+            None => {}
+        }
+    }
+
+    /// Marginal rate under the stored curve, if one is set.
+    pub fn tokens_per_usdt(&self, total_contributions: u64, hard_cap: u64) -> Option<u64> {
+        match self.kind {
+            Self::LINEAR => Some(linear_rate(
+                self.start_tokens_per_usdt,
+                self.end_tokens_per_usdt,
+                total_contributions,
+                hard_cap,
+            )),
+            Self::STEPWISE => Some(stepwise_rate(
+                &self.steps[..self.step_count as usize],
+                total_contributions,
+            )),
+            _ => None,
+        }
+    }
+}
+
+fn linear_rate(start_tokens_per_usdt: u64, end_tokens_per_usdt: u64, total_contributions: u64, hard_cap: u64) -> u64 {
+    let filled = total_contributions.min(hard_cap) as u128;
+    let drop = (start_tokens_per_usdt - end_tokens_per_usdt) as u128;
+    start_tokens_per_usdt - (drop * filled / hard_cap.max(1) as u128) as u64
+}
+
+fn stepwise_rate(steps: &[PriceStep], total_contributions: u64) -> u64 {
+    steps
+        .iter()
+        .rev()
+        .find(|step| total_contributions >= step.min_total)
+        .map_or(0, |step| step.tokens_per_usdt)
+}
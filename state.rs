@@ -1,46 +1,1887 @@
-use anchor_lang::prelude::*;
-use std::collections::BTreeMap;
-
-#[account]
-#[derive(Default)]
-pub struct Presale {
-    pub is_initialized: bool,
-    pub owner: Pubkey,
-    pub usdt_mint: Pubkey,
-    pub min_contribution: u64,
-    pub hard_cap: u64,
-    pub total_contributions: u64,
-    pub is_active: bool,
-    pub is_closed: bool,
-    pub refunds_allowed: bool,
-    pub paused: bool,
-    pub whitelist: BTreeMap<Pubkey, String>,
-    pub tiers: BTreeMap<String, u64>,
-    pub contributions: BTreeMap<Pubkey, u64>,
-    pub refunded: BTreeMap<Pubkey, bool>,
-    pub contributors: Vec<Pubkey>,
-    pub tier_total_contributions: BTreeMap<String, u64>,
-}
-
-impl Presale {
-    pub const LEN: usize = 8 +  // Discriminator
-        1 + // is_initialized
-        32 + // owner
-        32 + // usdt_mint
-        8 +  // min_contribution
-        8 +  // hard_cap
-        8 +  // total_contributions
-        1 +  // is_active
-        1 +  // is_closed
-        1 +  // refunds_allowed
-        1 +  // paused
-        4 +  // whitelist map length
-        (MAX_USERS * (32 + MAX_TIER_NAME_LENGTH)) + 
-        4 +  // tiers map length
-        (MAX_TIERS * (MAX_TIER_NAME_LENGTH + 8)) + 
-        4 +  // contributions map length
-        (MAX_USERS * (32 + 8)) + 
-        4 +  // refunded map length
-        (MAX_USERS * (32 + 1)) + 
-        4 + (MAX_USERS * 32); // contributors list
-} 
\ No newline at end of file
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
+use anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+use crate::error::*;
+use crate::pricing::{PodPricingCurve, PricingCurve};
+use crate::{
+    CLAIM_PERIOD, LOTTERY_DRAW_PERIOD, MAX_ADMIN_LOG_ENTRIES, MAX_COUNCIL_MEMBERS, MAX_PAYMENT_MINTS,
+    MAX_PAYOUT_RECIPIENTS, MAX_PRESALE_DESCRIPTION_LENGTH, MAX_PRESALE_NAME_LENGTH,
+    MAX_PRESALE_SYMBOL_LENGTH, MAX_MINTS_PER_USER, MAX_RECEIPT_URI_LENGTH, MAX_SALE_PHASES,
+    MAX_SNAPSHOTS, MAX_TIERS, MAX_TIER_NAME_LENGTH, USDT_DECIMALS,
+};
+
+/// Sale state that changes with every contribution: caps, totals, flags
+/// and the per-tier running totals. Tier definitions and whitelist
+/// settings live in the presale's `Registry`, which contributions only
+/// read. Zero-copy, so `contribute` and the other hot instructions read and
+/// write their fields in place rather than deserializing and reserializing
+/// the whole account. Every field is plain old data: flags are `PodBool`,
+/// an unset optional key is `Pubkey::default()`, and lists are fixed arrays
+/// with a count; methods of the same name return the `Option` or slice
+/// views. Fields are grouped by alignment so the layout has no implicit
+/// padding.
+#[account(zero_copy)]
+pub struct SaleConfig {
+    /// Index of this sale among the creator's presales; part of the PDA
+    /// seeds so one creator can run several sales.
+    pub sale_id: u64,
+    /// Index of the next `AdminProposal`, used in its seeds.
+    pub proposal_count: u64,
+    /// Delay, in seconds, between queueing a sensitive parameter change and
+    /// applying it; zero lets the owner apply changes directly.
+    pub timelock_delay: i64,
+    /// Index of the next `QueuedChange`, used in its seeds.
+    pub change_count: u64,
+    /// Outstanding contributions paid in each entry of `payment_mints`, at
+    /// the same index, net of refunds and excess returned after allocation.
+    pub payment_totals: [u64; MAX_PAYMENT_MINTS],
+    pub min_contribution: u64,
+    /// Contributions must be a multiple of this amount; zero allows any amount.
+    pub contribution_increment: u64,
+    /// Smallest amount accepted in a single contribution.
+    pub dust_threshold: u64,
+    pub hard_cap: u64,
+    /// Minimum raise for the sale to succeed. Closing below it forces
+    /// refunds and blocks withdrawals.
+    pub soft_cap: u64,
+    /// Contributions are accepted in `[start_time, end_time]`; anyone may
+    /// finalize the sale once `end_time` has passed.
+    pub start_time: i64,
+    pub end_time: i64,
+    /// Ordered, non-overlapping sale phases, `phase_count` of them; none
+    /// means a single phase governed by the tier and public-sale settings.
+    pub phases: [PodSalePhase; MAX_SALE_PHASES],
+    pub total_contributions: u64,
+    /// Length of the refund window opened at close; zero means no deadline.
+    pub refund_period: i64,
+    /// Refunds stop after this time; zero while refunds are allowed means
+    /// they never stop.
+    pub refund_deadline: i64,
+    /// Raise `finalize_allocation` scales against, fixed by its first batch.
+    pub allocation_requested: u64,
+    /// Accepted contributions of the wallets allocated so far.
+    pub allocation_accepted: u64,
+    /// Excess booked by `finalize_allocation` and not yet claimed back.
+    pub unclaimed_excess: u64,
+    /// When non-zero, an emergency withdraw to `emergency_withdraw_destination`
+    /// can be executed from this time.
+    pub emergency_withdraw_at: i64,
+    /// End of the commit phase; zero disables commit-reveal contributions.
+    pub commit_end: i64,
+    /// End of the reveal phase, after which unrevealed escrows are reclaimable.
+    pub reveal_end: i64,
+    /// Fixed lottery ticket price; zero disables lottery mode.
+    pub lottery_ticket_amount: u64,
+    /// USDT base units credited per SOL; zero disables `contribute_sol`.
+    pub sol_usdt_rate: u64,
+    pub max_price_staleness: u64,
+    pub total_sol_contributions: u64,
+    /// Minimum seconds between contributions from the same wallet.
+    pub contribution_cooldown: i64,
+    /// Running totals of the tiers, indexed like `Registry::tiers`.
+    pub tier_totals: [TierTotals; MAX_TIERS],
+    pub early_bird_cutoff: i64,
+    /// When set, overrides tier prices with a rate that follows the raise.
+    pub pricing_curve: PodPricingCurve,
+    /// Last `MAX_ADMIN_LOG_ENTRIES` privileged actions; once full, the
+    /// oldest entry is overwritten.
+    pub admin_log: [AdminLogEntry; MAX_ADMIN_LOG_ENTRIES],
+    /// Number of privileged actions ever logged; the next entry goes to
+    /// `admin_log_count % MAX_ADMIN_LOG_ENTRIES`.
+    pub admin_log_count: u64,
+    /// Sequence number of the last event emitted for this presale.
+    pub sequence: u64,
+    /// Index of the next `ContributionAccount` `finalize_allocation` expects.
+    pub allocation_cursor: u32,
+    /// `ContributionAccount`s created so far; each takes the next index.
+    pub contribution_account_count: u32,
+    /// Tickets sold; each `LotteryTicket` records its index among them.
+    pub lottery_ticket_count: u32,
+    /// Tickets ranked below this number by `lottery_rank` won.
+    pub lottery_winner_count: u32,
+    /// Tickets not yet settled; withdrawals wait until every loser is repaid.
+    pub lottery_unsettled: u32,
+    /// Wallets with a non-zero contribution; the wallets themselves and
+    /// everything recorded per wallet live in their `ContributionAccount`
+    /// PDAs.
+    pub contributor_count: u32,
+    /// Times a wallet's whole contribution has been refunded.
+    pub refunded_count: u32,
+    /// Exit fee, in basis points, kept in the vault on refunds taken while
+    /// the sale is still open.
+    pub refund_fee_bps: u16,
+    /// Launchpad fee, in basis points, taken from every `withdraw_funds`
+    /// and `withdraw_sol`. Copied from the global config at initialize.
+    pub platform_fee_bps: u16,
+    pub max_price_confidence_bps: u16,
+    /// Bonus, in basis points of purchased tokens, for contributions made
+    /// before `early_bird_cutoff` or while the raise is below
+    /// `early_bird_fill_bps` of the hard cap (zero disables either trigger).
+    pub early_bird_bonus_bps: u16,
+    pub early_bird_fill_bps: u16,
+    /// Wallets `withdraw_funds` and `withdraw_sol` split the proceeds
+    /// between; none sends everything to the withdrawer.
+    pub payout_recipients: [PayoutRecipient; MAX_PAYOUT_RECIPIENTS],
+    pub owner: Pubkey,
+    /// Global config the presale was created under.
+    pub config: Pubkey,
+    /// Key the presale PDA was derived from at initialize. Unlike `owner`
+    /// it never changes, so ownership can move without orphaning the PDA.
+    pub creator: Pubkey,
+    /// Display metadata for explorers, zero-padded; updatable until the
+    /// sale starts.
+    pub name: [u8; MAX_PRESALE_NAME_LENGTH],
+    pub symbol: [u8; MAX_PRESALE_SYMBOL_LENGTH],
+    pub description: [u8; MAX_PRESALE_DESCRIPTION_LENGTH],
+    /// Proposed new owner, who must call `accept_ownership`.
+    pub pending_owner: Pubkey,
+    /// Delegate for whitelist, blacklist and registration management.
+    pub operator: Pubkey,
+    /// Delegate allowed to withdraw raise proceeds.
+    pub treasurer: Pubkey,
+    /// Admin council; when non-empty, sensitive instructions need an
+    /// `AdminProposal` approved by `council_threshold` members.
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    /// Accepted 6-decimal stablecoin mints; contributions are aggregated
+    /// across them in USDT units.
+    pub payment_mints: [Pubkey; MAX_PAYMENT_MINTS],
+    /// Vault authority's associated token account created for each entry of
+    /// `payment_mints`, at the same index.
+    pub payment_vaults: [Pubkey; MAX_PAYMENT_MINTS],
+    pub platform_fee_recipient: Pubkey,
+    /// Key allowed to pause the sale and trigger emergency mode; unset
+    /// disables it.
+    pub guardian: Pubkey,
+    pub emergency_withdraw_destination: Pubkey,
+    /// Receives refunds left unclaimed when the refund window closes.
+    pub treasury: Pubkey,
+    /// Receipt token minted 1:1 with USDT contributions; unset disables receipts.
+    pub receipt_mint: Pubkey,
+    /// Base metadata URI for receipt NFTs, zero-padded; empty disables them.
+    pub receipt_nft_uri: [u8; MAX_RECEIPT_URI_LENGTH],
+    /// sha256 of the seed the owner reveals in `draw_lottery`.
+    pub lottery_seed_hash: [u8; 32],
+    /// Seed revealed by `draw_lottery`, mixed with a slot hash from the
+    /// draw; ranks the tickets.
+    pub lottery_seed: [u8; 32],
+    /// Pyth SOL/USD price account; when set it takes precedence over `sol_usdt_rate`.
+    pub sol_price_feed: Pubkey,
+    /// Canonical bumps, stored at initialize so signer seeds and account
+    /// constraints don't have to re-derive them.
+    pub bump: u8,
+    pub sol_vault_bump: u8,
+    /// Bump of the `vault_authority` PDA that owns the token vaults.
+    pub vault_authority_bump: u8,
+    pub council_len: u8,
+    pub council_threshold: u8,
+    pub payment_mint_count: u8,
+    pub phase_count: u8,
+    pub payout_recipient_count: u8,
+    pub is_initialized: PodBool,
+    pub is_active: PodBool,
+    pub is_closed: PodBool,
+    /// Set when the sale is called off before taking any funds; permanent.
+    pub is_cancelled: PodBool,
+    pub refunds_allowed: PodBool,
+    pub paused: PodBool,
+    /// When set, a contribution overshooting the hard cap is reduced to the
+    /// remaining capacity instead of being rejected.
+    pub partial_fill: PodBool,
+    /// When set, contributions may exceed the hard cap during the sale and
+    /// are scaled down pro-rata by `finalize_allocation`.
+    pub oversubscription: PodBool,
+    pub allocation_finalized: PodBool,
+    /// Lets users withdraw part of their contribution while the sale is open.
+    pub partial_refunds_enabled: PodBool,
+    /// Set by the first refund or withdrawal after close; a closed sale can
+    /// only be reopened before then.
+    pub settlement_started: PodBool,
+    /// Set by the guardian or an emergency withdraw; halts contributions and
+    /// unlocks refunds for all.
+    pub emergency: PodBool,
+    /// When unset, refunded amounts keep counting against the user's
+    /// maximum contribution instead of freeing it up again.
+    pub refunds_restore_capacity: PodBool,
+    pub lottery_drawn: PodBool,
+    /// Whether the raise had reached `soft_cap` when the sale closed. Fixed
+    /// at close, since refunds can later take `total_contributions` back
+    /// below the cap.
+    pub soft_cap_reached: PodBool,
+    pub _padding: [u8; 6],
+    /// Layout version. New fields go after it, in space taken from
+    /// `_padding` or by growing the account in `migrate`.
+    pub version: u8,
+}
+
+// `initialize` creates the presale at full size through a CPI, which may
+// allocate at most `MAX_PERMITTED_DATA_INCREASE` bytes.
+const _: () = assert!(SaleConfig::LEN <= MAX_PERMITTED_DATA_INCREASE);
+
+// A layout change must bump `PRESALE_VERSION` and keep the old layout in
+// `legacy` for `migrate`; only then update these sizes.
+const _: () = assert!(SaleConfig::LEN == 4128 && Registry::LEN == 1104);
+
+impl SaleConfig {
+    pub const LEN: usize = 8 + std::mem::size_of::<SaleConfig>();
+
+    pub fn name(&self) -> &str {
+        padded_str(&self.name)
+    }
+
+    pub fn symbol(&self) -> &str {
+        padded_str(&self.symbol)
+    }
+
+    pub fn description(&self) -> &str {
+        padded_str(&self.description)
+    }
+
+    /// Stores the display metadata, which callers have already checked
+    /// against the `MAX_PRESALE_*_LENGTH` limits.
+    pub fn set_metadata(&mut self, name: &str, symbol: &str, description: &str) {
+        set_padded(&mut self.name, name);
+        set_padded(&mut self.symbol, symbol);
+        set_padded(&mut self.description, description);
+    }
+
+    pub fn receipt_nft_uri(&self) -> Option<&str> {
+        Some(padded_str(&self.receipt_nft_uri)).filter(|uri| !uri.is_empty())
+    }
+
+    /// Stores the receipt NFT URI, which callers have already checked
+    /// against `MAX_RECEIPT_URI_LENGTH`.
+    pub fn set_receipt_nft_uri(&mut self, uri: Option<&str>) {
+        set_padded(&mut self.receipt_nft_uri, uri.unwrap_or_default());
+    }
+
+    pub fn pending_owner(&self) -> Option<Pubkey> {
+        optional_key(self.pending_owner)
+    }
+
+    pub fn operator(&self) -> Option<Pubkey> {
+        optional_key(self.operator)
+    }
+
+    pub fn treasurer(&self) -> Option<Pubkey> {
+        optional_key(self.treasurer)
+    }
+
+    pub fn guardian(&self) -> Option<Pubkey> {
+        optional_key(self.guardian)
+    }
+
+    pub fn treasury(&self) -> Option<Pubkey> {
+        optional_key(self.treasury)
+    }
+
+    pub fn receipt_mint(&self) -> Option<Pubkey> {
+        optional_key(self.receipt_mint)
+    }
+
+    pub fn sol_price_feed(&self) -> Option<Pubkey> {
+        optional_key(self.sol_price_feed)
+    }
+
+    pub fn council(&self) -> &[Pubkey] {
+        &self.council[..self.council_len as usize]
+    }
+
+    /// Replaces the council; callers have already checked the member count.
+    pub fn set_council(&mut self, members: &[Pubkey]) {
+        self.council_len = fill_slots(&mut self.council, members);
+    }
+
+    pub fn payment_mints(&self) -> &[Pubkey] {
+        &self.payment_mints[..self.payment_mint_count as usize]
+    }
+
+    pub fn payment_vaults(&self) -> &[Pubkey] {
+        &self.payment_vaults[..self.payment_mint_count as usize]
+    }
+
+    /// Accepts `mint`, paid into `vault`, in the next payment slot.
+    pub fn add_payment_mint(&mut self, mint: Pubkey, vault: Pubkey) -> Result<()> {
+        let index = self.payment_mint_count as usize;
+        require!(index < MAX_PAYMENT_MINTS, PresaleError::ExceedsMaxPaymentMints);
+        self.payment_mints[index] = mint;
+        self.payment_vaults[index] = vault;
+        self.payment_totals[index] = 0;
+        self.payment_mint_count += 1;
+        Ok(())
+    }
+
+    pub fn phases(&self) -> &[PodSalePhase] {
+        &self.phases[..self.phase_count as usize]
+    }
+
+    /// Replaces the sale phases; callers have already validated them.
+    pub fn set_phases(&mut self, phases: &[SalePhase]) {
+        let phases: Vec<PodSalePhase> = phases.iter().map(PodSalePhase::from).collect();
+        self.phase_count = fill_slots(&mut self.phases, &phases);
+    }
+
+    pub fn payout_recipients(&self) -> &[PayoutRecipient] {
+        &self.payout_recipients[..self.payout_recipient_count as usize]
+    }
+
+    /// Replaces the payout recipients; callers have already validated them.
+    pub fn set_payout_recipients(&mut self, recipients: &[PayoutRecipient]) {
+        self.payout_recipient_count = fill_slots(&mut self.payout_recipients, recipients);
+    }
+
+    /// Logged actions still held in the ring buffer, in slot order.
+    pub fn admin_log(&self) -> &[AdminLogEntry] {
+        let len = self.admin_log_count.min(MAX_ADMIN_LOG_ENTRIES as u64) as usize;
+        &self.admin_log[..len]
+    }
+
+    pub fn pricing_curve(&self) -> Option<PricingCurve> {
+        self.pricing_curve.get()
+    }
+
+    /// Converts a USDT amount into sale tokens at `tier_info`'s rate.
+    pub fn tokens_for(&self, tier_info: &TierInfo, amount: u64) -> Result<u64> {
+        let tokens_per_usdt = self
+            .pricing_curve
+            .tokens_per_usdt(self.total_contributions, self.hard_cap)
+            .unwrap_or(tier_info.tokens_per_usdt);
+        let tokens = (amount as u128)
+            .checked_mul(tokens_per_usdt as u128)
+            .ok_or(PresaleError::Overflow)?
+            / USDT_DECIMALS as u128;
+        u64::try_from(tokens).map_err(|_| error!(PresaleError::Overflow))
+    }
+
+    /// Early-bird bonus on `tokens` bought at `now`, given the raise so far.
+    pub fn early_bird_bonus(&self, tokens: u64, now: i64) -> u64 {
+        let before_cutoff = self.early_bird_cutoff != 0 && now < self.early_bird_cutoff;
+        let below_fill = self.early_bird_fill_bps != 0
+            && (self.total_contributions as u128) * 10_000
+                < (self.hard_cap as u128) * (self.early_bird_fill_bps as u128);
+
+        if !before_cutoff && !below_fill {
+            return 0;
+        }
+        ((tokens as u128) * (self.early_bird_bonus_bps as u128) / 10_000) as u64
+    }
+
+    /// Running totals of `tier`.
+    pub fn tier_totals(&self, tier: u8) -> Result<&TierTotals> {
+        self.tier_totals
+            .get(tier as usize)
+            .ok_or(error!(PresaleError::TierDoesNotExist))
+    }
+
+    pub fn tier_totals_mut(&mut self, tier: u8) -> Result<&mut TierTotals> {
+        self.tier_totals
+            .get_mut(tier as usize)
+            .ok_or(error!(PresaleError::TierDoesNotExist))
+    }
+
+    /// Counts a new member of `tier`, enforcing its optional member cap.
+    /// Returns `true` when this fills the tier's last slot.
+    pub fn add_tier_member(&mut self, registry: &Registry, tier: u8) -> Result<bool> {
+        let max_members = registry.tier_at(tier)?.max_members();
+        let totals = self.tier_totals_mut(tier)?;
+        let member_count = totals
+            .member_count
+            .checked_add(1)
+            .ok_or(PresaleError::Overflow)?;
+
+        if let Some(max_members) = max_members {
+            require!(member_count <= max_members, PresaleError::TierCapacityReached);
+        }
+
+        totals.member_count = member_count;
+
+        Ok(max_members == Some(member_count))
+    }
+
+    /// Converts `lamports` into USDT base units at the configured rate.
+    pub fn sol_to_usdt(&self, lamports: u64) -> Result<u64> {
+        let amount = (lamports as u128)
+            .checked_mul(self.sol_usdt_rate as u128)
+            .ok_or(PresaleError::Overflow)?
+            / LAMPORTS_PER_SOL as u128;
+        u64::try_from(amount).map_err(|_| error!(PresaleError::Overflow))
+    }
+
+    /// Adds `amount` to `tier`'s running total, enforcing its optional cap.
+    pub fn add_tier_contribution(&mut self, registry: &Registry, tier: u8, amount: u64) -> Result<()> {
+        let total_cap = registry.tier_at(tier)?.total_cap();
+        let totals = self.tier_totals_mut(tier)?;
+
+        totals.total_contributions = totals
+            .total_contributions
+            .checked_add(amount)
+            .ok_or(PresaleError::Overflow)?;
+
+        if let Some(total_cap) = total_cap {
+            require!(totals.total_contributions <= total_cap, PresaleError::ExceedsTierCap);
+        }
+
+        Ok(())
+    }
+
+    /// Takes `amount` off `tier`'s running total.
+    pub fn release_tier_contribution(&mut self, tier: u8, amount: u64) {
+        if let Ok(totals) = self.tier_totals_mut(tier) {
+            totals.total_contributions = totals.total_contributions.saturating_sub(amount);
+        }
+    }
+
+    pub fn remove_tier_member(&mut self, tier: u8) {
+        if let Ok(totals) = self.tier_totals_mut(tier) {
+            totals.member_count = totals.member_count.saturating_sub(1);
+        }
+    }
+
+    /// Vault holding contributions paid in `mint`, if the mint is accepted.
+    pub fn payment_vault(&self, mint: &Pubkey) -> Option<Pubkey> {
+        self.payment_mints()
+            .iter()
+            .position(|payment_mint| payment_mint == mint)
+            .map(|index| self.payment_vaults[index])
+    }
+
+    /// Outstanding contributions paid in `mint`; zero for a mint that is not
+    /// accepted.
+    pub fn payment_total(&self, mint: &Pubkey) -> u64 {
+        self.payment_mints()
+            .iter()
+            .position(|payment_mint| payment_mint == mint)
+            .map_or(0, |index| self.payment_totals[index])
+    }
+
+    /// Adds `amount` to the outstanding total of `mint`; contributions in
+    /// the native mint are tracked in `total_sol_contributions` instead.
+    pub fn add_payment_total(&mut self, mint: &Pubkey, amount: u64) -> Result<()> {
+        if let Some(index) = self.payment_mints().iter().position(|payment_mint| payment_mint == mint) {
+            let total = &mut self.payment_totals[index];
+            *total = total.checked_add(amount).ok_or(PresaleError::Overflow)?;
+        }
+        Ok(())
+    }
+
+    /// Removes `amount` from the outstanding total of `mint`.
+    pub fn release_payment_total(&mut self, mint: &Pubkey, amount: u64) -> Result<()> {
+        if let Some(index) = self.payment_mints().iter().position(|payment_mint| payment_mint == mint) {
+            let total = &mut self.payment_totals[index];
+            *total = total.checked_sub(amount).ok_or(PresaleError::Overflow)?;
+        }
+        Ok(())
+    }
+
+    /// Rank of lottery ticket `index` in the draw. Tickets are ranked by an
+    /// affine permutation of their indices keyed by the revealed seed, so
+    /// every ticket gets a distinct rank without the entrants being stored.
+    pub fn lottery_rank(&self, index: u32) -> u32 {
+        let tickets = self.lottery_ticket_count as u64;
+        if tickets <= 1 {
+            return 0;
+        }
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&self.lottery_seed[..8]);
+        // The multiplier must be coprime with the ticket count for the
+        // mapping to be a permutation.
+        let mut multiplier = u64::from_le_bytes(word) % tickets;
+        while multiplier == 0 || gcd(multiplier, tickets) != 1 {
+            multiplier = (multiplier + 1) % tickets;
+        }
+        word.copy_from_slice(&self.lottery_seed[8..16]);
+        let offset = u64::from_le_bytes(word) % tickets;
+        ((multiplier as u128 * index as u128 + offset as u128) % tickets as u128) as u32
+    }
+
+    /// Time by which `draw_lottery` must run; after it, anyone can refund
+    /// the tickets of an undrawn lottery.
+    pub fn lottery_draw_deadline(&self) -> i64 {
+        self.end_time.saturating_add(LOTTERY_DRAW_PERIOD)
+    }
+
+    /// Checks the lottery can be drawn at `now`: after the sale window and
+    /// no later than the draw deadline.
+    pub fn check_lottery_drawable(&self, now: i64) -> Result<()> {
+        require!(self.lottery_ticket_amount > 0, PresaleError::LotteryNotEnabled);
+        require!(!self.lottery_drawn.get(), PresaleError::LotteryAlreadyDrawn);
+        require!(now > self.end_time, PresaleError::SaleNotEnded);
+        require!(now <= self.lottery_draw_deadline(), PresaleError::LotteryDrawExpired);
+        Ok(())
+    }
+
+    /// Checks the draw deadline passed at `now` without a draw, so tickets
+    /// can be refunded.
+    pub fn check_lottery_expired(&self, now: i64) -> Result<()> {
+        require!(self.lottery_ticket_amount > 0, PresaleError::LotteryNotEnabled);
+        require!(!self.lottery_drawn.get(), PresaleError::LotteryAlreadyDrawn);
+        require!(now > self.lottery_draw_deadline(), PresaleError::LotteryDrawPending);
+        Ok(())
+    }
+
+    pub fn refund_window_over(&self, now: i64) -> bool {
+        self.refund_deadline != 0 && now > self.refund_deadline
+    }
+
+    /// Whether the sale is over for good: closed, with no refunds possible
+    /// any more.
+    pub fn is_settled(&self, now: i64) -> bool {
+        self.is_closed.get()
+            && !self.emergency.get()
+            && (!self.refunds_allowed.get() || self.refund_window_over(now))
+    }
+
+    /// Sets `refunds_allowed` at close and, when refunds are on and a refund
+    /// period is configured, starts the refund window.
+    pub fn open_refunds(&mut self, refunds_allowed: bool, now: i64) -> Result<()> {
+        self.refunds_allowed = refunds_allowed.into();
+        self.refund_deadline = if refunds_allowed && self.refund_period > 0 {
+            now.checked_add(self.refund_period).ok_or(PresaleError::Overflow)?
+        } else {
+            0
+        };
+        Ok(())
+    }
+
+    pub fn is_operator(&self, key: &Pubkey) -> bool {
+        *key == self.owner || self.operator() == Some(*key)
+    }
+
+    pub fn is_treasurer(&self, key: &Pubkey) -> bool {
+        *key == self.owner || self.treasurer() == Some(*key)
+    }
+
+    pub fn can_pause(&self, key: &Pubkey) -> bool {
+        *key == self.owner || self.guardian() == Some(*key)
+    }
+
+    /// Appends a privileged action to the admin log ring buffer.
+    pub fn log_admin_action(&mut self, op: AdminOp, actor: Pubkey) -> Result<()> {
+        let slot = (self.admin_log_count % MAX_ADMIN_LOG_ENTRIES as u64) as usize;
+        self.admin_log[slot] = AdminLogEntry {
+            sequence: self.admin_log_count,
+            timestamp: Clock::get()?.unix_timestamp,
+            actor,
+            op: op as u8,
+            _padding: [0; 7],
+        };
+        self.admin_log_count = self.admin_log_count.checked_add(1).ok_or(PresaleError::Overflow)?;
+        Ok(())
+    }
+
+    /// Advances the event sequence and returns the number for the event
+    /// about to be emitted. Numbers are consecutive per presale, so an
+    /// indexer seeing a gap knows it missed an event and must re-sync.
+    pub fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+
+    /// Checks that refunds can be taken after close. Emergency mode skips
+    /// the pause, close and refund window requirements.
+    pub fn check_refunds_open(&self, now: i64) -> Result<()> {
+        if self.emergency.get() {
+            return Ok(());
+        }
+        require!(!self.paused.get(), PresaleError::PresalePaused);
+        require!(self.is_closed.get(), PresaleError::PresaleNotClosed);
+        require!(self.refunds_allowed.get(), PresaleError::RefundsNotAllowed);
+        require!(!self.refund_window_over(now), PresaleError::RefundWindowClosed);
+        Ok(())
+    }
+
+    /// Checks that proceeds can be withdrawn: the sale closed with its soft
+    /// cap reached and any refund window it opened is over. A failed sale's
+    /// leftovers only leave through `sweep_unclaimed_refunds`, and refunds
+    /// opened without a deadline never close.
+    pub fn check_withdrawals_open(&self, now: i64) -> Result<()> {
+        require!(!self.paused.get(), PresaleError::PresalePaused);
+        require!(!self.emergency.get(), PresaleError::EmergencyMode);
+        require!(self.is_closed.get(), PresaleError::PresaleNotClosed);
+        require!(self.soft_cap_reached.get(), PresaleError::SoftCapNotReached);
+        require!(
+            !self.refunds_allowed.get() || self.refund_window_over(now),
+            PresaleError::RefundWindowOpen
+        );
+        require!(self.lottery_unsettled == 0, PresaleError::LotteryNotSettled);
+        require!(
+            !self.oversubscription.get() || self.allocation_finalized.get(),
+            PresaleError::AllocationNotFinalized
+        );
+        require!(self.unclaimed_excess == 0, PresaleError::ExcessNotClaimed);
+        Ok(())
+    }
+
+    /// Time after which the owner may close contributors' accounts: a
+    /// `CLAIM_PERIOD` after the later of the sale's end and its refund
+    /// deadline.
+    pub fn claim_deadline(&self) -> i64 {
+        self.end_time.max(self.refund_deadline).saturating_add(CLAIM_PERIOD)
+    }
+
+    /// Checks that `authority` may close `user`'s contribution account at
+    /// `now`. Nothing is closed before the sale closes, so a recreated
+    /// account can never take contributions, or replay the user's permits
+    /// from a reset nonce. The user may then close a `drained` account; the
+    /// user or the owner may close any account once the sale is settled,
+    /// the owner only after the claim deadline.
+    pub fn check_user_account_closable(
+        &self,
+        authority: &Pubkey,
+        user: &Pubkey,
+        drained: bool,
+        now: i64,
+    ) -> Result<()> {
+        let is_user = authority == user;
+        require!(is_user || *authority == self.owner, PresaleError::Unauthorized);
+        require!(self.is_closed.get(), PresaleError::PresaleNotClosed);
+        // `finalize_allocation` walks every account by index.
+        require!(
+            !self.oversubscription.get() || self.allocation_finalized.get(),
+            PresaleError::AllocationNotFinalized
+        );
+        if is_user && drained {
+            return Ok(());
+        }
+        require!(self.is_settled(now), PresaleError::UserAccountInUse);
+        require!(
+            is_user || now > self.claim_deadline(),
+            PresaleError::ClaimDeadlineNotReached
+        );
+        Ok(())
+    }
+
+    /// Checks that a close can still be undone: nothing has been refunded,
+    /// withdrawn or allocated since.
+    pub fn check_reopenable(&self) -> Result<()> {
+        require!(self.is_closed.get(), PresaleError::PresaleNotClosed);
+        require!(!self.is_cancelled.get(), PresaleError::PresaleCancelled);
+        require!(!self.settlement_started.get(), PresaleError::SettlementStarted);
+        require!(!self.emergency.get(), PresaleError::EmergencyMode);
+        require!(!self.allocation_finalized.get(), PresaleError::AllocationAlreadyFinalized);
+        require!(
+            self.allocation_cursor == 0 && self.allocation_requested == 0,
+            PresaleError::AllocationInProgress
+        );
+        Ok(())
+    }
+
+    pub fn in_sale_window(&self, now: i64) -> bool {
+        now >= self.start_time && now <= self.end_time
+    }
+
+    /// Returns the kind of the phase running at `now`, if any.
+    pub fn current_phase(&self, now: i64) -> Option<PhaseKind> {
+        self.phases()
+            .iter()
+            .find(|phase| now >= phase.start_time && now <= phase.end_time)
+            .map(PodSalePhase::kind)
+    }
+
+    /// Whether direct contributions are locked because a commit-reveal
+    /// round is configured and not yet over.
+    pub fn in_commit_reveal(&self, now: i64) -> bool {
+        self.commit_end != 0 && now <= self.reveal_end
+    }
+
+    /// Status to record for this presale in the registry.
+    pub fn status(&self) -> PresaleStatus {
+        if self.is_cancelled.get() {
+            PresaleStatus::Cancelled
+        } else if self.is_closed.get() {
+            PresaleStatus::Closed
+        } else {
+            PresaleStatus::Active
+        }
+    }
+}
+
+/// Tier definitions and whitelist settings of a presale, kept apart from
+/// its `SaleConfig` so contributions only ever read them. Written by the
+/// owner and operator; PDA seeded by the presale.
+#[account(zero_copy)]
+pub struct Registry {
+    /// Tier slots; other records refer to a tier by its index here.
+    pub tiers: [TierInfo; MAX_TIERS],
+    /// `balance_tier_count` of them are in use.
+    pub balance_tiers: [BalanceTier; MAX_TIERS],
+    /// Tiers whitelisted users are promoted to once their cumulative
+    /// contribution reaches the threshold; `upgrade_threshold_count` of
+    /// them are in use.
+    pub upgrade_thresholds: [UpgradeThreshold; MAX_TIERS],
+    /// Presale this registry belongs to.
+    pub presale: Pubkey,
+    pub gating_mint: Pubkey,
+    pub pass_merkle_tree: Pubkey,
+    pub kyc_issuer: Pubkey,
+    pub bump: u8,
+    /// Tier public-sale contributors are counted in, when `has_default_tier`.
+    pub default_tier: u8,
+    pub balance_tier_count: u8,
+    pub upgrade_threshold_count: u8,
+    /// Once set, no wallet can be added to the whitelist or moved between tiers.
+    pub whitelist_frozen: PodBool,
+    pub public_sale: PodBool,
+    pub has_default_tier: PodBool,
+    /// Layout version, bumped together with `SaleConfig::version`.
+    pub version: u8,
+}
+
+impl Registry {
+    pub const LEN: usize = 8 + std::mem::size_of::<Registry>();
+
+    pub fn gating_mint(&self) -> Option<Pubkey> {
+        optional_key(self.gating_mint)
+    }
+
+    pub fn pass_merkle_tree(&self) -> Option<Pubkey> {
+        optional_key(self.pass_merkle_tree)
+    }
+
+    pub fn kyc_issuer(&self) -> Option<Pubkey> {
+        optional_key(self.kyc_issuer)
+    }
+
+    pub fn default_tier(&self) -> Option<u8> {
+        self.has_default_tier.get().then_some(self.default_tier)
+    }
+
+    pub fn set_default_tier(&mut self, tier: Option<u8>) {
+        self.has_default_tier = tier.is_some().into();
+        self.default_tier = tier.unwrap_or_default();
+    }
+
+    pub fn balance_tiers(&self) -> &[BalanceTier] {
+        &self.balance_tiers[..self.balance_tier_count as usize]
+    }
+
+    /// Replaces the balance tiers; callers have already checked the count.
+    pub fn set_balance_tiers(&mut self, balance_tiers: &[BalanceTier]) {
+        self.balance_tier_count = fill_slots(&mut self.balance_tiers, balance_tiers);
+    }
+
+    pub fn upgrade_thresholds(&self) -> &[UpgradeThreshold] {
+        &self.upgrade_thresholds[..self.upgrade_threshold_count as usize]
+    }
+
+    /// Replaces the upgrade thresholds; callers have already checked the count.
+    pub fn set_upgrade_thresholds(&mut self, upgrade_thresholds: &[UpgradeThreshold]) {
+        self.upgrade_threshold_count = fill_slots(&mut self.upgrade_thresholds, upgrade_thresholds);
+    }
+
+    /// Returns the tier with the largest max contribution unlocked by holding
+    /// `balance` of the gating token, if any balance tier qualifies.
+    pub fn best_balance_tier(&self, balance: u64) -> Option<(u8, u64)> {
+        self.balance_tiers()
+            .iter()
+            .filter(|balance_tier| balance >= balance_tier.min_balance)
+            .filter_map(|balance_tier| {
+                self.tier_at(balance_tier.tier)
+                    .ok()
+                    .map(|tier_info| (balance_tier.tier, tier_info.max_contribution))
+            })
+            .max_by_key(|(_, max_contribution)| *max_contribution)
+    }
+
+    /// Returns the tier with the largest max contribution unlocked by a
+    /// cumulative contribution of `contribution`, if any threshold is met.
+    pub fn best_upgrade_tier(&self, contribution: u64) -> Option<(u8, u64)> {
+        self.upgrade_thresholds()
+            .iter()
+            .filter(|threshold| contribution >= threshold.min_contribution)
+            .filter_map(|threshold| {
+                self.tier_at(threshold.tier)
+                    .ok()
+                    .map(|tier_info| (threshold.tier, tier_info.max_contribution))
+            })
+            .max_by_key(|(_, max_contribution)| *max_contribution)
+    }
+
+    /// Index of the tier named `name`, if it exists.
+    pub fn tier_index(&self, name: &str) -> Option<u8> {
+        self.tiers
+            .iter()
+            .position(|tier_info| !tier_info.is_empty() && tier_info.name() == name)
+            .map(|index| index as u8)
+    }
+
+    /// Normalizes a tier name given as an instruction argument and returns
+    /// the index of the tier it names.
+    pub fn resolve_tier_name(&self, name: &str) -> Result<u8> {
+        let normalized_tier = name.trim().to_lowercase();
+        require!(
+            normalized_tier.len() <= MAX_TIER_NAME_LENGTH,
+            PresaleError::TierNameTooLong
+        );
+        self.tier_index(&normalized_tier)
+            .ok_or(error!(PresaleError::TierDoesNotExist))
+    }
+
+    pub fn tier_at(&self, tier: u8) -> Result<&TierInfo> {
+        self.tiers
+            .get(tier as usize)
+            .filter(|tier_info| !tier_info.is_empty())
+            .ok_or(error!(PresaleError::TierDoesNotExist))
+    }
+
+    pub fn tier_at_mut(&mut self, tier: u8) -> Result<&mut TierInfo> {
+        self.tiers
+            .get_mut(tier as usize)
+            .filter(|tier_info| !tier_info.is_empty())
+            .ok_or(error!(PresaleError::TierDoesNotExist))
+    }
+
+    pub fn has_tier(&self, name: &str) -> bool {
+        self.tier_index(name).is_some()
+    }
+
+    pub fn tier_count(&self) -> usize {
+        self.tiers.iter().filter(|tier_info| !tier_info.is_empty()).count()
+    }
+
+    /// Stores a new tier in the first free slot and returns its index.
+    pub fn insert_tier(&mut self, tier_info: TierInfo) -> Result<u8> {
+        require!(!tier_info.is_empty(), PresaleError::InvalidTierNameFormat);
+        let index = self
+            .tiers
+            .iter()
+            .position(|slot| slot.is_empty())
+            .ok_or(PresaleError::ExceedsMaxTiers)?;
+        self.tiers[index] = tier_info;
+        Ok(index as u8)
+    }
+}
+
+/// A `bool` stored as a byte, for zero-copy accounts.
+#[derive(Clone, Copy, Default, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(transparent)]
+pub struct PodBool(u8);
+
+impl PodBool {
+    pub fn get(self) -> bool {
+        self.0 != 0
+    }
+}
+
+impl From<bool> for PodBool {
+    fn from(value: bool) -> Self {
+        Self(value as u8)
+    }
+}
+
+/// An unset optional key is stored as `Pubkey::default()`.
+fn optional_key(key: Pubkey) -> Option<Pubkey> {
+    (key != Pubkey::default()).then_some(key)
+}
+
+fn padded_str(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+    std::str::from_utf8(&bytes[..len]).unwrap_or_default()
+}
+
+fn set_padded(dest: &mut [u8], value: &str) {
+    dest.fill(0);
+    dest[..value.len()].copy_from_slice(value.as_bytes());
+}
+
+/// Copies `items` to the front of `slots`, clearing the rest, and returns
+/// the number stored.
+fn fill_slots<T: Copy + bytemuck::Zeroable>(slots: &mut [T], items: &[T]) -> u8 {
+    slots.fill(T::zeroed());
+    slots[..items.len()].copy_from_slice(items);
+    items.len() as u8
+}
+
+/// Program-wide settings every presale is created under.
+#[account]
+#[derive(Default, InitSpace)]
+pub struct GlobalConfig {
+    /// Platform authority allowed to update the config.
+    pub authority: Pubkey,
+    pub platform_fee_bps: u16,
+    pub platform_fee_recipient: Pubkey,
+    /// Largest hard cap a presale may be created with; zero means no limit.
+    pub max_hard_cap: u64,
+    /// Refund period new presales start with.
+    pub default_refund_period: i64,
+    /// Contribution cooldown new presales start with.
+    pub default_contribution_cooldown: i64,
+    /// Mints presales may accept as payment; empty allows any 6-decimal mint.
+    #[max_len(16)]
+    pub allowed_payment_mints: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl GlobalConfig {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+
+    pub fn allows_payment_mint(&self, mint: &Pubkey) -> bool {
+        self.allowed_payment_mints.is_empty() || self.allowed_payment_mints.contains(mint)
+    }
+}
+
+/// Number of presales a creator has initialized; the next presale takes
+/// `sale_count` as its `sale_id`.
+#[account]
+#[derive(Default, InitSpace)]
+pub struct SaleCounter {
+    pub creator: Pubkey,
+    pub sale_count: u64,
+    pub bump: u8,
+}
+
+impl SaleCounter {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+/// Lifecycle status of a presale as tracked by the registry.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, InitSpace)]
+pub enum PresaleStatus {
+    #[default]
+    Active,
+    Closed,
+    Cancelled,
+    /// The presale account itself has been closed; the entry stays for
+    /// history.
+    AccountClosed,
+}
+
+/// A presale's record in the program-wide registry, at PDA
+/// `[b"registry_entry", index]`.
+#[account]
+#[derive(Default, InitSpace)]
+pub struct RegistryEntry {
+    /// Position among all presales, in creation order.
+    pub index: u64,
+    pub presale: Pubkey,
+    pub owner: Pubkey,
+    pub status: PresaleStatus,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl RegistryEntry {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+/// Program-wide count of the presales created through `initialize`. Each
+/// has a `RegistryEntry` at an index below `presale_count`, so indexers and
+/// front ends can discover sales without scanning accounts.
+#[account]
+#[derive(Default, InitSpace)]
+pub struct PresaleRegistry {
+    pub presale_count: u64,
+    pub bump: u8,
+}
+
+impl PresaleRegistry {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+/// Aggregate sale figures at a point in time.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct SnapshotEntry {
+    pub timestamp: i64,
+    pub total_contributions: u64,
+    pub total_sol_contributions: u64,
+    pub contributor_count: u32,
+    /// Raised per tier, indexed like `Registry::tiers`.
+    pub tier_contributions: [u64; MAX_TIERS],
+}
+
+impl SnapshotEntry {
+    pub const LEN: usize = Self::INIT_SPACE;
+
+    pub fn of(presale: &SaleConfig, timestamp: i64) -> Self {
+        let mut tier_contributions = [0u64; MAX_TIERS];
+        for (raised, totals) in tier_contributions.iter_mut().zip(presale.tier_totals.iter()) {
+            *raised = totals.total_contributions;
+        }
+        SnapshotEntry {
+            timestamp,
+            total_contributions: presale.total_contributions,
+            total_sol_contributions: presale.total_sol_contributions,
+            contributor_count: presale.contributor_count,
+            tier_contributions,
+        }
+    }
+}
+
+/// Rolling history of a presale's totals, written by the permissionless
+/// `snapshot` instruction. Keeps the last `MAX_SNAPSHOTS` entries; once
+/// full, the oldest is overwritten.
+#[account]
+#[derive(Default, InitSpace)]
+pub struct PresaleSnapshots {
+    pub presale: Pubkey,
+    #[max_len(64)]
+    pub snapshots: Vec<SnapshotEntry>,
+    /// Number of snapshots ever taken; the next one goes to
+    /// `snapshot_count % MAX_SNAPSHOTS`.
+    pub snapshot_count: u64,
+    pub bump: u8,
+}
+
+// Created through a CPI, which can allocate at most
+// `MAX_PERMITTED_DATA_INCREASE` bytes.
+const _: () = assert!(PresaleSnapshots::LEN <= MAX_PERMITTED_DATA_INCREASE);
+
+impl PresaleSnapshots {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+
+    /// Timestamp of the most recent snapshot, if any.
+    pub fn last_timestamp(&self) -> Option<i64> {
+        if self.snapshot_count == 0 {
+            return None;
+        }
+        let last = ((self.snapshot_count - 1) % MAX_SNAPSHOTS as u64) as usize;
+        self.snapshots.get(last).map(|entry| entry.timestamp)
+    }
+
+    /// Stores `entry` in the ring buffer and returns its snapshot number.
+    pub fn push(&mut self, entry: SnapshotEntry) -> Result<u64> {
+        if self.snapshots.len() < MAX_SNAPSHOTS {
+            self.snapshots.push(entry);
+        } else {
+            let slot = (self.snapshot_count % MAX_SNAPSHOTS as u64) as usize;
+            self.snapshots[slot] = entry;
+        }
+        let index = self.snapshot_count;
+        self.snapshot_count = self.snapshot_count.checked_add(1).ok_or(PresaleError::Overflow)?;
+        Ok(index)
+    }
+}
+
+/// Privileged instruction recorded in the admin log.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, InitSpace)]
+pub enum AdminOp {
+    #[default]
+    ClosePresale,
+    ReopenPresale,
+    CancelPresale,
+    WithdrawFunds,
+    WithdrawSol,
+    SetHardCap,
+    SetMinContribution,
+    SetSaleWindow,
+    SetRefundFee,
+    CreateTier,
+    RemoveTier,
+    FreezeWhitelist,
+    SetUserLimit,
+    Pause,
+    Unpause,
+    SetCouncil,
+    ProposeOwner,
+    AcceptOwnership,
+    SetOperator,
+    SetTreasurer,
+    SetGuardian,
+    SetPayoutRecipients,
+    TriggerEmergency,
+    InitiateEmergencyWithdraw,
+    ExecuteEmergencyWithdraw,
+    RescueTokens,
+    ComplianceRefund,
+    Migrate,
+    RenameTier,
+    SetPassMerkleTree,
+    CreateReceiptMint,
+    CreateTierPass,
+    MintTierPass,
+    SetPresaleMetadata,
+    DrawLottery,
+    FinalizeAllocation,
+    BulkRefund,
+    SetContributionRules,
+    SetSalePhases,
+    ConfigureLottery,
+    SetPublicSale,
+    SetPartialFill,
+    SetRefundPeriod,
+    SetRefundsRestoreCapacity,
+    SetPartialRefunds,
+    SetOversubscription,
+    SetEarlyBirdBonus,
+    SetPricingCurve,
+    SetBalanceTiers,
+    SetUpgradeThresholds,
+    SetTierMaxMembers,
+    SetReceiptNftUri,
+    SetKycIssuer,
+    QueueChange,
+    CancelChange,
+    SetTimelockDelay,
+    SetTreasury,
+    SetSolUsdtRate,
+    AddPaymentMint,
+    SetSolPriceFeed,
+    UpdateTierMaxContribution,
+    SetTierTotalCap,
+    SetContributionCooldown,
+    SetCommitRevealWindow,
+}
+
+#[zero_copy]
+#[derive(Default)]
+pub struct AdminLogEntry {
+    /// Position of the action among all logged actions.
+    pub sequence: u64,
+    pub timestamp: i64,
+    pub actor: Pubkey,
+    /// `AdminOp` discriminant.
+    pub op: u8,
+    pub _padding: [u8; 7],
+}
+
+#[zero_copy]
+pub struct TierInfo {
+    pub max_contribution: u64,
+    /// Sale token base units bought per whole USDT (1_000_000 base units).
+    pub tokens_per_usdt: u64,
+    /// Cap on the tier's combined contributions; `u64::MAX` when uncapped.
+    pub total_cap: u64,
+    /// Cap on the number of whitelisted members; `u32::MAX` when unlimited.
+    pub max_members: u32,
+    /// Normalized name, zero-padded; an all-zero name marks a free slot.
+    pub name: [u8; MAX_TIER_NAME_LENGTH],
+    pub _padding: [u8; 4],
+}
+
+impl Default for TierInfo {
+    fn default() -> Self {
+        Self {
+            max_contribution: 0,
+            tokens_per_usdt: 0,
+            total_cap: u64::MAX,
+            max_members: u32::MAX,
+            name: [0; MAX_TIER_NAME_LENGTH],
+            _padding: [0; 4],
+        }
+    }
+}
+
+impl TierInfo {
+    pub fn new(name: &str, max_contribution: u64, tokens_per_usdt: u64) -> Self {
+        let mut tier_info = Self {
+            max_contribution,
+            tokens_per_usdt,
+            ..Self::default()
+        };
+        tier_info.set_name(name);
+        tier_info
+    }
+
+    pub fn total_cap(&self) -> Option<u64> {
+        (self.total_cap != u64::MAX).then_some(self.total_cap)
+    }
+
+    pub fn set_total_cap(&mut self, total_cap: Option<u64>) {
+        self.total_cap = total_cap.unwrap_or(u64::MAX);
+    }
+
+    pub fn max_members(&self) -> Option<u32> {
+        (self.max_members != u32::MAX).then_some(self.max_members)
+    }
+
+    pub fn set_max_members(&mut self, max_members: Option<u32>) {
+        self.max_members = max_members.unwrap_or(u32::MAX);
+    }
+
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|byte| *byte == 0).unwrap_or(MAX_TIER_NAME_LENGTH);
+        std::str::from_utf8(&self.name[..len]).unwrap_or_default()
+    }
+
+    /// Stores `name`, which callers have already checked against
+    /// `MAX_TIER_NAME_LENGTH`.
+    pub fn set_name(&mut self, name: &str) {
+        self.name = [0; MAX_TIER_NAME_LENGTH];
+        self.name[..name.len()].copy_from_slice(name.as_bytes());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.name[0] == 0
+    }
+}
+
+/// Contributions and members counted in a tier so far.
+#[zero_copy]
+#[derive(Default)]
+pub struct TierTotals {
+    pub total_contributions: u64,
+    pub member_count: u32,
+    pub _padding: [u8; 4],
+}
+
+#[zero_copy]
+#[derive(Default)]
+pub struct BalanceTier {
+    pub min_balance: u64,
+    pub tier: u8,
+    pub _padding: [u8; 7],
+}
+
+impl BalanceTier {
+    pub fn new(min_balance: u64, tier: u8) -> Self {
+        Self {
+            min_balance,
+            tier,
+            ..Self::default()
+        }
+    }
+}
+
+#[zero_copy]
+#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Default, InitSpace)]
+pub struct PayoutRecipient {
+    pub wallet: Pubkey,
+    /// Share of the withdrawn amount, in basis points.
+    pub share_bps: u16,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, InitSpace)]
+pub enum PhaseKind {
+    /// Whitelisted users only, up to their assigned allocation.
+    #[default]
+    Guaranteed,
+    /// Eligible users may contribute beyond their tier max until the hard cap.
+    Fcfs,
+    /// Open to everyone under the default tier.
+    Public,
+}
+
+impl PhaseKind {
+    fn from_u8(kind: u8) -> Self {
+        match kind {
+            1 => PhaseKind::Fcfs,
+            2 => PhaseKind::Public,
+            _ => PhaseKind::Guaranteed,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace)]
+pub struct SalePhase {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub kind: PhaseKind,
+}
+
+/// `SalePhase` as stored in the presale.
+#[zero_copy]
+#[derive(Default)]
+pub struct PodSalePhase {
+    pub start_time: i64,
+    pub end_time: i64,
+    /// `PhaseKind` discriminant.
+    pub kind: u8,
+    pub _padding: [u8; 7],
+}
+
+impl PodSalePhase {
+    pub fn kind(&self) -> PhaseKind {
+        PhaseKind::from_u8(self.kind)
+    }
+}
+
+impl From<&SalePhase> for PodSalePhase {
+    fn from(phase: &SalePhase) -> Self {
+        Self {
+            start_time: phase.start_time,
+            end_time: phase.end_time,
+            kind: phase.kind as u8,
+            _padding: [0; 7],
+        }
+    }
+}
+
+impl From<&PodSalePhase> for SalePhase {
+    fn from(phase: &PodSalePhase) -> Self {
+        Self {
+            start_time: phase.start_time,
+            end_time: phase.end_time,
+            kind: phase.kind(),
+        }
+    }
+}
+
+#[zero_copy]
+#[derive(Default)]
+pub struct UpgradeThreshold {
+    pub min_contribution: u64,
+    pub tier: u8,
+    pub _padding: [u8; 7],
+}
+
+impl UpgradeThreshold {
+    pub fn new(min_contribution: u64, tier: u8) -> Self {
+        Self {
+            min_contribution,
+            tier,
+            ..Self::default()
+        }
+    }
+}
+
+/// One `bulk_assign_tiers` item: 33 bytes on the wire.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct TierAssignment {
+    pub user: Pubkey,
+    pub tier: u8,
+}
+
+#[account]
+#[derive(Default, InitSpace)]
+pub struct WhitelistEntry {
+    pub presale: Pubkey,
+    pub user: Pubkey,
+    pub tier: u8,
+    /// The tier's limit when the entry was written. Informational only:
+    /// contributions are checked against the tier's current limit.
+    pub max_contribution: u64,
+    pub valid_from: Option<i64>,
+    pub valid_until: Option<i64>,
+    pub bump: u8,
+}
+
+impl WhitelistEntry {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+#[account]
+#[derive(Default, InitSpace)]
+pub struct BlacklistEntry {
+    pub presale: Pubkey,
+    pub user: Pubkey,
+    pub bump: u8,
+}
+
+impl BlacklistEntry {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+#[account]
+#[derive(Default, InitSpace)]
+pub struct RegistrationRequest {
+    pub presale: Pubkey,
+    pub user: Pubkey,
+    pub requested_at: i64,
+    pub bump: u8,
+}
+
+impl RegistrationRequest {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+#[account]
+#[derive(Default, InitSpace)]
+pub struct WhitelistAuditTrail {
+    pub presale: Pubkey,
+    pub user: Pubkey,
+    #[max_len(16)]
+    pub records: Vec<AuditRecord>,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace)]
+pub struct AuditRecord {
+    pub assigner: Pubkey,
+    pub tier: u8,
+    pub timestamp: i64,
+}
+
+impl AuditRecord {
+    pub const LEN: usize = Self::INIT_SPACE;
+}
+
+impl WhitelistAuditTrail {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+/// A non-transferable KYC credential for `user`, issued by `issuer`. It is
+/// not tied to a single presale so one attestation can gate many sales.
+#[account]
+#[derive(Default, InitSpace)]
+pub struct KycAttestation {
+    pub issuer: Pubkey,
+    pub user: Pubkey,
+    pub issued_at: i64,
+    pub expires_at: Option<i64>,
+    pub bump: u8,
+}
+
+impl KycAttestation {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+/// A sealed contribution made during the commit phase. `escrowed` is held in
+/// the presale vault until the user reveals the committed amount.
+#[account]
+#[derive(Default, InitSpace)]
+pub struct ContributionCommitment {
+    pub presale: Pubkey,
+    pub user: Pubkey,
+    pub payment_mint: Pubkey,
+    /// sha256(amount_le || salt || user)
+    pub commitment: [u8; 32],
+    pub escrowed: u64,
+    pub bump: u8,
+}
+
+impl ContributionCommitment {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+/// An escrowed lottery ticket, settled after the draw into either a
+/// contribution (winners) or a refund (losers).
+#[account]
+#[derive(Default, InitSpace)]
+pub struct LotteryTicket {
+    pub presale: Pubkey,
+    pub user: Pubkey,
+    pub payment_mint: Pubkey,
+    pub tier: u8,
+    pub amount: u64,
+    pub bump: u8,
+    /// Position of the ticket among the presale's tickets, ranked by
+    /// `SaleConfig::lottery_rank` at the draw.
+    pub index: u32,
+}
+
+impl LotteryTicket {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+/// Per-user contribution record, created on the user's first contribution
+/// (or when the owner sets a limit for the wallet). It is the only ledger of
+/// what the wallet paid and bought; the presale keeps aggregates alone.
+#[account]
+#[derive(Default, InitSpace)]
+pub struct ContributionAccount {
+    pub presale: Pubkey,
+    pub user: Pubkey,
+    /// Current contribution, in USDT units, net of refunds.
+    pub amount: u64,
+    /// Index of the tier the contribution is counted in.
+    pub tier: u8,
+    /// Set once the whole contribution has been refunded.
+    pub refunded: bool,
+    pub first_contribution_at: i64,
+    pub last_contribution_at: i64,
+    pub bump: u8,
+    /// Sale tokens bought, priced at the tier's rate.
+    pub purchased_tokens: u64,
+    /// Early-bird bonus tokens owed on top of `purchased_tokens`.
+    pub bonus_tokens: u64,
+    /// Refunded amounts still counted against the maximum contribution
+    /// when `refunds_restore_capacity` is unset.
+    pub refunded_amount: u64,
+    /// Contribution per payment mint, so refunds can return the exact mint
+    /// that was paid. SOL is booked under the native mint at its USDT value.
+    pub mint_contributions: [MintContribution; MAX_MINTS_PER_USER],
+    /// Lamports paid through `contribute_sol`.
+    pub sol_contribution: u64,
+    /// Next unused nonce for the user's signed contribution permits.
+    pub permit_nonce: u64,
+    /// Negotiated maximum contribution that replaces the resolved tier's
+    /// limit.
+    pub user_limit: Option<u64>,
+    /// Creation order within the presale; `finalize_allocation` walks the
+    /// accounts by it.
+    pub index: u32,
+}
+
+impl ContributionAccount {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+
+    /// Contribution currently booked in `mint`.
+    pub fn mint_contribution(&self, mint: &Pubkey) -> u64 {
+        self.mint_contributions
+            .iter()
+            .find(|entry| entry.mint == *mint)
+            .map_or(0, |entry| entry.amount)
+    }
+
+    /// The entry for `mint`, claiming a free slot on its first use.
+    pub fn mint_entry_mut(&mut self, mint: &Pubkey) -> Result<&mut MintContribution> {
+        let index = match self.mint_contributions.iter().position(|entry| entry.mint == *mint) {
+            Some(index) => index,
+            None => {
+                let index = self
+                    .mint_contributions
+                    .iter()
+                    .position(|entry| entry.is_free())
+                    .ok_or(PresaleError::ExceedsMaxPaymentMints)?;
+                self.mint_contributions[index] = MintContribution {
+                    mint: *mint,
+                    ..Default::default()
+                };
+                index
+            }
+        };
+        Ok(&mut self.mint_contributions[index])
+    }
+
+    /// Whether anything is still owed to the wallet: a contribution, SOL
+    /// or unclaimed allocation excess.
+    pub fn has_balance(&self) -> bool {
+        self.amount > 0
+            || self.sol_contribution > 0
+            || self.mint_contributions.iter().any(|entry| entry.amount > 0 || entry.excess > 0)
+    }
+
+    /// Clears and returns the allocation excess owed back in `mint`.
+    pub fn take_excess(&mut self, mint: &Pubkey) -> u64 {
+        self.mint_contributions
+            .iter_mut()
+            .find(|entry| entry.mint == *mint)
+            .map_or(0, |entry| std::mem::take(&mut entry.excess))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct MintContribution {
+    pub mint: Pubkey,
+    /// Contribution paid in `mint`, in USDT units.
+    pub amount: u64,
+    /// Part of the contribution not accepted by `finalize_allocation`,
+    /// owed back through `claim_excess`.
+    pub excess: u64,
+}
+
+impl MintContribution {
+    pub fn is_free(&self) -> bool {
+        self.amount == 0 && self.excess == 0
+    }
+}
+
+/// Per-user refund record, created on the user's first refund.
+#[account]
+#[derive(Default, InitSpace)]
+pub struct RefundTicket {
+    pub presale: Pubkey,
+    pub user: Pubkey,
+    /// Total refunded across all refunds, in USDT units.
+    pub refunded_amount: u64,
+    pub refund_count: u32,
+    pub fully_refunded: bool,
+    pub last_refund_at: i64,
+    pub bump: u8,
+}
+
+impl RefundTicket {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+/// A sensitive instruction, with its arguments, that the council approves.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Default, InitSpace)]
+pub enum AdminAction {
+    #[default]
+    WithdrawSol,
+    WithdrawFunds { payment_mint: Pubkey, amount: Option<u64> },
+    ClosePresale { refunds_allowed: bool },
+    SetHardCap { new_hard_cap: u64 },
+    SetCouncil {
+        #[max_len(7)]
+        members: Vec<Pubkey>,
+        threshold: u8,
+    },
+    EmergencyWithdraw { destination: Pubkey },
+    SetPayoutRecipients {
+        #[max_len(5)]
+        recipients: Vec<PayoutRecipient>,
+    },
+}
+
+impl AdminAction {
+    pub const LEN: usize = Self::INIT_SPACE;
+}
+
+#[account]
+#[derive(Default, InitSpace)]
+pub struct AdminProposal {
+    pub presale: Pubkey,
+    pub index: u64,
+    pub action: AdminAction,
+    #[max_len(7)]
+    pub approvals: Vec<Pubkey>,
+    pub executed: bool,
+    pub bump: u8,
+}
+
+impl AdminProposal {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+/// A sensitive parameter change, with its arguments, held by the timelock.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum ParamChange {
+    HardCap { new_hard_cap: u64 },
+    MinContribution { new_min: u64 },
+    CreateTier {
+        #[max_len(32)]
+        tier_name: String,
+        max_contribution: u64,
+        tokens_per_usdt: u64,
+    },
+    TierTotalCap {
+        #[max_len(32)]
+        tier_name: String,
+        total_cap: Option<u64>,
+    },
+    TierMaxContribution {
+        #[max_len(32)]
+        tier_name: String,
+        max_contribution: u64,
+    },
+    TierMaxMembers {
+        #[max_len(32)]
+        tier_name: String,
+        max_members: Option<u32>,
+    },
+    TimelockDelay { delay: i64 },
+    RemoveTier {
+        #[max_len(32)]
+        tier_name: String,
+    },
+    RenameTier {
+        #[max_len(32)]
+        old_tier_name: String,
+        #[max_len(32)]
+        new_tier_name: String,
+    },
+    BalanceTiers {
+        gating_mint: Option<Pubkey>,
+        #[max_len(10, 32)]
+        tier_names: Vec<String>,
+        #[max_len(10)]
+        min_balances: Vec<u64>,
+    },
+    UpgradeThresholds {
+        #[max_len(10, 32)]
+        tier_names: Vec<String>,
+        #[max_len(10)]
+        min_contributions: Vec<u64>,
+    },
+}
+
+impl Default for ParamChange {
+    fn default() -> Self {
+        ParamChange::MinContribution { new_min: 0 }
+    }
+}
+
+impl ParamChange {
+    pub const LEN: usize = Self::INIT_SPACE;
+}
+
+#[account]
+#[derive(Default, InitSpace)]
+pub struct QueuedChange {
+    pub presale: Pubkey,
+    pub index: u64,
+    pub change: ParamChange,
+    /// Earliest time the change can be applied.
+    pub eta: i64,
+    pub bump: u8,
+}
+
+impl QueuedChange {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sale closed at time 0 with its soft cap reached and no refunds.
+    fn closed_sale() -> Box<SaleConfig> {
+        let mut presale: Box<SaleConfig> = bytemuck::zeroed_box();
+        presale.soft_cap = 1_000;
+        presale.total_contributions = 1_000;
+        presale.is_closed = true.into();
+        presale.soft_cap_reached = true.into();
+        presale
+    }
+
+    #[test]
+    fn withdrawals_open_once_a_successful_sale_closes() {
+        let presale = closed_sale();
+        presale.check_withdrawals_open(0).unwrap();
+
+        let mut presale = closed_sale();
+        presale.is_closed = false.into();
+        let err = presale.check_withdrawals_open(0).err().unwrap();
+        assert_eq!(err, PresaleError::PresaleNotClosed.into());
+    }
+
+    #[test]
+    fn withdrawals_follow_the_soft_cap_recorded_at_close() {
+        let mut presale = closed_sale();
+        presale.total_contributions = 0;
+        presale.check_withdrawals_open(0).unwrap();
+
+        let mut presale = closed_sale();
+        presale.soft_cap_reached = false.into();
+        presale.open_refunds(true, 0).unwrap();
+        presale.refund_deadline = 10;
+        let err = presale.check_withdrawals_open(11).err().unwrap();
+        assert_eq!(err, PresaleError::SoftCapNotReached.into());
+    }
+
+    #[test]
+    fn withdrawals_wait_for_the_refund_window() {
+        let mut presale = closed_sale();
+        presale.refund_period = 10;
+        presale.open_refunds(true, 0).unwrap();
+
+        let err = presale.check_withdrawals_open(10).err().unwrap();
+        assert_eq!(err, PresaleError::RefundWindowOpen.into());
+        presale.check_withdrawals_open(11).unwrap();
+    }
+
+    #[test]
+    fn refunds_without_a_deadline_block_withdrawals_for_good() {
+        let mut presale = closed_sale();
+        presale.open_refunds(true, 0).unwrap();
+
+        assert_eq!(presale.refund_deadline, 0);
+        let err = presale.check_withdrawals_open(i64::MAX).err().unwrap();
+        assert_eq!(err, PresaleError::RefundWindowOpen.into());
+    }
+
+    #[test]
+    fn closed_sales_reopen_until_settlement_starts() {
+        let presale = closed_sale();
+        presale.check_reopenable().unwrap();
+
+        let mut presale = closed_sale();
+        presale.settlement_started = true.into();
+        let err = presale.check_reopenable().err().unwrap();
+        assert_eq!(err, PresaleError::SettlementStarted.into());
+
+        let mut presale = closed_sale();
+        presale.is_cancelled = true.into();
+        let err = presale.check_reopenable().err().unwrap();
+        assert_eq!(err, PresaleError::PresaleCancelled.into());
+    }
+
+    #[test]
+    fn started_allocations_block_reopening() {
+        let mut presale = closed_sale();
+        presale.allocation_requested = 1_000;
+        let err = presale.check_reopenable().err().unwrap();
+        assert_eq!(err, PresaleError::AllocationInProgress.into());
+
+        let mut presale = closed_sale();
+        presale.allocation_cursor = 1;
+        let err = presale.check_reopenable().err().unwrap();
+        assert_eq!(err, PresaleError::AllocationInProgress.into());
+
+        let mut presale = closed_sale();
+        presale.allocation_finalized = true.into();
+        let err = presale.check_reopenable().err().unwrap();
+        assert_eq!(err, PresaleError::AllocationAlreadyFinalized.into());
+    }
+
+    #[test]
+    fn only_the_user_or_the_owner_closes_user_accounts() {
+        let mut presale = closed_sale();
+        presale.owner = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+
+        let err = presale.check_user_account_closable(&stranger, &user, true, 0).err().unwrap();
+        assert_eq!(err, PresaleError::Unauthorized.into());
+        presale.check_user_account_closable(&user, &user, false, 0).unwrap();
+    }
+
+    #[test]
+    fn user_accounts_stay_open_until_the_sale_closes() {
+        let mut presale = closed_sale();
+        presale.is_closed = false.into();
+        let user = Pubkey::new_unique();
+
+        // A recreated account would start over at permit nonce zero.
+        let err = presale.check_user_account_closable(&user, &user, true, 0).err().unwrap();
+        assert_eq!(err, PresaleError::PresaleNotClosed.into());
+
+        let mut presale = closed_sale();
+        presale.oversubscription = true.into();
+        let err = presale.check_user_account_closable(&user, &user, true, 0).err().unwrap();
+        assert_eq!(err, PresaleError::AllocationNotFinalized.into());
+    }
+
+    #[test]
+    fn users_close_drained_accounts_during_the_refund_window() {
+        let mut presale = closed_sale();
+        presale.refund_period = 10;
+        presale.open_refunds(true, 0).unwrap();
+        let user = Pubkey::new_unique();
+
+        presale.check_user_account_closable(&user, &user, true, 5).unwrap();
+        let err = presale.check_user_account_closable(&user, &user, false, 5).err().unwrap();
+        assert_eq!(err, PresaleError::UserAccountInUse.into());
+        presale.check_user_account_closable(&user, &user, false, 11).unwrap();
+    }
+
+    #[test]
+    fn owners_close_user_accounts_after_the_claim_deadline() {
+        let mut presale = closed_sale();
+        presale.owner = Pubkey::new_unique();
+        presale.refund_period = 10;
+        presale.open_refunds(true, 0).unwrap();
+        let owner = presale.owner;
+        let user = Pubkey::new_unique();
+
+        assert_eq!(presale.claim_deadline(), 10 + CLAIM_PERIOD);
+        let err = presale
+            .check_user_account_closable(&owner, &user, true, 10 + CLAIM_PERIOD)
+            .err()
+            .unwrap();
+        assert_eq!(err, PresaleError::ClaimDeadlineNotReached.into());
+        presale.check_user_account_closable(&owner, &user, false, 11 + CLAIM_PERIOD).unwrap();
+    }
+
+    #[test]
+    fn lottery_ranks_are_a_permutation_of_the_tickets() {
+        let mut presale = closed_sale();
+        for (tickets, byte) in [(1u32, 0u8), (2, 7), (10, 3), (97, 255), (360, 42)] {
+            presale.lottery_ticket_count = tickets;
+            presale.lottery_seed = [byte; 32];
+            let mut ranks: Vec<u32> = (0..tickets).map(|index| presale.lottery_rank(index)).collect();
+            ranks.sort_unstable();
+            assert_eq!(ranks, (0..tickets).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn lotteries_are_drawn_by_the_deadline_or_refunded() {
+        let mut presale = closed_sale();
+        presale.end_time = 100;
+        presale.lottery_ticket_amount = 50;
+        let deadline = presale.lottery_draw_deadline();
+        assert_eq!(deadline, 100 + LOTTERY_DRAW_PERIOD);
+
+        let err = presale.check_lottery_drawable(100).err().unwrap();
+        assert_eq!(err, PresaleError::SaleNotEnded.into());
+        presale.check_lottery_drawable(deadline).unwrap();
+        let err = presale.check_lottery_drawable(deadline + 1).err().unwrap();
+        assert_eq!(err, PresaleError::LotteryDrawExpired.into());
+
+        let err = presale.check_lottery_expired(deadline).err().unwrap();
+        assert_eq!(err, PresaleError::LotteryDrawPending.into());
+        presale.check_lottery_expired(deadline + 1).unwrap();
+
+        presale.lottery_drawn = true.into();
+        let err = presale.check_lottery_expired(deadline + 1).err().unwrap();
+        assert_eq!(err, PresaleError::LotteryAlreadyDrawn.into());
+    }
+}